@@ -0,0 +1,7 @@
+-- Per-server role requirements for slash commands
+CREATE TABLE CommandPermission (
+  serverId INTEGER,
+  commandName TEXT,
+  roleId INTEGER,
+  PRIMARY KEY(serverId, commandName, roleId)
+);
@@ -0,0 +1,6 @@
+-- Users who asked not to be tracked
+CREATE TABLE OptOut (
+  userId INTEGER,
+  optedOutAt INTEGER,
+  PRIMARY KEY(userId)
+);
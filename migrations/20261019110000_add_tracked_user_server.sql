@@ -0,0 +1,8 @@
+-- Servers in which a user was added to the monitoring list
+CREATE TABLE TrackedUserServer (
+  userId INTEGER,
+  serverId INTEGER,
+  addedAt INTEGER,
+  PRIMARY KEY(userId, serverId),
+  FOREIGN KEY(userId) REFERENCES User(discordId) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Per-server limits on how long history is kept
+-- A NULL limit disables that limit for the server
+CREATE TABLE RetentionPolicy (
+  serverId INTEGER,
+  maxAgeDays INTEGER,
+  maxEntries INTEGER,
+  PRIMARY KEY(serverId)
+);
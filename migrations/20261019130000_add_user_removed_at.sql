@@ -0,0 +1,2 @@
+-- Soft-delete for monitored users: NULL while tracked, removal timestamp during the grace period
+ALTER TABLE User ADD COLUMN removedAt INTEGER;
@@ -0,0 +1,14 @@
+-- One row per update pass: inserted when the pass starts, completed with its counts when it finishes
+CREATE TABLE UpdateRun (
+  id INTEGER,
+  entityType TEXT,
+  triggeredBy TEXT,
+  startedAt INTEGER,
+  finishedAt INTEGER,
+  checked INTEGER,
+  changes INTEGER,
+  failures INTEGER,
+  PRIMARY KEY(id)
+);
+
+CREATE INDEX idx_update_run_started_at ON UpdateRun(startedAt);
@@ -0,0 +1,13 @@
+-- Outcome of the latest checks of every monitored user and server
+-- nextCheckAt is set while an entity that keeps failing is backed off; NULL means check on every pass
+ALTER TABLE User ADD COLUMN lastCheckedAt INTEGER;
+ALTER TABLE User ADD COLUMN lastSuccessAt INTEGER;
+ALTER TABLE User ADD COLUMN consecutiveFailures INTEGER DEFAULT 0;
+ALTER TABLE User ADD COLUMN lastError TEXT;
+ALTER TABLE User ADD COLUMN nextCheckAt INTEGER;
+
+ALTER TABLE Server ADD COLUMN lastCheckedAt INTEGER;
+ALTER TABLE Server ADD COLUMN lastSuccessAt INTEGER;
+ALTER TABLE Server ADD COLUMN consecutiveFailures INTEGER DEFAULT 0;
+ALTER TABLE Server ADD COLUMN lastError TEXT;
+ALTER TABLE Server ADD COLUMN nextCheckAt INTEGER;
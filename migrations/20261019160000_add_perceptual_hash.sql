@@ -0,0 +1,4 @@
+-- Perceptual hash of archived images so visually identical pictures can be found across users
+-- NULL for images archived before the column existed or that could not be decoded
+ALTER TABLE ProfilePicture ADD COLUMN perceptualHash TEXT;
+ALTER TABLE ServerPicture ADD COLUMN perceptualHash TEXT;
@@ -0,0 +1,12 @@
+-- Channel per server that is alerted when a tracked user takes on another tracked user's avatar or name
+CREATE TABLE AlertChannel (
+  serverId INTEGER,
+  channelId INTEGER,
+  PRIMARY KEY(serverId)
+);
+
+-- How far the alert job has scanned the history tables; a single row
+CREATE TABLE AlertScan (
+  id INTEGER PRIMARY KEY CHECK (id = 1),
+  scannedUntil INTEGER NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Links that delete archived images, as handed out by the image store on upload
+CREATE TABLE ArchivedImage (
+  link TEXT PRIMARY KEY,
+  deleteUrl TEXT NOT NULL
+);
+
+-- Archived images no history row references anymore that still have to be deleted from the image store
+CREATE TABLE PendingImageDeletion (
+  link TEXT PRIMARY KEY,
+  deleteUrl TEXT,
+  queuedAt INTEGER NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  lastError TEXT
+);
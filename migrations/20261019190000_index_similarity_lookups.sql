@@ -0,0 +1,21 @@
+-- Display name reduced by alerts::normalize_name, so look-alike names are found through an index
+-- NULL for names recorded before the column existed until the alert job fills it in
+ALTER TABLE UsernameChange ADD COLUMN normalizedName TEXT;
+CREATE INDEX idx_UsernameChange_normalizedName ON UsernameChange(normalizedName);
+CREATE INDEX idx_UsernameChange_userId_changedAt ON UsernameChange(userId, changedAt);
+CREATE INDEX idx_UsernameChange_changedAt ON UsernameChange(changedAt);
+
+-- A user's next picture and the pictures recorded since the last alert scan
+CREATE INDEX idx_ProfilePicture_userId_changedAt ON ProfilePicture(userId, changedAt);
+CREATE INDEX idx_ProfilePicture_changedAt ON ProfilePicture(changedAt);
+
+-- Perceptual hashes within NEAR_MATCH_DISTANCE (6) of 64 bits share at least one of their 8 bytes,
+-- so similar pictures are looked up by byte instead of comparing every stored hash
+CREATE INDEX idx_ProfilePicture_hash_byte0 ON ProfilePicture(substr(perceptualHash, 1, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte1 ON ProfilePicture(substr(perceptualHash, 3, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte2 ON ProfilePicture(substr(perceptualHash, 5, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte3 ON ProfilePicture(substr(perceptualHash, 7, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte4 ON ProfilePicture(substr(perceptualHash, 9, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte5 ON ProfilePicture(substr(perceptualHash, 11, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte6 ON ProfilePicture(substr(perceptualHash, 13, 2));
+CREATE INDEX idx_ProfilePicture_hash_byte7 ON ProfilePicture(substr(perceptualHash, 15, 2));
@@ -0,0 +1,86 @@
+// ABOUTME: Maintenance subcommands for the SQLite file itself
+// ABOUTME: Applies migrations, writes consistent backups with VACUUM INTO and reclaims unused space
+use std::path::Path;
+
+use sqlx::SqlitePool;
+
+use crate::cli::CliError;
+use crate::util::format::format_bytes;
+
+/// Reports the latest migration; pending ones were applied when connecting.
+pub async fn migrate(database: &SqlitePool) -> Result<(), CliError> {
+    let latest = sqlx::query_as::<_, (i64, String)>(
+        "SELECT version, description FROM _sqlx_migrations ORDER BY version DESC LIMIT 1",
+    )
+    .fetch_optional(database)
+    .await?;
+
+    match latest {
+        Some((version, description)) => {
+            println!(
+                "Database is up to date at migration {} ({})",
+                version, description
+            )
+        }
+        None => println!("Database is up to date"),
+    }
+
+    Ok(())
+}
+
+/// Writes a consistent copy of the database while the bot may keep running.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `path` - File to create; an existing file is never overwritten
+pub async fn backup(database: &SqlitePool, path: &Path) -> Result<(), CliError> {
+    if path.exists() {
+        return Err(CliError::InvalidInput(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+
+    let target = path.to_str().ok_or_else(|| {
+        CliError::InvalidInput(format!("{} is not a valid UTF-8 path", path.display()))
+    })?;
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(target)
+        .execute(database)
+        .await?;
+
+    let size = std::fs::metadata(path)?.len();
+    println!(
+        "Backed up database to {} ({})",
+        path.display(),
+        format_bytes(size as i64)
+    );
+
+    Ok(())
+}
+
+/// Rebuilds the database file, returning the space freed by deleted history.
+pub async fn vacuum(database: &SqlitePool) -> Result<(), CliError> {
+    let before = database_size(database).await?;
+
+    sqlx::query("VACUUM").execute(database).await?;
+
+    let after = database_size(database).await?;
+    println!(
+        "Vacuumed database from {} to {}",
+        format_bytes(before),
+        format_bytes(after)
+    );
+
+    Ok(())
+}
+
+/// Size of the database file in bytes.
+async fn database_size(database: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(database)
+    .await
+}
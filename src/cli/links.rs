@@ -0,0 +1,77 @@
+// ABOUTME: Maintenance subcommand that checks the archived image links
+// ABOUTME: Requests every distinct link on the image store and lists the ones that no longer resolve
+use std::time::Duration;
+
+use futures::{stream, StreamExt};
+use sqlx::SqlitePool;
+
+use crate::cli::CliError;
+
+/// Links checked at the same time.
+const CONCURRENT_CHECKS: usize = 8;
+
+/// Time a single link may take to answer.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Checks that every archived image can still be downloaded.
+///
+/// # Returns
+/// * `Result<(), CliError>` - `CliError::Failed` if at least one link is broken
+pub async fn verify_links(database: &SqlitePool) -> Result<(), CliError> {
+    let links = sqlx::query!(
+        r#"SELECT link AS "link!: String", MIN(entityType) AS "entity_type!: String", MIN(entityId) AS "entity_id!: i64"
+        FROM (
+            SELECT link, 'user' AS entityType, userId AS entityId FROM ProfilePicture WHERE link IS NOT NULL
+            UNION ALL
+            SELECT link, 'server' AS entityType, serverId AS entityId FROM ServerPicture WHERE link IS NOT NULL
+        )
+        GROUP BY link
+        ORDER BY link"#
+    )
+    .fetch_all(database)
+    .await?;
+
+    println!("Checking {} archived links", links.len());
+
+    let client = reqwest::Client::builder()
+        .timeout(CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| CliError::Failed(format!("Unable to create HTTP client: {}", e)))?;
+
+    let total = links.len();
+    let mut broken = 0;
+
+    let mut results = stream::iter(links)
+        .map(|row| {
+            let client = &client;
+            async move {
+                let result = match client.head(&row.link).send().await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(response.status().to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                (row, result)
+            }
+        })
+        .buffer_unordered(CONCURRENT_CHECKS);
+
+    while let Some((row, result)) = results.next().await {
+        if let Err(reason) = result {
+            broken += 1;
+            println!(
+                "Broken: {} ({} {}): {}",
+                row.link, row.entity_type, row.entity_id, reason
+            );
+        }
+    }
+
+    if broken > 0 {
+        return Err(CliError::Failed(format!(
+            "{} of {} archived links are broken",
+            broken, total
+        )));
+    }
+
+    println!("All {} archived links are reachable", total);
+    Ok(())
+}
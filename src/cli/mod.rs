@@ -0,0 +1,219 @@
+// ABOUTME: Command line interface of the pfp-checker binary
+// ABOUTME: Runs the bot by default and offers maintenance subcommands that work on the database without a Discord gateway
+mod database;
+mod links;
+mod transfer;
+mod users;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use sqlx::SqlitePool;
+
+use crate::db::connection::establish_connection;
+use crate::util::config;
+
+/// Archives Discord profile pictures, usernames and server icons.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Runs the bot (the default without a subcommand)
+    Run,
+    /// Asks a running bot whether it is ready, for Docker's HEALTHCHECK
+    Healthcheck,
+    /// Applies pending database migrations
+    Migrate,
+    /// Writes a consistent copy of the database to a new file
+    Backup {
+        /// File to create
+        path: PathBuf,
+    },
+    /// Exports all tracked users, servers and their history as JSON
+    Export {
+        /// File to create
+        path: PathBuf,
+    },
+    /// Imports a JSON export, keeping rows that already exist
+    Import {
+        /// File written by `export`
+        path: PathBuf,
+    },
+    /// Adds a user to the monitoring list
+    AddUser {
+        /// Discord ID of the user
+        user_id: u64,
+        /// Server the user is tracked for, used by retention policies and data exports
+        #[arg(long)]
+        server: Option<u64>,
+    },
+    /// Removes a user from the monitoring list; their history is purged after the grace period
+    RemoveUser {
+        /// Discord ID of the user
+        user_id: u64,
+    },
+    /// Checks a monitored user or server right away, ignoring any backoff
+    CheckNow {
+        /// Discord ID of the user or server
+        id: u64,
+    },
+    /// Rebuilds the database file to reclaim unused space
+    Vacuum,
+    /// Checks that every archived image link can still be downloaded
+    VerifyLinks,
+}
+
+/// Error of a maintenance subcommand, printed before exiting with status 1.
+#[derive(Debug)]
+pub enum CliError {
+    /// A required environment variable is missing.
+    Config(String),
+    /// A database query failed.
+    Database(sqlx::Error),
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// An export could not be written or read.
+    Json(serde_json::Error),
+    /// An argument cannot be used.
+    InvalidInput(String),
+    /// The subcommand ran but found a problem, e.g. broken links or an unhealthy bot.
+    Failed(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Config(reason) => write!(f, "Configuration error: {}", reason),
+            CliError::Database(err) => write!(f, "Database error: {}", err),
+            CliError::Io(err) => write!(f, "I/O error: {}", err),
+            CliError::Json(err) => write!(f, "Invalid export: {}", err),
+            CliError::InvalidInput(reason) => write!(f, "Invalid input: {}", reason),
+            CliError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Database(err) => Some(err),
+            CliError::Io(err) => Some(err),
+            CliError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for CliError {
+    fn from(err: sqlx::Error) -> Self {
+        CliError::Database(err)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(err: serde_json::Error) -> Self {
+        CliError::Json(err)
+    }
+}
+
+/// Runs a maintenance subcommand.
+///
+/// `run` starts the bot and is handled by `main` before getting here.
+///
+/// # Arguments
+/// * `command` - The parsed subcommand
+///
+/// # Returns
+/// * `Result<(), CliError>` - Ok if successful, error otherwise
+pub async fn run(command: CliCommand) -> Result<(), CliError> {
+    match command {
+        CliCommand::Run => unreachable!("the bot is started by main"),
+        CliCommand::Healthcheck => {
+            return crate::util::health::probe(config::http_addr())
+                .await
+                .map_err(|reason| CliError::Failed(format!("Not healthy: {}", reason)));
+        }
+        CliCommand::CheckNow { id } => return users::check_now(to_id(id)?).await,
+        _ => {}
+    }
+
+    let database = connect().await?;
+
+    match command {
+        CliCommand::Migrate => database::migrate(&database).await,
+        CliCommand::Backup { path } => database::backup(&database, &path).await,
+        CliCommand::Export { path } => transfer::export(&database, &path).await,
+        CliCommand::Import { path } => transfer::import(&database, &path).await,
+        CliCommand::AddUser { user_id, server } => {
+            let server_id = server.map(to_id).transpose()?;
+            users::add_user(&database, to_id(user_id)?, server_id).await
+        }
+        CliCommand::RemoveUser { user_id } => users::remove_user(&database, to_id(user_id)?).await,
+        CliCommand::Vacuum => database::vacuum(&database).await,
+        CliCommand::VerifyLinks => links::verify_links(&database).await,
+        CliCommand::Run | CliCommand::Healthcheck | CliCommand::CheckNow { .. } => unreachable!(),
+    }
+}
+
+/// Opens the database from `DATABASE_URL`, applying pending migrations.
+async fn connect() -> Result<Arc<SqlitePool>, CliError> {
+    let database_url = config::database_url()
+        .map_err(|_| CliError::Config("DATABASE_URL must be set".to_string()))?;
+
+    Ok(establish_connection(&database_url).await?)
+}
+
+/// Converts a Discord ID to the signed integer stored in the database.
+fn to_id(id: u64) -> Result<i64, CliError> {
+    i64::try_from(id).map_err(|_| CliError::InvalidInput(format!("{} is not a Discord ID", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_subcommand_runs_the_bot() {
+        let cli = Cli::try_parse_from(["pfp-checker"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_parse_add_user_with_server() {
+        let cli =
+            Cli::try_parse_from(["pfp-checker", "add-user", "123", "--server", "456"]).unwrap();
+
+        match cli.command {
+            Some(CliCommand::AddUser { user_id, server }) => {
+                assert_eq!(user_id, 123);
+                assert_eq!(server, Some(456));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_now_requires_id() {
+        assert!(Cli::try_parse_from(["pfp-checker", "check-now"]).is_err());
+        assert!(Cli::try_parse_from(["pfp-checker", "check-now", "abc"]).is_err());
+    }
+
+    #[test]
+    fn test_to_id_rejects_out_of_range() {
+        assert_eq!(to_id(42).unwrap(), 42);
+        assert!(to_id(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,426 @@
+// ABOUTME: Export and import of the tracking data as a single JSON document
+// ABOUTME: Moves users, servers, their history and per-server settings between databases
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::cli::CliError;
+use crate::util::alerts;
+
+/// Format version written by [`export`]; [`import`] refuses other versions.
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    version: u32,
+    exported_at: i64,
+    users: Vec<UserRow>,
+    profile_pictures: Vec<PictureRow>,
+    usernames: Vec<UsernameRow>,
+    servers: Vec<ServerRow>,
+    server_pictures: Vec<PictureRow>,
+    tracked_user_servers: Vec<TrackedUserServerRow>,
+    opt_outs: Vec<OptOutRow>,
+    command_permissions: Vec<CommandPermissionRow>,
+    retention_policies: Vec<RetentionPolicyRow>,
+    /// Missing in exports written before similarity alerts existed.
+    #[serde(default)]
+    alert_channels: Vec<AlertChannelRow>,
+    /// Missing in exports written before delete links were recorded.
+    #[serde(default)]
+    archived_images: Vec<ArchivedImageRow>,
+    #[serde(default)]
+    pending_image_deletions: Vec<PendingImageDeletionRow>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserRow {
+    discord_id: i64,
+    tracked_since: Option<i64>,
+    removed_at: Option<i64>,
+}
+
+/// A row of ProfilePicture or ServerPicture; `entity_id` is the user or server ID.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PictureRow {
+    checksum: String,
+    entity_id: i64,
+    changed_at: i64,
+    link: Option<String>,
+    /// Missing in exports written before perceptual hashes were recorded.
+    #[serde(default)]
+    perceptual_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsernameRow {
+    user_id: i64,
+    changed_at: Option<i64>,
+    username: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerRow {
+    server_id: i64,
+    tracked_since: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedUserServerRow {
+    user_id: i64,
+    server_id: i64,
+    added_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OptOutRow {
+    user_id: i64,
+    opted_out_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommandPermissionRow {
+    server_id: i64,
+    command_name: String,
+    role_id: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RetentionPolicyRow {
+    server_id: i64,
+    max_age_days: Option<i64>,
+    max_entries: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AlertChannelRow {
+    server_id: i64,
+    channel_id: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedImageRow {
+    link: String,
+    delete_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingImageDeletionRow {
+    link: String,
+    delete_url: Option<String>,
+    queued_at: i64,
+}
+
+/// Writes all tracking data to a new JSON file.
+///
+/// Archived images stay on the image store; only their links are exported.
+/// Update pass history and check status are operational data and left out.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `path` - File to create; an existing file is never overwritten
+pub async fn export(database: &SqlitePool, path: &Path) -> Result<(), CliError> {
+    let export = Export {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now().timestamp(),
+        users: sqlx::query_as!(
+            UserRow,
+            r#"SELECT discordId AS "discord_id!: i64", trackedSince AS tracked_since, removedAt AS removed_at
+            FROM User ORDER BY discordId"#
+        )
+        .fetch_all(database)
+        .await?,
+        profile_pictures: sqlx::query_as!(
+            PictureRow,
+            r#"SELECT checksum AS "checksum!: String", userId AS "entity_id!: i64", changedAt AS "changed_at!: i64", link, perceptualHash AS perceptual_hash
+            FROM ProfilePicture ORDER BY userId, changedAt"#
+        )
+        .fetch_all(database)
+        .await?,
+        usernames: sqlx::query_as!(
+            UsernameRow,
+            r#"SELECT userId AS "user_id!: i64", changedAt AS changed_at, username
+            FROM UsernameChange ORDER BY userId, changedAt"#
+        )
+        .fetch_all(database)
+        .await?,
+        servers: sqlx::query_as!(
+            ServerRow,
+            r#"SELECT serverId AS "server_id!: i64", trackedSince AS tracked_since FROM Server ORDER BY serverId"#
+        )
+        .fetch_all(database)
+        .await?,
+        server_pictures: sqlx::query_as!(
+            PictureRow,
+            r#"SELECT checksum AS "checksum!: String", serverId AS "entity_id!: i64", changedAt AS "changed_at!: i64", link, perceptualHash AS perceptual_hash
+            FROM ServerPicture ORDER BY serverId, changedAt"#
+        )
+        .fetch_all(database)
+        .await?,
+        tracked_user_servers: sqlx::query_as!(
+            TrackedUserServerRow,
+            r#"SELECT userId AS "user_id!: i64", serverId AS "server_id!: i64", addedAt AS added_at
+            FROM TrackedUserServer ORDER BY userId, serverId"#
+        )
+        .fetch_all(database)
+        .await?,
+        opt_outs: sqlx::query_as!(
+            OptOutRow,
+            r#"SELECT userId AS "user_id!: i64", optedOutAt AS opted_out_at FROM OptOut ORDER BY userId"#
+        )
+        .fetch_all(database)
+        .await?,
+        command_permissions: sqlx::query_as!(
+            CommandPermissionRow,
+            r#"SELECT serverId AS "server_id!: i64", commandName AS "command_name!: String", roleId AS "role_id!: i64"
+            FROM CommandPermission ORDER BY serverId, commandName, roleId"#
+        )
+        .fetch_all(database)
+        .await?,
+        retention_policies: sqlx::query_as!(
+            RetentionPolicyRow,
+            r#"SELECT serverId AS "server_id!: i64", maxAgeDays AS max_age_days, maxEntries AS max_entries
+            FROM RetentionPolicy ORDER BY serverId"#
+        )
+        .fetch_all(database)
+        .await?,
+        alert_channels: sqlx::query_as!(
+            AlertChannelRow,
+            r#"SELECT serverId AS "server_id!: i64", channelId AS "channel_id!: i64"
+            FROM AlertChannel WHERE channelId IS NOT NULL ORDER BY serverId"#
+        )
+        .fetch_all(database)
+        .await?,
+        archived_images: sqlx::query_as!(
+            ArchivedImageRow,
+            r#"SELECT link AS "link!: String", deleteUrl AS delete_url FROM ArchivedImage ORDER BY link"#
+        )
+        .fetch_all(database)
+        .await?,
+        pending_image_deletions: sqlx::query_as!(
+            PendingImageDeletionRow,
+            r#"SELECT link AS "link!: String", deleteUrl AS delete_url, queuedAt AS queued_at
+            FROM PendingImageDeletion ORDER BY queuedAt"#
+        )
+        .fetch_all(database)
+        .await?,
+    };
+
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &export)?;
+
+    println!(
+        "Exported {} users, {} profile pictures, {} usernames, {} servers and {} server icons to {}",
+        export.users.len(),
+        export.profile_pictures.len(),
+        export.usernames.len(),
+        export.servers.len(),
+        export.server_pictures.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Imports a file written by [`export`] in a single transaction.
+///
+/// Rows that already exist are kept as they are, so importing the same file twice
+/// or into a database that already holds part of the data is safe.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `path` - File written by [`export`]
+pub async fn import(database: &SqlitePool, path: &Path) -> Result<(), CliError> {
+    let file = std::fs::File::open(path)?;
+    let export: Export = serde_json::from_reader(std::io::BufReader::new(file))?;
+
+    if export.version != EXPORT_VERSION {
+        return Err(CliError::InvalidInput(format!(
+            "Export version {} is not supported, expected {}",
+            export.version, EXPORT_VERSION
+        )));
+    }
+
+    let mut transaction = database.begin().await?;
+    let mut imported = 0;
+
+    for user in &export.users {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO User (discordId, trackedSince, removedAt) VALUES (?, ?, ?)",
+            user.discord_id,
+            user.tracked_since,
+            user.removed_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for picture in &export.profile_pictures {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO ProfilePicture (checksum, userId, changedAt, link, perceptualHash) VALUES (?, ?, ?, ?, ?)",
+            picture.checksum,
+            picture.entity_id,
+            picture.changed_at,
+            picture.link,
+            picture.perceptual_hash
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for username in &export.usernames {
+        // UsernameChange has no primary key, so skip exact duplicates by hand
+        let normalized_name = username.username.as_deref().map(alerts::normalize_name);
+        imported += sqlx::query!(
+            "INSERT INTO UsernameChange (userId, changedAt, username, normalizedName)
+            SELECT ?1, ?2, ?3, ?4
+            WHERE NOT EXISTS (SELECT 1 FROM UsernameChange WHERE userId = ?1 AND changedAt IS ?2 AND username IS ?3)",
+            username.user_id,
+            username.changed_at,
+            username.username,
+            normalized_name
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for server in &export.servers {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO Server (serverId, trackedSince) VALUES (?, ?)",
+            server.server_id,
+            server.tracked_since
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for picture in &export.server_pictures {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO ServerPicture (checksum, serverId, changedAt, link, perceptualHash) VALUES (?, ?, ?, ?, ?)",
+            picture.checksum,
+            picture.entity_id,
+            picture.changed_at,
+            picture.link,
+            picture.perceptual_hash
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for tracked in &export.tracked_user_servers {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO TrackedUserServer (userId, serverId, addedAt) VALUES (?, ?, ?)",
+            tracked.user_id,
+            tracked.server_id,
+            tracked.added_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for opt_out in &export.opt_outs {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO OptOut (userId, optedOutAt) VALUES (?, ?)",
+            opt_out.user_id,
+            opt_out.opted_out_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for permission in &export.command_permissions {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO CommandPermission (serverId, commandName, roleId) VALUES (?, ?, ?)",
+            permission.server_id,
+            permission.command_name,
+            permission.role_id
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for policy in &export.retention_policies {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO RetentionPolicy (serverId, maxAgeDays, maxEntries) VALUES (?, ?, ?)",
+            policy.server_id,
+            policy.max_age_days,
+            policy.max_entries
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for alert_channel in &export.alert_channels {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO AlertChannel (serverId, channelId) VALUES (?, ?)",
+            alert_channel.server_id,
+            alert_channel.channel_id
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for image in &export.archived_images {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO ArchivedImage (link, deleteUrl) VALUES (?, ?)",
+            image.link,
+            image.delete_url
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for deletion in &export.pending_image_deletions {
+        imported += sqlx::query!(
+            "INSERT OR IGNORE INTO PendingImageDeletion (link, deleteUrl, queuedAt) VALUES (?, ?, ?)",
+            deletion.link,
+            deletion.delete_url,
+            deletion.queued_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    transaction.commit().await?;
+
+    let exported_at = DateTime::<Utc>::from_timestamp(export.exported_at, 0)
+        .map(|exported_at| exported_at.to_rfc3339())
+        .unwrap_or_else(|| export.exported_at.to_string());
+    println!(
+        "Imported {} new rows from {} (exported {})",
+        imported,
+        path.display(),
+        exported_at
+    );
+
+    Ok(())
+}
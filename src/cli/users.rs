@@ -0,0 +1,155 @@
+// ABOUTME: Maintenance subcommands that manage the monitoring list without the slash commands
+// ABOUTME: Adds and removes users and checks a single user or server right away
+use chrono::Utc;
+use serenity::http::Http;
+use sqlx::SqlitePool;
+
+use crate::cli::{connect, CliError};
+use crate::commands::restoremonitor;
+use crate::util::check_status;
+use crate::util::chron_update::{self, CheckOutcome};
+use crate::util::config::Config;
+use crate::util::discord::{self, CdnClient, HttpDiscord};
+use crate::util::storage::ImgBBStore;
+
+/// Adds a user to the monitoring list, like /monitor.
+///
+/// Users who opted out are refused, and users removed during their grace period
+/// are restored with their history.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `user_id` - Discord ID of the user
+/// * `server_id` - Server the user is tracked for, if any
+pub async fn add_user(
+    database: &SqlitePool,
+    user_id: i64,
+    server_id: Option<i64>,
+) -> Result<(), CliError> {
+    let opted_out = sqlx::query_scalar!("SELECT userId FROM OptOut WHERE userId = ?", user_id)
+        .fetch_optional(database)
+        .await?;
+
+    if opted_out.is_some() {
+        return Err(CliError::InvalidInput(format!(
+            "{} has opted out of tracking and cannot be monitored",
+            user_id
+        )));
+    }
+
+    let now = Utc::now().timestamp();
+
+    let entry = sqlx::query!(
+        "SELECT removedAt FROM User WHERE discordId = ? LIMIT 1",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    match entry {
+        Some(record) if record.removedAt.is_some() => {
+            restoremonitor::restore_user(database, user_id).await?;
+            println!(
+                "Resumed monitoring {}, their history has been kept",
+                user_id
+            );
+        }
+        Some(_) => println!("{} is already being monitored", user_id),
+        None => {
+            sqlx::query!(
+                "INSERT INTO User (discordId, trackedSince) VALUES (?, ?)",
+                user_id,
+                now
+            )
+            .execute(database)
+            .await?;
+            println!(
+                "Added {} to the monitoring list, they are checked on the next update pass",
+                user_id
+            );
+        }
+    }
+
+    if let Some(server_id) = server_id {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO TrackedUserServer (userId, serverId, addedAt) VALUES (?, ?, ?)",
+            user_id,
+            server_id,
+            now
+        )
+        .execute(database)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Removes a user from the monitoring list, like /removemonitor.
+///
+/// The history is kept for the removal grace period and purged by the daily cleanup of the bot.
+pub async fn remove_user(database: &SqlitePool, user_id: i64) -> Result<(), CliError> {
+    let now = Utc::now().timestamp();
+
+    let result = sqlx::query!(
+        "UPDATE User SET removedAt = ? WHERE discordId = ? AND removedAt IS NULL",
+        now,
+        user_id
+    )
+    .execute(database)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(CliError::InvalidInput(format!(
+            "{} is not being monitored",
+            user_id
+        )));
+    }
+
+    println!(
+        "Stopped monitoring {}, their history is purged after the grace period unless restored",
+        user_id
+    );
+
+    Ok(())
+}
+
+/// Checks a monitored user or server right away, ignoring any backoff.
+///
+/// Needs the full configuration, as it talks to the Discord API and archives new images.
+pub async fn check_now(id: i64) -> Result<(), CliError> {
+    let config = Config::from_env().map_err(|_| {
+        CliError::Config("DISCORD_TOKEN, DATABASE_URL and IMGBB_KEY must be set".to_string())
+    })?;
+    let database = connect().await?;
+    let http = Http::new(&config.discord_token);
+    let cdn = CdnClient::new(config.discord_cdn_url, discord::DOWNLOAD_TIMEOUT);
+    let discord = HttpDiscord::new(&http, &cdn);
+    let image_store = ImgBBStore::new(config.imgbb_key).with_upload_url(config.imgbb_upload_url);
+
+    if check_status::user_status(&database, id).await?.is_some() {
+        let outcome = chron_update::check_user_now(&discord, &database, &image_store, id).await;
+        report("user", id, outcome)
+    } else if check_status::server_status(&database, id).await?.is_some() {
+        let outcome = chron_update::check_server_now(&discord, &database, &image_store, id).await;
+        report("server", id, outcome)
+    } else {
+        Err(CliError::InvalidInput(format!(
+            "{} is neither a monitored user nor a monitored server",
+            id
+        )))
+    }
+}
+
+/// Prints the outcome of a manual check, failing the subcommand if the check failed.
+fn report(entity_type: &str, id: i64, outcome: CheckOutcome) -> Result<(), CliError> {
+    match outcome {
+        CheckOutcome::Failed(reason, error) => Err(CliError::Failed(format!(
+            "Checking {} {} failed ({}): {}",
+            entity_type, id, reason, error
+        ))),
+        outcome => {
+            println!("Checked {} {}: {}", entity_type, id, outcome.as_str());
+            Ok(())
+        }
+    }
+}
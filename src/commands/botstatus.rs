@@ -0,0 +1,204 @@
+// ABOUTME: Command showing the health of the update scheduler and the size of the archive
+// ABOUTME: Reports last and next update passes, durations, failure rates, tracked entities and storage usage
+use chrono::Utc;
+use serenity::all::{
+    colours, CommandInteraction, Context, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::chron_update::UPDATE_INTERVAL_SECS;
+use crate::util::format::format_bytes;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::update_runs;
+
+/// Window of the duration and failure statistics.
+const STATS_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Handles the /botstatus command.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+) -> Result<(), BotError> {
+    let now = Utc::now().timestamp();
+
+    let latest_runs = update_runs::latest_runs(database).await?;
+    let last_scheduled_start = update_runs::last_scheduled_start(database).await?;
+    let stats = update_runs::run_stats_since(database, now - STATS_WINDOW_SECS).await?;
+
+    let active_users = sqlx::query_scalar!("SELECT COUNT(*) FROM User WHERE removedAt IS NULL")
+        .fetch_one(database)
+        .await?;
+    let removed_users =
+        sqlx::query_scalar!("SELECT COUNT(*) FROM User WHERE removedAt IS NOT NULL")
+            .fetch_one(database)
+            .await?;
+    let servers = sqlx::query_scalar!("SELECT COUNT(*) FROM Server")
+        .fetch_one(database)
+        .await?;
+    let archived_images = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM (SELECT link FROM ProfilePicture WHERE link IS NOT NULL UNION SELECT link FROM ServerPicture WHERE link IS NOT NULL)"
+    )
+    .fetch_one(database)
+    .await?;
+    let database_bytes = sqlx::query_scalar::<_, i64>(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(database)
+    .await?;
+
+    let last_passes = if latest_runs.is_empty() {
+        "No update pass has run yet.".to_string()
+    } else {
+        latest_runs
+            .iter()
+            .map(|run| match run.finished_at {
+                Some(finished_at) => format!(
+                    "**{}**: finished <t:{}:R> after {}",
+                    run.entity_type,
+                    finished_at,
+                    format_duration((finished_at - run.started_at) as f64)
+                ),
+                None => format!(
+                    "**{}**: started <t:{}:R>, not finished",
+                    run.entity_type, run.started_at
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let next_pass = match last_scheduled_start {
+        Some(started_at) => format!("<t:{}:R>", started_at + UPDATE_INTERVAL_SECS as i64),
+        None => "Once the bot is connected".to_string(),
+    };
+
+    let recent_stats = if stats.is_empty() {
+        "No finished update passes.".to_string()
+    } else {
+        stats
+            .iter()
+            .map(|stats| {
+                format!(
+                    "**{}**: {} passes, {} on average, {:.1}% of {} checks failed",
+                    stats.entity_type,
+                    stats.runs,
+                    format_duration(stats.average_duration_secs),
+                    stats.failure_rate() * 100.0,
+                    stats.checked
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Bot Status")
+        .fields(vec![
+            ("Last update passes", last_passes, false),
+            ("Next scheduled pass", next_pass, false),
+            ("Last 24 hours", recent_stats, false),
+            (
+                "Tracked",
+                format!(
+                    "{} users ({} pending removal)\n{} servers",
+                    active_users, removed_users, servers
+                ),
+                true,
+            ),
+            (
+                "Storage",
+                format!(
+                    "{} archived images\n{} database",
+                    archived_images,
+                    format_bytes(database_bytes)
+                ),
+                true,
+            ),
+        ])
+        .footer(CreateEmbedFooter::new(format!(
+            "Version {}",
+            env!("CARGO_PKG_VERSION")
+        )))
+        .colour(colours::branding::BLURPLE);
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Formats seconds as `1h 2m`, `3m 4s` or `5s`.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round().max(0.0) as i64;
+
+    if seconds >= 3600 {
+        format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Registers the /botstatus command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("botstatus")
+        .description("Shows when profiles were last checked and how much is archived.")
+}
+
+pub struct BotStatusCommand;
+
+#[async_trait]
+impl SlashCommand for BotStatusCommand {
+    fn name(&self) -> &'static str {
+        "botstatus"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(4.4), "4s");
+        assert_eq!(format_duration(125.0), "2m 5s");
+        assert_eq!(format_duration(7260.0), "2h 1m");
+    }
+}
@@ -0,0 +1,493 @@
+// ABOUTME: Command to manage per-server bot settings such as role requirements, history retention and alerts
+// ABOUTME: Provides the /config permissions, /config retention and /config alerts subcommand groups
+use serenity::async_trait;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::alerts;
+use crate::util::permissions;
+use crate::util::retention::{self, PruneScope, RetentionPolicy};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::storage::ImageStore;
+
+/// Handles the /config command.
+///
+/// Requires MANAGE_GUILD permission. Dispatches to the subcommand group selected
+/// by the member.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `global_retention` - Retention policy used when a server has none
+/// * `options` - The resolved command options
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    global_retention: RetentionPolicy,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let guild_id = i64::from(interaction.guild_id.ok_or(BotError::GuildOnly)?);
+
+    permissions::require_manage_guild(interaction)?;
+
+    match options.first() {
+        Some(ResolvedOption {
+            name: "permissions",
+            value: ResolvedValue::SubCommandGroup(sub_options),
+            ..
+        }) => run_permissions(ctx, interaction, database, guild_id, sub_options).await,
+        Some(ResolvedOption {
+            name: "retention",
+            value: ResolvedValue::SubCommandGroup(sub_options),
+            ..
+        }) => {
+            run_retention(
+                ctx,
+                interaction,
+                database,
+                image_store,
+                global_retention,
+                guild_id,
+                sub_options,
+            )
+            .await
+        }
+        Some(ResolvedOption {
+            name: "alerts",
+            value: ResolvedValue::SubCommandGroup(sub_options),
+            ..
+        }) => run_alerts(ctx, interaction, database, guild_id, sub_options).await,
+        _ => Err(BotError::InvalidInput(
+            "Unknown configuration option.".to_string(),
+        )),
+    }
+}
+
+/// Handles the /config permissions subcommands.
+async fn run_permissions(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    guild_id: i64,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return Err(BotError::InvalidInput(
+            "Unknown permissions option.".to_string(),
+        ));
+    };
+
+    if *subcommand == "list" {
+        let entries = permissions::fetch_all_required_roles(database, guild_id).await?;
+        let content = if entries.is_empty() {
+            "No role requirements configured for this server.".to_string()
+        } else {
+            entries
+                .iter()
+                .map(|(name, role_id)| format!("`/{}` requires <@&{}>", name, role_id))
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+
+        return respond(ctx, interaction, content).await;
+    }
+
+    let mut command_name = None;
+    let mut role = None;
+
+    for option in sub_options {
+        match (option.name, &option.value) {
+            ("command", ResolvedValue::String(value)) => command_name = Some(*value),
+            ("role", ResolvedValue::Role(value)) => role = Some(*value),
+            _ => {}
+        }
+    }
+
+    let (Some(command_name), Some(role)) = (command_name, role) else {
+        return Err(BotError::InvalidInput(
+            "Please provide a command and a role.".to_string(),
+        ));
+    };
+
+    if !permissions::CONFIGURABLE_COMMANDS.contains(&command_name) {
+        return Err(BotError::InvalidInput(format!(
+            "`/{}` cannot be configured.",
+            command_name
+        )));
+    }
+
+    let content = match *subcommand {
+        "add" => {
+            if permissions::add_required_role(database, guild_id, command_name, role.id).await? {
+                format!("`/{}` now requires {}.", command_name, role.name)
+            } else {
+                format!("`/{}` already requires {}.", command_name, role.name)
+            }
+        }
+        "remove" => {
+            if permissions::remove_required_role(database, guild_id, command_name, role.id).await? {
+                format!("`/{}` no longer requires {}.", command_name, role.name)
+            } else {
+                format!("`/{}` does not require {}.", command_name, role.name)
+            }
+        }
+        _ => "Unknown permissions option.".to_string(),
+    };
+
+    respond(ctx, interaction, content).await
+}
+
+/// Handles the /config retention subcommands.
+async fn run_retention(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    global_retention: RetentionPolicy,
+    guild_id: i64,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return Err(BotError::InvalidInput(
+            "Unknown retention option.".to_string(),
+        ));
+    };
+
+    let content = match *subcommand {
+        "set" => {
+            let mut policy = RetentionPolicy::default();
+
+            for option in sub_options {
+                match (option.name, &option.value) {
+                    ("max_age_days", ResolvedValue::Integer(value)) => {
+                        policy.max_age_days = Some(*value)
+                    }
+                    ("max_entries", ResolvedValue::Integer(value)) => {
+                        policy.max_entries = Some(*value)
+                    }
+                    _ => {}
+                }
+            }
+
+            retention::set_server_policy(database, guild_id, policy).await?;
+            format!(
+                "Retention for this server set to: {}\nUse `/config retention preview` to see what the next cleanup removes.",
+                describe_policy(&policy)
+            )
+        }
+        "clear" => {
+            if retention::clear_server_policy(database, guild_id).await? {
+                format!(
+                    "Retention policy removed. The global policy applies again: {}",
+                    describe_policy(&global_retention)
+                )
+            } else {
+                "This server has no retention policy.".to_string()
+            }
+        }
+        "show" => match retention::fetch_server_policy(database, guild_id).await? {
+            Some(policy) => format!("This server keeps: {}", describe_policy(&policy)),
+            None => format!(
+                "This server uses the global policy: {}",
+                describe_policy(&global_retention)
+            ),
+        },
+        "preview" => {
+            let report = retention::prune(
+                database,
+                image_store,
+                global_retention,
+                PruneScope::Server(guild_id),
+                true,
+            )
+            .await?;
+
+            if report.total_rows() == 0 {
+                "The next cleanup would not remove anything for this server.".to_string()
+            } else {
+                format!(
+                    "The next cleanup would remove:\n- {} profile pictures\n- {} usernames\n- {} server icons\n- {} archived images no longer referenced anywhere",
+                    report.profile_pictures,
+                    report.usernames,
+                    report.server_pictures,
+                    report.images
+                )
+            }
+        }
+        _ => "Unknown retention option.".to_string(),
+    };
+
+    respond(ctx, interaction, content).await
+}
+
+/// Handles the /config alerts subcommands.
+async fn run_alerts(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    guild_id: i64,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return Err(BotError::InvalidInput("Unknown alerts option.".to_string()));
+    };
+
+    let content = match *subcommand {
+        "set" => {
+            let Some(channel) = sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::Channel(channel) => Some(channel),
+                _ => None,
+            }) else {
+                return Err(BotError::InvalidInput(
+                    "Please provide a channel.".to_string(),
+                ));
+            };
+
+            alerts::set_alert_channel(database, guild_id, i64::from(channel.id)).await?;
+            format!(
+                "Members monitored in this server who take on another tracked user's profile picture or display name are now reported in <#{}>.",
+                channel.id
+            )
+        }
+        "clear" => {
+            if alerts::clear_alert_channel(database, guild_id).await? {
+                "Similarity alerts turned off.".to_string()
+            } else {
+                "Similarity alerts are not turned on.".to_string()
+            }
+        }
+        "show" => match alerts::fetch_alert_channel(database, guild_id).await? {
+            Some(channel_id) => format!("Similarity alerts are sent to <#{}>.", channel_id),
+            None => "Similarity alerts are off.".to_string(),
+        },
+        _ => "Unknown alerts option.".to_string(),
+    };
+
+    respond(ctx, interaction, content).await
+}
+
+fn describe_policy(policy: &RetentionPolicy) -> String {
+    match (policy.max_age_days, policy.max_entries) {
+        (None, None) => "all history, forever".to_string(),
+        (Some(days), None) => format!("history of the last {} days", days),
+        (None, Some(entries)) => format!("the last {} entries per user or server", entries),
+        (Some(days), Some(entries)) => format!(
+            "the last {} entries per user or server, at most {} days old",
+            entries, days
+        ),
+    }
+}
+
+async fn respond(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    content: impl Into<String>,
+) -> Result<(), BotError> {
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Registers the /config command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    let command_option = permissions::CONFIGURABLE_COMMANDS.iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "command",
+            "The command to configure.",
+        )
+        .required(true),
+        |option, command_name| {
+            option.add_string_choice(format!("/{}", command_name), *command_name)
+        },
+    );
+
+    let role_option = CreateCommandOption::new(
+        CommandOptionType::Role,
+        "role",
+        "The role members need to use the command.",
+    )
+    .required(true);
+
+    CreateCommand::new("config")
+        .description("Configures the bot for this server.")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "permissions",
+                "Manage which roles may use the bot's commands.",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Require a role to use a command.",
+                )
+                .add_sub_option(command_option.clone())
+                .add_sub_option(role_option.clone()),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Stop requiring a role to use a command.",
+                )
+                .add_sub_option(command_option)
+                .add_sub_option(role_option),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "Show the configured role requirements.",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "retention",
+                "Manage how long history is kept.",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "set",
+                    "Limit the history kept for this server. Leave both empty to keep everything.",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "max_age_days",
+                        "Drop history older than this many days.",
+                    )
+                    .min_int_value(1),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "max_entries",
+                        "Keep only this many entries per user or server.",
+                    )
+                    .min_int_value(1),
+                ),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "clear",
+                "Use the global retention policy again.",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "show",
+                "Show the retention policy of this server.",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "preview",
+                "Show what the next cleanup would remove.",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "alerts",
+                "Manage alerts about accounts reusing another user's picture or name.",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "set",
+                    "Send similarity alerts to a channel.",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "The moderator channel to alert.",
+                    )
+                    .channel_types(vec![ChannelType::Text])
+                    .required(true),
+                ),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "clear",
+                "Stop sending similarity alerts.",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "show",
+                "Show where similarity alerts are sent.",
+            )),
+        )
+}
+
+pub struct ConfigCommand;
+
+#[async_trait]
+impl SlashCommand for ConfigCommand {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.image_store.as_ref(),
+            state.retention,
+            &interaction.data.options(),
+        )
+        .await?;
+        Ok(())
+    }
+}
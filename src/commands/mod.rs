@@ -1,10 +1,91 @@
+pub mod botstatus;
+pub mod config;
 pub mod monitor;
 pub mod monitorserver;
+pub mod mydata;
+pub mod optin;
+pub mod optout;
+pub mod pfpgrid;
 pub mod pfphistory;
+pub mod pfptimeline;
 pub mod ping;
 pub mod removemonitor;
 pub mod removemonitorserver;
+pub mod restoremonitor;
 pub mod serverpfphistory;
 pub mod serverstats;
 pub mod stats;
+pub mod trackingerrors;
 pub mod usernamehistory;
+pub mod whoused;
+
+use crate::util::pagination::PaginationRouter;
+use crate::util::slash_command::CommandRegistry;
+
+/// Builds the registry of every slash command, in the order they are registered with Discord.
+pub fn registry() -> CommandRegistry {
+    CommandRegistry::new()
+        .with_command(ping::PingCommand)
+        .with_command(botstatus::BotStatusCommand)
+        .with_command(monitor::MonitorCommand)
+        .with_command(removemonitor::RemoveMonitorCommand)
+        .with_command(restoremonitor::RestoreMonitorCommand)
+        .with_command(pfphistory::PfpHistoryCommand)
+        .with_command(usernamehistory::UsernameHistoryCommand)
+        .with_command(stats::StatsCommand)
+        .with_command(pfptimeline::PfpTimelineCommand)
+        .with_command(pfpgrid::PfpGridCommand)
+        .with_command(whoused::WhoUsedCommand)
+        .with_command(monitorserver::MonitorServerCommand)
+        .with_command(removemonitorserver::RemoveMonitorServerCommand)
+        .with_command(serverpfphistory::ServerPfpHistoryCommand)
+        .with_command(serverstats::ServerStatsCommand)
+        .with_command(trackingerrors::TrackingErrorsCommand)
+        .with_command(optout::OptOutCommand)
+        .with_command(optin::OptInCommand)
+        .with_command(mydata::MyDataCommand)
+        .with_command(config::ConfigCommand)
+}
+
+/// Builds the router for the buttons of every paginated command.
+///
+/// New paginated commands only need to register their view here.
+pub fn paginated_views() -> PaginationRouter {
+    PaginationRouter::new()
+        .with_view(pfphistory::PfpHistoryView)
+        .with_view(pfphistory::PfpGalleryView)
+        .with_view(usernamehistory::UsernameHistoryView)
+        .with_view(serverpfphistory::ServerPfpHistoryView)
+        .with_view(serverpfphistory::ServerPfpGalleryView)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_registry_names_match_definitions() {
+        let registry = registry();
+
+        for definition in registry.definitions() {
+            let json = serde_json::to_value(&definition).unwrap();
+            let name = json["name"].as_str().unwrap();
+            let command = registry
+                .get(name)
+                .expect("registered command not found by name");
+            assert_eq!(command.name(), name);
+        }
+    }
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let definitions = registry().definitions();
+        let names: HashSet<String> = definitions
+            .iter()
+            .map(|definition| serde_json::to_value(definition).unwrap()["name"].to_string())
+            .collect();
+
+        assert_eq!(names.len(), definitions.len());
+    }
+}
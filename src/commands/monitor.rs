@@ -1,99 +1,159 @@
 use std::time::SystemTime;
 
 use chrono::{DateTime, Utc};
+use serenity::async_trait;
 use serenity::builder::*;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use sqlx::SqlitePool;
+use tracing::error;
 
+use crate::commands::restoremonitor;
+use crate::error::BotError;
+use crate::util::chron_update;
+use crate::util::discord::HttpDiscord;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Handles the /monitor command, adding a user to the monitoring list.
+///
+/// # Returns
+/// * `Result<Option<i64>, BotError>` - The user if they were added or resumed and should be checked right away
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
     options: &[ResolvedOption<'_>],
-) -> Result<(), serenity::Error> {
-    if let Some(ResolvedOption {
+) -> Result<Option<i64>, BotError> {
+    let Some(ResolvedOption {
         value: ResolvedValue::User(user, _),
         ..
     }) = options.first()
-    {
-        let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
-                                          // trait for sqlx.
+    else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
 
-        let entry = sqlx::query!(
-            "SELECT trackedSince FROM User WHERE discordId = ? LIMIT 1",
-            user_id,
-        )
-        .fetch_one(database)
-        .await;
-
-        if let Ok(record) = entry {
-            let tracking_start_date = record.trackedSince.unwrap();
-            let dt = DateTime::from_timestamp(tracking_start_date, 0).unwrap();
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new().content(format!(
-                            "{} is already being tracked since <t:{}:F>",
-                            user.name,
-                            dt.timestamp()
-                        )),
-                    ),
-                )
-                .await
-                .unwrap();
-            return Ok(());
-        }
+    let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
+                                      // trait for sqlx.
 
-        let now = SystemTime::now();
-        let dt: DateTime<Utc> = now.into();
-        let timestamp = dt.timestamp();
+    let opted_out = sqlx::query!("SELECT userId FROM OptOut WHERE userId = ?", user_id)
+        .fetch_optional(database)
+        .await?;
 
-        // Add the user to the database
-        sqlx::query_as!(
-            objects::User,
-            "INSERT INTO User (discordId, trackedSince) VALUES (?, ?)",
-            user_id,
-            timestamp
-        )
-        .execute(database)
-        .await
-        .unwrap();
+    if opted_out.is_some() {
+        return Err(BotError::Permission(format!(
+            "{} has opted out of tracking and cannot be monitored.",
+            user.name
+        )));
+    }
 
-        // Reply with confirmation
-        interaction
-            .create_response(
-                &ctx.http,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().content(format!(
-                        "Sucessfully added {} to the monitoring list.",
-                        user.name
-                    )),
+    let entry = sqlx::query!(
+        "SELECT trackedSince, removedAt FROM User WHERE discordId = ? LIMIT 1",
+        user_id,
+    )
+    .fetch_optional(database)
+    .await?;
+
+    if let Some(record) = entry {
+        record_tracking_server(database, interaction, user_id).await;
+
+        let (content, resumed) = if record.removedAt.is_some() {
+            // Removed during the grace period, resume with the kept history
+            restoremonitor::restore_user(database, user_id).await?;
+            (
+                format!(
+                    "Resumed monitoring {}. Their previous history has been kept.",
+                    user.name
                 ),
+                true,
             )
-            .await
-            .unwrap();
-    } else {
+        } else {
+            let content = match record.trackedSince {
+                Some(tracked_since) => format!(
+                    "{} is already being tracked since <t:{}:F>",
+                    user.name, tracked_since
+                ),
+                None => format!("{} is already being tracked.", user.name),
+            };
+            (content, false)
+        };
+
         interaction
             .create_response(
                 &ctx.http,
                 CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().content("Invalid User ID."),
+                    CreateInteractionResponseMessage::new().content(content),
                 ),
             )
-            .await
-            .unwrap();
-
-        return Ok(());
+            .await?;
+        return Ok(resumed.then_some(user_id));
     }
 
-    Ok(())
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    // Add the user to the database
+    sqlx::query_as!(
+        objects::User,
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, ?)",
+        user_id,
+        timestamp
+    )
+    .execute(database)
+    .await?;
+
+    record_tracking_server(database, interaction, user_id).await;
+
+    // Reply with confirmation
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().content(
+                format!("Sucessfully added {} to the monitoring list.", user.name),
+            )),
+        )
+        .await?;
+
+    Ok(Some(user_id))
+}
+
+/// Remembers the server in which a user was added to the monitoring list.
+///
+/// Tracking itself is global; this only records which servers asked for it so the
+/// information can be included in a user's data export.
+async fn record_tracking_server(
+    database: &SqlitePool,
+    interaction: &CommandInteraction,
+    user_id: i64,
+) {
+    let Some(guild_id) = interaction.guild_id else {
+        return;
+    };
+    let server_id = i64::from(guild_id);
+
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    if let Err(e) = sqlx::query!(
+        "INSERT OR IGNORE INTO TrackedUserServer (userId, serverId, addedAt) VALUES (?, ?, ?)",
+        user_id,
+        server_id,
+        timestamp
+    )
+    .execute(database)
+    .await
+    {
+        error!(server_id, user_id, error = ?e, "Failed to record tracking server");
+    }
 }
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("monitor")
         .description("Adds a user to the Monitor List.")
+        .default_member_permissions(Permissions::MODERATE_MEMBERS)
+        .dm_permission(false)
         .add_option(
             CreateCommandOption::new(
                 CommandOptionType::User,
@@ -103,3 +163,43 @@ pub fn register() -> CreateCommand {
             .required(true),
         )
 }
+
+pub struct MonitorCommand;
+
+#[async_trait]
+impl SlashCommand for MonitorCommand {
+    fn name(&self) -> &'static str {
+        "monitor"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        let added = run(
+            ctx,
+            interaction,
+            &state.database,
+            &interaction.data.options(),
+        )
+        .await?;
+
+        // Record the first picture and username now instead of waiting for the next pass
+        if let Some(user_id) = added {
+            chron_update::check_user_now(
+                &HttpDiscord::new(&ctx.http, &state.cdn),
+                &state.database,
+                state.image_store.as_ref(),
+                user_id,
+            )
+            .await;
+        }
+        Ok(())
+    }
+}
@@ -3,11 +3,20 @@
 use std::time::SystemTime;
 
 use chrono::{DateTime, Utc};
+use serenity::async_trait;
 use serenity::builder::*;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use sqlx::SqlitePool;
 
+use crate::error::BotError;
+use crate::util::chron_update::update_monitored_servers;
+use crate::util::discord::HttpDiscord;
+use crate::util::permissions;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::update_runs::RunTrigger;
+
 /// Handles the /monitorserver command to add a server to the monitoring list.
 ///
 /// Requires MANAGE_GUILD permission. Checks if the server is already tracked
@@ -19,88 +28,50 @@ use sqlx::SqlitePool;
 /// * `database` - SQLite connection pool
 ///
 /// # Returns
-/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
-) -> Result<(), serenity::Error> {
+) -> Result<(), BotError> {
     // Get the guild (server) from the interaction
-    let guild_id = match interaction.guild_id {
-        Some(id) => i64::from(id),
-        None => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("This command can only be used in a server."),
-                    ),
-                )
-                .await?;
-            return Ok(());
-        }
-    };
-
-    // Check if user has permission to manage server
-    if let Some(member) = &interaction.member {
-        if let Some(permissions) = member.permissions {
-            if !permissions.manage_guild() {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(
-                                "You need 'Manage Server' permission to use this command.",
-                            ),
-                        ),
-                    )
-                    .await?;
-                return Ok(());
-            }
-        }
-    }
+    let guild_id = i64::from(interaction.guild_id.ok_or(BotError::GuildOnly)?);
+
+    permissions::require_manage_guild(interaction)?;
 
     // Check if server is already being tracked
     let entry = sqlx::query!(
         "SELECT trackedSince FROM Server WHERE serverId = ? LIMIT 1",
         guild_id,
     )
-    .fetch_one(database)
-    .await;
+    .fetch_optional(database)
+    .await?;
 
-    if let Ok(record) = entry {
+    if let Some(record) = entry {
         let guild_name = interaction
             .guild_id
             .and_then(|id| ctx.cache.guild(id))
             .map(|g| g.name.clone())
             .unwrap_or_else(|| "This server".to_string());
 
-        if let Some(tracking_start_date) = record.trackedSince {
-            if let Some(dt) = DateTime::from_timestamp(tracking_start_date, 0) {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(format!(
-                                "{} is already being tracked since <t:{}:F>",
-                                guild_name,
-                                dt.timestamp()
-                            )),
-                        ),
-                    )
-                    .await?;
-                return Ok(());
-            }
-        }
-
-        // If trackedSince is NULL or invalid, respond with generic message
+        let content = match record
+            .trackedSince
+            .and_then(|tracking_start_date| DateTime::from_timestamp(tracking_start_date, 0))
+        {
+            Some(dt) => format!(
+                "{} is already being tracked since <t:{}:F>",
+                guild_name,
+                dt.timestamp()
+            ),
+            // If trackedSince is NULL or invalid, respond with generic message
+            None => format!("{} is already being tracked.", guild_name),
+        };
+
         interaction
             .create_response(
                 &ctx.http,
                 CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .content(format!("{} is already being tracked.", guild_name)),
+                    CreateInteractionResponseMessage::new().content(content),
                 ),
             )
             .await?;
@@ -118,27 +89,13 @@ pub async fn run(
         .unwrap_or_else(|| "This server".to_string());
 
     // Add the server to the database
-    let insert_result = sqlx::query!(
+    sqlx::query!(
         "INSERT INTO Server (serverId, trackedSince) VALUES (?, ?)",
         guild_id,
         timestamp
     )
     .execute(database)
-    .await;
-
-    if let Err(e) = insert_result {
-        eprintln!("Failed to insert server {}: {:?}", guild_id, e);
-        interaction
-            .create_response(
-                &ctx.http,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .content("Failed to add server to monitoring list. Please try again."),
-                ),
-            )
-            .await?;
-        return Ok(());
-    }
+    .await?;
 
     // Reply with confirmation
     interaction
@@ -163,4 +120,36 @@ pub async fn run(
 pub fn register() -> CreateCommand {
     CreateCommand::new("monitorserver")
         .description("Adds this server to the monitoring list to track server icon changes.")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+}
+
+pub struct MonitorServerCommand;
+
+#[async_trait]
+impl SlashCommand for MonitorServerCommand {
+    fn name(&self) -> &'static str {
+        "monitorserver"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await?;
+        update_monitored_servers(
+            &HttpDiscord::new(&ctx.http, &state.cdn),
+            &state.database,
+            state.image_store.as_ref(),
+            RunTrigger::Command,
+        )
+        .await;
+        Ok(())
+    }
 }
@@ -0,0 +1,389 @@
+// ABOUTME: Command that sends users a copy of every record the bot holds about them
+// ABOUTME: Bundles tracking and check status, avatar (with image bytes), username and server data as JSON and delivers it by DM
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use serde::Serialize;
+use serenity::async_trait;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+use tracing::{error, warn};
+
+use crate::error::BotError;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::storage::ImageStore;
+
+/// Upper bound for the raw image bytes embedded in one export.
+///
+/// Images are base64 encoded, so this keeps the attachment below Discord's upload limit.
+const MAX_EMBEDDED_IMAGE_BYTES: usize = 6 * 1024 * 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DataExport {
+    user_id: String,
+    generated_at: String,
+    tracked_since: Option<i64>,
+    /// Set while a /removemonitor grace period runs
+    removed_at: Option<i64>,
+    last_checked_at: Option<i64>,
+    last_success_at: Option<i64>,
+    consecutive_failures: Option<i64>,
+    last_error: Option<String>,
+    next_check_at: Option<i64>,
+    opted_out_at: Option<i64>,
+    tracked_in_servers: Vec<TrackedServerExport>,
+    profile_pictures: Vec<ProfilePictureExport>,
+    usernames: Vec<UsernameExport>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedServerExport {
+    server_id: String,
+    added_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfilePictureExport {
+    checksum: Option<String>,
+    changed_at: Option<i64>,
+    link: Option<String>,
+    perceptual_hash: Option<String>,
+    /// Base64 encoded image, `None` if it could not be fetched or exceeded the size budget
+    image: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsernameExport {
+    username: Option<String>,
+    changed_at: Option<i64>,
+}
+
+/// Raw image bytes an export may still embed.
+struct ImageBudget {
+    remaining: usize,
+}
+
+impl ImageBudget {
+    fn new(bytes: usize) -> Self {
+        ImageBudget { remaining: bytes }
+    }
+
+    /// Whether any further image could still fit.
+    fn has_room(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Base64 encodes an image if it fits into the remaining budget.
+    ///
+    /// Images that do not fit are skipped, so smaller images later on may still be embedded.
+    fn embed(&mut self, bytes: &[u8]) -> Option<String> {
+        if bytes.len() > self.remaining {
+            return None;
+        }
+
+        self.remaining -= bytes.len();
+        Some(STANDARD.encode(bytes))
+    }
+}
+
+/// Handles the /mydata command for the invoking user.
+///
+/// Collects every row stored about the caller, downloads their archived profile
+/// pictures from the image store and sends the result as a JSON attachment by DM.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+) -> Result<(), BotError> {
+    // Downloading the images can take longer than Discord's response window
+    interaction.defer_ephemeral(&ctx.http).await?;
+
+    let user_id = i64::from(interaction.user.id);
+
+    let export = collect_user_data(database, image_store, user_id).await?;
+
+    let json = match serde_json::to_vec_pretty(&export) {
+        Ok(json) => json,
+        Err(e) => {
+            error!(user_id, error = ?e, "Failed to serialize data export");
+            interaction
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content("Failed to collect your data. Please try again."),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let omitted_images = export
+        .profile_pictures
+        .iter()
+        .filter(|picture| picture.image.is_none())
+        .count();
+
+    let mut message_content = format!(
+        "Here is all data PFP Checker holds about you: {} profile pictures, {} usernames and {} servers tracking you.",
+        export.profile_pictures.len(),
+        export.usernames.len(),
+        export.tracked_in_servers.len()
+    );
+    if omitted_images > 0 {
+        message_content.push_str(&format!(
+            "\n{} images could not be embedded and are included as links only.",
+            omitted_images
+        ));
+    }
+
+    let message = CreateMessage::new()
+        .content(message_content)
+        .add_file(CreateAttachment::bytes(
+            json,
+            format!("pfp-checker-data-{}.json", user_id),
+        ));
+
+    let content = match interaction.user.direct_message(&ctx.http, message).await {
+        Ok(_) => "Your data has been sent to you in a direct message.",
+        Err(e) => {
+            warn!(user_id, error = ?e, "Failed to send data export");
+            "I couldn't send you a direct message. Please allow DMs from server members and try again."
+        }
+    };
+
+    interaction
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// Gathers every row referencing the user and embeds their archived images.
+async fn collect_user_data(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    user_id: i64,
+) -> Result<DataExport, sqlx::Error> {
+    let user = sqlx::query!(
+        r#"SELECT trackedSince AS tracked_since, removedAt AS removed_at,
+            lastCheckedAt AS last_checked_at, lastSuccessAt AS last_success_at,
+            consecutiveFailures AS consecutive_failures, lastError AS last_error,
+            nextCheckAt AS next_check_at
+        FROM User WHERE discordId = ?"#,
+        user_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    let opted_out_at =
+        sqlx::query_scalar!("SELECT optedOutAt FROM OptOut WHERE userId = ?", user_id)
+            .fetch_optional(database)
+            .await?
+            .flatten();
+
+    let tracked_in_servers = sqlx::query!(
+        "SELECT serverId, addedAt FROM TrackedUserServer WHERE userId = ? ORDER BY addedAt",
+        user_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .filter_map(|entry| {
+        Some(TrackedServerExport {
+            server_id: entry.serverId?.to_string(),
+            added_at: entry.addedAt,
+        })
+    })
+    .collect();
+
+    let pictures = sqlx::query!(
+        "SELECT checksum, changedAt, link, perceptualHash FROM ProfilePicture WHERE userId = ? ORDER BY changedAt",
+        user_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    let mut budget = ImageBudget::new(MAX_EMBEDDED_IMAGE_BYTES);
+    let mut profile_pictures = Vec::with_capacity(pictures.len());
+
+    for picture in pictures {
+        let mut image = None;
+
+        if let Some(link) = &picture.link {
+            if budget.has_room() {
+                match image_store.fetch(link).await {
+                    Ok(bytes) => image = budget.embed(&bytes),
+                    Err(e) => {
+                        warn!(link = %link, error = %e, "Failed to fetch image for data export");
+                    }
+                }
+            }
+        }
+
+        profile_pictures.push(ProfilePictureExport {
+            checksum: picture.checksum,
+            changed_at: picture.changedAt,
+            link: picture.link,
+            perceptual_hash: picture.perceptualHash,
+            image,
+        });
+    }
+
+    let usernames = sqlx::query!(
+        "SELECT username, changedAt FROM UsernameChange WHERE userId = ? ORDER BY changedAt",
+        user_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|entry| UsernameExport {
+        username: entry.username,
+        changed_at: entry.changedAt,
+    })
+    .collect();
+
+    Ok(DataExport {
+        user_id: user_id.to_string(),
+        generated_at: Utc::now().to_rfc3339(),
+        tracked_since: user.as_ref().and_then(|user| user.tracked_since),
+        removed_at: user.as_ref().and_then(|user| user.removed_at),
+        last_checked_at: user.as_ref().and_then(|user| user.last_checked_at),
+        last_success_at: user.as_ref().and_then(|user| user.last_success_at),
+        consecutive_failures: user.as_ref().and_then(|user| user.consecutive_failures),
+        last_error: user.as_ref().and_then(|user| user.last_error.clone()),
+        next_check_at: user.as_ref().and_then(|user| user.next_check_at),
+        opted_out_at,
+        tracked_in_servers,
+        profile_pictures,
+        usernames,
+    })
+}
+
+/// Registers the /mydata command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("mydata")
+        .description("Receive a copy of all data stored about you by direct message.")
+}
+
+pub struct MyDataCommand;
+
+#[async_trait]
+impl SlashCommand for MyDataCommand {
+    fn name(&self) -> &'static str {
+        "mydata"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.image_store.as_ref(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_budget_skips_images_that_do_not_fit() {
+        let mut budget = ImageBudget::new(10);
+
+        assert_eq!(budget.embed(b"abcdef").as_deref(), Some("YWJjZGVm"));
+        assert_eq!(budget.embed(b"too large"), None);
+        assert!(budget.has_room());
+        assert_eq!(budget.embed(b"abcd").as_deref(), Some("YWJjZA=="));
+        assert!(!budget.has_room());
+        assert_eq!(budget.embed(b"x"), None);
+    }
+
+    #[test]
+    fn test_export_serialization() {
+        let export = DataExport {
+            user_id: "123456789012345678".to_string(),
+            generated_at: "2026-10-19T12:00:00+00:00".to_string(),
+            tracked_since: Some(1_000),
+            removed_at: None,
+            last_checked_at: Some(2_000),
+            last_success_at: Some(1_500),
+            consecutive_failures: Some(1),
+            last_error: Some("Unknown User".to_string()),
+            next_check_at: Some(3_800),
+            opted_out_at: None,
+            tracked_in_servers: vec![TrackedServerExport {
+                server_id: "42".to_string(),
+                added_at: Some(1_000),
+            }],
+            profile_pictures: vec![ProfilePictureExport {
+                checksum: Some("abc".to_string()),
+                changed_at: Some(1_000),
+                link: Some("https://i.ibb.co/abc/pfp.png".to_string()),
+                perceptual_hash: None,
+                image: None,
+            }],
+            usernames: vec![UsernameExport {
+                username: Some("alice".to_string()),
+                changed_at: Some(1_000),
+            }],
+        };
+
+        let json = serde_json::to_value(&export).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "userId": "123456789012345678",
+                "generatedAt": "2026-10-19T12:00:00+00:00",
+                "trackedSince": 1000,
+                "removedAt": null,
+                "lastCheckedAt": 2000,
+                "lastSuccessAt": 1500,
+                "consecutiveFailures": 1,
+                "lastError": "Unknown User",
+                "nextCheckAt": 3800,
+                "optedOutAt": null,
+                "trackedInServers": [{ "serverId": "42", "addedAt": 1000 }],
+                "profilePictures": [{
+                    "checksum": "abc",
+                    "changedAt": 1000,
+                    "link": "https://i.ibb.co/abc/pfp.png",
+                    "perceptualHash": null,
+                    "image": null
+                }],
+                "usernames": [{ "username": "alice", "changedAt": 1000 }]
+            })
+        );
+    }
+}
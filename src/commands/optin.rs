@@ -0,0 +1,85 @@
+// ABOUTME: Command that lets users who opted out allow tracking again
+// ABOUTME: Removes the caller's OptOut block so /monitor may track them
+use serenity::async_trait;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Handles the /optin command for the invoking user.
+///
+/// Removes a previous opt-out. The user is not tracked again until someone uses
+/// /monitor on them.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+) -> Result<(), BotError> {
+    let user_id = i64::from(interaction.user.id);
+
+    let deleted = sqlx::query!("DELETE FROM OptOut WHERE userId = ?", user_id)
+        .execute(database)
+        .await?;
+
+    let content = if deleted.rows_affected() > 0 {
+        "You have opted back in. You can be added to the monitoring list again."
+    } else {
+        "You have not opted out of tracking."
+    };
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Registers the /optin command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("optin").description("Allow being tracked again after opting out.")
+}
+
+pub struct OptInCommand;
+
+#[async_trait]
+impl SlashCommand for OptInCommand {
+    fn name(&self) -> &'static str {
+        "optin"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await?;
+        Ok(())
+    }
+}
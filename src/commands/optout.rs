@@ -0,0 +1,182 @@
+// ABOUTME: Command that lets users stop being tracked and deletes everything stored about them
+// ABOUTME: Removes User, ProfilePicture, UsernameChange and TrackedUserServer rows, archived images, and records an OptOut block
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serenity::async_trait;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::error::BotError;
+use crate::util::image_deletion::{self, QueuedImages};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::storage::ImageStore;
+
+/// Handles the /optout command for the invoking user.
+///
+/// Deletes the caller's tracking entry together with their profile picture and
+/// username history, removes the archived images from the image store and records
+/// an opt-out so /monitor refuses to track them again until they use /optin. Images
+/// the store fails to delete stay queued and are retried by the daily cleanup.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+) -> Result<(), BotError> {
+    let user_id = i64::from(interaction.user.id);
+
+    let images = delete_user_data(database, user_id).await?;
+    let deletions = image_deletion::delete_queued(database, image_store, &images.queued).await?;
+    if deletions.failed > 0 {
+        warn!(
+            user_id,
+            failed = deletions.failed,
+            "Archived images of opted out user queued for another deletion attempt"
+        );
+    }
+
+    let mut content = if deletions.failed == 0 {
+        "You have been opted out. All data stored about you has been deleted and you will not be tracked again.".to_string()
+    } else {
+        format!(
+            "You have been opted out. Your tracking records and history have been deleted and you will not be tracked again. {} of {} archived images could not be removed from the image host yet; their removal is retried automatically.",
+            deletions.failed,
+            images.queued.len()
+        )
+    };
+    if !images.without_delete_link.is_empty() {
+        warn!(
+            user_id,
+            images = images.without_delete_link.len(),
+            "Archived images of opted out user must be removed from the image host by the operator"
+        );
+        content.push_str(&format!(
+            " {} archived images were uploaded before deletion was supported and must be removed from the image host by the bot operator.",
+            images.without_delete_link.len()
+        ));
+    }
+    content.push_str(" Use /optin to allow tracking again.");
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Deletes every row stored about a user and records the opt-out in one transaction.
+///
+/// The links of the user's archived images are queued for deletion in the same transaction,
+/// since the images stay on the image host until the store deletes them.
+///
+/// # Returns
+/// * `Result<QueuedImages, sqlx::Error>` - The user's archived images, queued or left to the operator
+pub async fn delete_user_data(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<QueuedImages, sqlx::Error> {
+    let mut transaction = database.begin().await?;
+
+    let links: Vec<String> = sqlx::query_scalar!(
+        "SELECT DISTINCT link FROM ProfilePicture WHERE userId = ? AND link IS NOT NULL",
+        user_id
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .flatten()
+    .collect();
+
+    sqlx::query!("DELETE FROM ProfilePicture WHERE userId = ?", user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query!("DELETE FROM UsernameChange WHERE userId = ?", user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query!("DELETE FROM TrackedUserServer WHERE userId = ?", user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query!("DELETE FROM User WHERE discordId = ?", user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    sqlx::query!(
+        "INSERT OR REPLACE INTO OptOut (userId, optedOutAt) VALUES (?, ?)",
+        user_id,
+        timestamp
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let images = image_deletion::queue(&mut transaction, &links, timestamp).await?;
+
+    transaction.commit().await?;
+
+    Ok(images)
+}
+
+/// Registers the /optout command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("optout")
+        .description("Stop being tracked and delete all data stored about you.")
+}
+
+pub struct OptOutCommand;
+
+#[async_trait]
+impl SlashCommand for OptOutCommand {
+    fn name(&self) -> &'static str {
+        "optout"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.image_store.as_ref(),
+        )
+        .await?;
+        Ok(())
+    }
+}
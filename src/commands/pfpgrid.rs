@@ -0,0 +1,175 @@
+// ABOUTME: Command that shows a user's archived profile pictures as one image grid
+// ABOUTME: Fetches the archived images from the image store and renders a dated contact sheet
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateAttachment, CreateCommandOption,
+    EditInteractionResponse, ResolvedOption, ResolvedValue, User,
+};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use sqlx::SqlitePool;
+use tracing::error;
+
+use crate::error::BotError;
+use crate::util::history;
+use crate::util::imaging::{self, ImagingError, MAX_GRID_TILES};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::storage::ImageStore;
+
+/// Handles the /pfpgrid command.
+///
+/// Renders the archived profile pictures of a monitored user, oldest first, into a single PNG
+/// with the date beneath each picture. The optional `count` limits the grid to the most recent ones.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `options` - The resolved command options
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let mut user: Option<&User> = None;
+    let mut count = MAX_GRID_TILES;
+    for option in options {
+        match (option.name, &option.value) {
+            ("member", ResolvedValue::User(value, _)) => user = Some(value),
+            ("count", ResolvedValue::Integer(value)) => {
+                count = usize::try_from(*value)
+                    .unwrap_or(MAX_GRID_TILES)
+                    .clamp(1, MAX_GRID_TILES)
+            }
+            _ => {}
+        }
+    }
+
+    let Some(user) = user else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
+
+    let user_id = i64::from(user.id);
+
+    sqlx::query!(
+        "SELECT discordId FROM User WHERE discordId = ? AND removedAt IS NULL",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .ok_or_else(|| BotError::NotTracked(user.name.clone()))?;
+
+    // Downloading and encoding the images can take longer than Discord's response window
+    interaction.defer(&ctx.http).await?;
+
+    let records = history::profile_pictures(database, user_id).await?;
+    if records.is_empty() {
+        interaction
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(
+                    "No Profile Pictures have been recorded for this User. Please wait at least 30 minutes and check again.",
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let total = records.len();
+    // Oldest first, so the most recent pictures are at the end
+    let records = &records[total.saturating_sub(count)..];
+    let images = imaging::fetch_archived(image_store, records).await;
+
+    let response = match tokio::task::spawn_blocking(move || imaging::render_grid(&images)).await {
+        Ok(Ok(grid)) => {
+            let mut content = format!("Profile pictures of <@{}>", user.id);
+            if records.len() < total {
+                content.push_str(&format!(" (the last {} of {})", records.len(), total));
+            }
+
+            EditInteractionResponse::new()
+                .content(content)
+                .new_attachment(CreateAttachment::bytes(
+                    grid,
+                    format!("pfp-grid-{}.png", user_id),
+                ))
+        }
+        Ok(Err(ImagingError::NoImages)) => EditInteractionResponse::new()
+            .content("None of the archived pictures of this User could be loaded."),
+        Ok(Err(ImagingError::TooLarge(_))) => EditInteractionResponse::new()
+            .content("The grid of this User is too large to upload. Try a smaller `count`."),
+        Ok(Err(e)) => {
+            error!(user_id, error = %e, "Failed to render grid");
+            EditInteractionResponse::new().content("Failed to render the grid.")
+        }
+        Err(e) => {
+            error!(user_id, error = %e, "Grid rendering panicked");
+            EditInteractionResponse::new().content("Failed to render the grid.")
+        }
+    };
+
+    interaction.edit_response(&ctx.http, response).await?;
+
+    Ok(())
+}
+
+/// Registers the /pfpgrid command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("pfpgrid")
+        .description("Shows the profile picture history of a User as one image.")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "member",
+                "The member whose profile pictures to show.",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "count",
+                "Only show the most recent pictures.",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_GRID_TILES as u64),
+        )
+}
+
+pub struct PfpGridCommand;
+
+#[async_trait]
+impl SlashCommand for PfpGridCommand {
+    fn name(&self) -> &'static str {
+        "pfpgrid"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.image_store.as_ref(),
+            &interaction.data.options(),
+        )
+        .await
+    }
+}
@@ -0,0 +1,159 @@
+// ABOUTME: Command that animates a user's archived profile pictures as a GIF
+// ABOUTME: Fetches the archived images from the image store and renders them in order with their dates
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateAttachment, CreateCommandOption,
+    EditInteractionResponse, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use sqlx::SqlitePool;
+use tracing::error;
+
+use crate::error::BotError;
+use crate::util::history;
+use crate::util::imaging::{self, ImagingError, MAX_TIMELINE_FRAMES};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::storage::ImageStore;
+
+/// Handles the /pfptimeline command.
+///
+/// Renders the archived profile pictures of a monitored user, oldest first, into an animated GIF.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `options` - The resolved command options
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
+        value: ResolvedValue::User(user, _),
+        ..
+    }) = options.first()
+    else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
+
+    let user_id = i64::from(user.id);
+
+    sqlx::query!(
+        "SELECT discordId FROM User WHERE discordId = ? AND removedAt IS NULL",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .ok_or_else(|| BotError::NotTracked(user.name.clone()))?;
+
+    // Downloading and encoding the images can take longer than Discord's response window
+    interaction.defer(&ctx.http).await?;
+
+    let records = history::profile_pictures(database, user_id).await?;
+    if records.is_empty() {
+        interaction
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(
+                    "No Profile Pictures have been recorded for this User. Please wait at least 30 minutes and check again.",
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let total = records.len();
+    // Oldest first, so only the pictures the timeline shows are downloaded
+    let records = &records[total.saturating_sub(MAX_TIMELINE_FRAMES)..];
+    let images = imaging::fetch_archived(image_store, records).await;
+
+    let response =
+        match tokio::task::spawn_blocking(move || imaging::render_timeline(&images)).await {
+            Ok(Ok(gif)) => {
+                let mut content = format!("Profile picture timeline of <@{}>", user.id);
+                if total > MAX_TIMELINE_FRAMES {
+                    content.push_str(&format!(
+                        " (the last {} of {} pictures)",
+                        MAX_TIMELINE_FRAMES, total
+                    ));
+                }
+
+                EditInteractionResponse::new()
+                    .content(content)
+                    .new_attachment(CreateAttachment::bytes(
+                        gif,
+                        format!("pfp-timeline-{}.gif", user_id),
+                    ))
+            }
+            Ok(Err(ImagingError::NoImages)) => EditInteractionResponse::new()
+                .content("None of the archived pictures of this User could be loaded."),
+            Ok(Err(ImagingError::TooLarge(_))) => EditInteractionResponse::new()
+                .content("The timeline of this User is too large to upload."),
+            Ok(Err(e)) => {
+                error!(user_id, error = %e, "Failed to render timeline");
+                EditInteractionResponse::new().content("Failed to render the timeline.")
+            }
+            Err(e) => {
+                error!(user_id, error = %e, "Timeline rendering panicked");
+                EditInteractionResponse::new().content("Failed to render the timeline.")
+            }
+        };
+
+    interaction.edit_response(&ctx.http, response).await?;
+
+    Ok(())
+}
+
+/// Registers the /pfptimeline command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("pfptimeline")
+        .description("Shows the profile picture history of a User as an animation.")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "member",
+                "The member whose profile pictures to animate.",
+            )
+            .required(true),
+        )
+}
+
+pub struct PfpTimelineCommand;
+
+#[async_trait]
+impl SlashCommand for PfpTimelineCommand {
+    fn name(&self) -> &'static str {
+        "pfptimeline"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.image_store.as_ref(),
+            &interaction.data.options(),
+        )
+        .await
+    }
+}
@@ -1,9 +1,14 @@
 use serenity::all::{
     CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
 };
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
 
-pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+use crate::error::BotError;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), BotError> {
     // Calculate API latency (time from command creation to now)
     let now = chrono::Utc::now();
     let command_timestamp = interaction.id.created_at();
@@ -30,3 +35,26 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
 pub fn register() -> CreateCommand {
     CreateCommand::new("ping").description("Check bot latency and responsiveness")
 }
+
+pub struct PingCommand;
+
+#[async_trait]
+impl SlashCommand for PingCommand {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        _state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction).await?;
+        Ok(())
+    }
+}
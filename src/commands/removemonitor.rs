@@ -1,86 +1,180 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serenity::async_trait;
 use serenity::builder::*;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 
 use sqlx::SqlitePool;
 
+use crate::commands::restoremonitor::UNDO_BUTTON_PREFIX;
+use crate::error::BotError;
+use crate::util::confirmation;
+use crate::util::permissions;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Name of the confirmation action for /removemonitor.
+pub const CONFIRMATION_ACTION: &str = "removemonitor";
+
+/// Handles the /removemonitor command.
+///
+/// Shows how much history is archived for the user and asks for confirmation
+/// before removing them. The removal itself happens in [`confirm`].
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
+    grace_days: i64,
     options: &[ResolvedOption<'_>],
-) -> Result<(), serenity::Error> {
-    if let Some(ResolvedOption {
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
         value: ResolvedValue::User(user, _),
         ..
     }) = options.first()
-    {
-        let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
-                                          // trait for sqlx.
-
-        let delete_result = sqlx::query!("DELETE FROM User WHERE discordId = ?", user_id)
-            .execute(database)
-            .await;
-
-        match delete_result {
-            Ok(res) => {
-                if res.rows_affected() > 0 {
-                    interaction
-                        .create_response(
-                            &ctx.http,
-                            CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new()
-                                    .content("Sucessfully deleted user."),
-                            ),
-                        )
-                        .await
-                        .unwrap();
-                } else {
-                    interaction
-                        .create_response(
-                            &ctx.http,
-                            CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new()
-                                    .content("Unable to find user. User may not be tracked."),
-                            ),
-                        )
-                        .await
-                        .unwrap();
-                }
-            }
-            Err(_) => {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content("Unable to delete User. User may not be tracked."),
-                        ),
-                    )
-                    .await
-                    .unwrap();
-            }
-        }
-
-        return Ok(());
-    } else {
-        interaction
-            .create_response(
-                &ctx.http,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new().content("Invalid User ID."),
-                ),
-            )
-            .await
-            .unwrap();
+    else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
+
+    let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
+                                      // trait for sqlx.
+
+    let counts = count_history(database, user_id)
+        .await?
+        .ok_or_else(|| BotError::NotTracked(user.name.clone()))?;
+
+    let (pictures, usernames) = counts;
+    let embed = CreateEmbed::new()
+        .title(format!("Stop monitoring {}?", user.name))
+        .description(format!(
+            "This will delete {} archived profile pictures and {} usernames after a grace period of {} days.",
+            pictures, usernames, grace_days
+        ));
+
+    confirmation::request_confirmation(
+        ctx,
+        interaction,
+        embed,
+        CONFIRMATION_ACTION,
+        user.id.get(),
+        false,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a user from monitoring once the confirmation prompt was accepted.
+///
+/// Stops polling the user but keeps their history for `grace_days`, during which
+/// the removal can be undone with the attached button or /restoremonitor. The
+/// history is purged by the daily cleanup afterwards.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `component` - The confirm button interaction
+/// * `database` - SQLite connection pool
+/// * `grace_days` - Days the history is kept before it is purged
+/// * `user_id` - The user to remove
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn confirm(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    database: &SqlitePool,
+    grace_days: i64,
+    user_id: u64,
+) -> Result<(), BotError> {
+    if !permissions::is_component_permitted(database, component, "removemonitor").await? {
+        return Err(BotError::Permission(
+            "You don't have a role required to use /removemonitor in this server.".to_string(),
+        ));
     }
 
+    let user_id = i64::try_from(user_id).unwrap_or_default();
+
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    let delete_result = sqlx::query!(
+        "UPDATE User SET removedAt = ? WHERE discordId = ? AND removedAt IS NULL",
+        timestamp,
+        user_id
+    )
+    .execute(database)
+    .await?;
+
+    let mut message = CreateInteractionResponseMessage::new()
+        .embeds(vec![])
+        .components(vec![]);
+
+    message = if delete_result.rows_affected() > 0 {
+        let purge_at = timestamp + grace_days * 24 * 60 * 60;
+        let undo_button = CreateButton::new(format!("{}{}", UNDO_BUTTON_PREFIX, user_id))
+            .label("Undo")
+            .style(ButtonStyle::Secondary);
+
+        message
+            .content(format!(
+                "Stopped monitoring <@{}>. Their history is kept until <t:{}:F> and can be restored with /restoremonitor until then.",
+                user_id, purge_at
+            ))
+            .components(vec![CreateActionRow::Buttons(vec![undo_button])])
+    } else {
+        message.content("Unable to find user. User may not be tracked.")
+    };
+
+    component
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+
     Ok(())
 }
 
+/// Counts the archived history of a monitored user.
+///
+/// # Returns
+/// * `Result<Option<(i64, i64)>, sqlx::Error>` - Picture and username counts, `None` if the user is not monitored
+async fn count_history(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<Option<(i64, i64)>, sqlx::Error> {
+    let monitored = sqlx::query_scalar!(
+        "SELECT discordId FROM User WHERE discordId = ? AND removedAt IS NULL",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    if monitored.is_none() {
+        return Ok(None);
+    }
+
+    let pictures = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM ProfilePicture WHERE userId = ?",
+        user_id
+    )
+    .fetch_one(database)
+    .await?;
+
+    let usernames = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM UsernameChange WHERE userId = ?",
+        user_id
+    )
+    .fetch_one(database)
+    .await?;
+
+    Ok(Some((pictures, usernames)))
+}
+
 pub fn register() -> CreateCommand {
     CreateCommand::new("removemonitor")
         .description("Removes a user from the Monitor List.")
+        .default_member_permissions(Permissions::MODERATE_MEMBERS)
+        .dm_permission(false)
         .add_option(
             CreateCommandOption::new(
                 CommandOptionType::User,
@@ -90,3 +184,33 @@ pub fn register() -> CreateCommand {
             .required(true),
         )
 }
+
+pub struct RemoveMonitorCommand;
+
+#[async_trait]
+impl SlashCommand for RemoveMonitorCommand {
+    fn name(&self) -> &'static str {
+        "removemonitor"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            state.removal_grace_days,
+            &interaction.data.options(),
+        )
+        .await?;
+        Ok(())
+    }
+}
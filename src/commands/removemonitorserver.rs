@@ -1,15 +1,25 @@
 // ABOUTME: Command to remove a Discord server from the monitoring list
-// ABOUTME: Deletes the server entry and all associated server icon history from the database
+// ABOUTME: Asks for confirmation, then deletes the server entry and all associated server icon history
+use serenity::async_trait;
 use serenity::builder::*;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 
 use sqlx::SqlitePool;
 
+use crate::error::BotError;
+use crate::util::confirmation;
+use crate::util::permissions;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Name of the confirmation action for /removemonitorserver.
+pub const CONFIRMATION_ACTION: &str = "removemonitorserver";
+
 /// Handles the /removemonitorserver command to remove a server from the monitoring list.
 ///
-/// Requires MANAGE_GUILD permission. Deletes the server entry and all associated
-/// server icon history from the database via CASCADE.
+/// Requires MANAGE_GUILD permission. Shows how many server icons are archived and
+/// asks for confirmation; the deletion happens in [`confirm`].
 ///
 /// # Arguments
 /// * `ctx` - The Serenity context
@@ -17,101 +27,125 @@ use sqlx::SqlitePool;
 /// * `database` - SQLite connection pool
 ///
 /// # Returns
-/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
-) -> Result<(), serenity::Error> {
+) -> Result<(), BotError> {
     // Get the guild (server) from the interaction
-    let guild_id = match interaction.guild_id {
-        Some(id) => i64::from(id),
-        None => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("This command can only be used in a server."),
-                    ),
-                )
-                .await?;
-            return Ok(());
-        }
-    };
+    let guild_id = i64::from(interaction.guild_id.ok_or(BotError::GuildOnly)?);
 
-    // Check if user has permission to manage server
-    if let Some(member) = &interaction.member {
-        if let Some(permissions) = member.permissions {
-            if !permissions.manage_guild() {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(
-                                "You need 'Manage Server' permission to use this command.",
-                            ),
-                        ),
-                    )
-                    .await?;
-                return Ok(());
-            }
-        }
-    }
+    permissions::require_manage_guild(interaction)?;
+
+    let guild_name = interaction
+        .guild_id
+        .and_then(|id| ctx.cache.guild(id))
+        .map(|g| g.name.clone())
+        .unwrap_or_else(|| "this server".to_string());
+
+    let icons = count_server_icons(database, guild_id)
+        .await?
+        .ok_or_else(|| BotError::NotTracked(guild_name.clone()))?;
+
+    let embed = CreateEmbed::new()
+        .title(format!("Stop monitoring {}?", guild_name))
+        .description(format!(
+            "This will immediately delete {} archived server icons. This cannot be undone.",
+            icons
+        ));
+
+    confirmation::request_confirmation(
+        ctx,
+        interaction,
+        embed,
+        CONFIRMATION_ACTION,
+        guild_id as u64,
+        false,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a server from monitoring once the confirmation prompt was accepted.
+///
+/// Deletes the server entry and all associated server icon history via CASCADE.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `component` - The confirm button interaction
+/// * `database` - SQLite connection pool
+/// * `server_id` - The server to remove
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn confirm(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    database: &SqlitePool,
+    server_id: u64,
+) -> Result<(), BotError> {
+    let guild_id = i64::try_from(server_id).unwrap_or_default();
 
     let delete_result = sqlx::query!("DELETE FROM Server WHERE serverId = ?", guild_id)
         .execute(database)
-        .await;
-
-    match delete_result {
-        Ok(res) => {
-            if res.rows_affected() > 0 {
-                let guild_name = interaction
-                    .guild_id
-                    .and_then(|id| ctx.cache.guild(id))
-                    .map(|g| g.name.clone())
-                    .unwrap_or_else(|| "Server".to_string());
-
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(format!(
-                                "Successfully removed {} from monitoring.",
-                                guild_name
-                            )),
-                        ),
-                    )
-                    .await?;
-            } else {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content("Unable to find server. Server may not be tracked."),
-                        ),
-                    )
-                    .await?;
-            }
-        }
-        Err(e) => {
-            eprintln!("Database error while deleting server {}: {:?}", guild_id, e);
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("Unable to delete server. Server may not be tracked."),
-                    ),
-                )
-                .await?;
-        }
-    }
+        .await?;
+
+    let content = if delete_result.rows_affected() > 0 {
+        let guild_name = ctx
+            .cache
+            .guild(GuildId::new(server_id))
+            .map(|g| g.name.clone())
+            .unwrap_or_else(|| "Server".to_string());
+
+        format!("Successfully removed {} from monitoring.", guild_name)
+    } else {
+        "Unable to find server. Server may not be tracked.".to_string()
+    };
+
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .embeds(vec![])
+                    .components(vec![]),
+            ),
+        )
+        .await?;
 
     Ok(())
 }
 
+/// Counts the archived icons of a monitored server.
+///
+/// # Returns
+/// * `Result<Option<i64>, sqlx::Error>` - Number of icons, `None` if the server is not monitored
+async fn count_server_icons(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    let monitored =
+        sqlx::query_scalar!("SELECT serverId FROM Server WHERE serverId = ?", server_id)
+            .fetch_optional(database)
+            .await?;
+
+    if monitored.is_none() {
+        return Ok(None);
+    }
+
+    let icons = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM ServerPicture WHERE serverId = ?",
+        server_id
+    )
+    .fetch_one(database)
+    .await?;
+
+    Ok(Some(icons))
+}
+
 /// Registers the /removemonitorserver command with Discord.
 ///
 /// # Returns
@@ -119,4 +153,29 @@ pub async fn run(
 pub fn register() -> CreateCommand {
     CreateCommand::new("removemonitorserver")
         .description("Removes this server from the monitoring list.")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+}
+
+pub struct RemoveMonitorServerCommand;
+
+#[async_trait]
+impl SlashCommand for RemoveMonitorServerCommand {
+    fn name(&self) -> &'static str {
+        "removemonitorserver"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await?;
+        Ok(())
+    }
 }
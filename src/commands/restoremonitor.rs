@@ -0,0 +1,183 @@
+// ABOUTME: Command to resume monitoring a user removed with /removemonitor during the grace period
+// ABOUTME: Clears the removedAt soft-delete marker so polling continues with the kept history
+use serenity::async_trait;
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::confirmation;
+use crate::util::permissions;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Custom ID prefix of the "Undo" button attached to /removemonitor replies.
+pub const UNDO_BUTTON_PREFIX: &str = "removemonitor_undo_";
+
+/// Handles the /restoremonitor command.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `options` - The resolved command options
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
+        value: ResolvedValue::User(user, _),
+        ..
+    }) = options.first()
+    else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
+
+    let content = if restore_user(database, i64::from(user.id)).await? {
+        format!(
+            "Resumed monitoring {}. Their history has been kept.",
+            user.name
+        )
+    } else {
+        format!(
+            "{} has not been removed recently. Removed users can only be restored during the grace period.",
+            user.name
+        )
+    };
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(content),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Clears the soft-delete marker of a user removed from monitoring.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - `false` if the user was not soft-deleted
+pub async fn restore_user(database: &SqlitePool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE User SET removedAt = NULL WHERE discordId = ? AND removedAt IS NOT NULL",
+        user_id
+    )
+    .execute(database)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Handles a click on the "Undo" button of a /removemonitor reply.
+///
+/// Only the member who removed the user may undo it.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `component` - The button interaction
+/// * `database` - SQLite connection pool
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn handle_undo_button(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    database: &SqlitePool,
+) -> Result<(), BotError> {
+    if !confirmation::clicked_by_invoker(component) {
+        return Err(BotError::Permission(
+            "Only the member who removed the user can undo this.".to_string(),
+        ));
+    }
+
+    // Undoing restores the user, so it needs what /restoremonitor needs
+    if !permissions::is_component_permitted(database, component, "restoremonitor").await? {
+        return Err(BotError::Permission(
+            "You don't have a role required to use /restoremonitor in this server.".to_string(),
+        ));
+    }
+
+    let user_id = component
+        .data
+        .custom_id
+        .strip_prefix(UNDO_BUTTON_PREFIX)
+        .and_then(|id| id.parse::<i64>().ok())
+        .ok_or_else(|| BotError::InvalidInput("Invalid User ID.".to_string()))?;
+
+    let content = if restore_user(database, user_id).await? {
+        format!("Removal undone. <@{}> is monitored again.", user_id)
+    } else {
+        "This removal can no longer be undone.".to_string()
+    };
+
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Registers the /restoremonitor command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("restoremonitor")
+        .description("Resumes monitoring a recently removed user, keeping their history.")
+        .default_member_permissions(Permissions::MODERATE_MEMBERS)
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "memberid",
+                "The User to restore to the monitor list.",
+            )
+            .required(true),
+        )
+}
+
+pub struct RestoreMonitorCommand;
+
+#[async_trait]
+impl SlashCommand for RestoreMonitorCommand {
+    fn name(&self) -> &'static str {
+        "restoremonitor"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            &interaction.data.options(),
+        )
+        .await?;
+        Ok(())
+    }
+}
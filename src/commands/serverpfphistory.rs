@@ -2,263 +2,175 @@
 // ABOUTME: Shows all recorded server icons with timestamps and navigation buttons
 use chrono::DateTime;
 use serenity::all::{
-    ButtonStyle, CommandInteraction, Context, CreateActionRow, CreateButton, CreateEmbed,
-    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    GuildId, ResolvedOption,
 };
-use serenity::builder::{CreateCommand, EditMessage};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
 
 use sqlx::SqlitePool;
 
+use crate::error::BotError;
+use crate::util::history;
 use crate::util::objects::EmbedEntry;
+use crate::util::pagination::{
+    first_page_response, render_gallery_page, HistoryMode, PageSource, PaginatedView,
+};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
 
 pub const ENTRIES_PER_PAGE: usize = 10;
 
 /// Handles the /serverpfphistory command to display paginated server icon history.
 ///
-/// Fetches all server icon records from the database and displays them in
-/// paginated embeds with navigation buttons.
+/// Loads all server icon records through [`ServerPfpHistoryView`] and displays
+/// them in paginated embeds with navigation buttons.
 ///
 /// # Arguments
 /// * `ctx` - The Serenity context
 /// * `interaction` - The command interaction
 /// * `database` - SQLite connection pool
+/// * `options` - The resolved command options
 ///
 /// # Returns
-/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
-) -> Result<(), serenity::Error> {
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
     // Get the guild (server) from the interaction
-    let guild_id = match interaction.guild_id {
-        Some(id) => id,
-        None => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("This command can only be used in a server."),
-                    ),
-                )
-                .await?;
-            return Ok(());
-        }
-    };
+    let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
 
-    let guild_name = interaction
-        .guild_id
-        .and_then(|id| ctx.cache.guild(id))
+    let guild_name = ctx
+        .cache
+        .guild(guild_id)
         .map(|g| g.name.clone())
         .unwrap_or_else(|| "This server".to_string());
 
-    // Fetch server picture history from database
-    let guild_id_i64 = i64::from(guild_id);
-    let entries = sqlx::query!(
-        "SELECT checksum, changedAt, link FROM ServerPicture WHERE serverId = ? ORDER BY changedAt DESC",
-        guild_id_i64
-    )
-    .fetch_all(database)
-    .await;
+    let view: &dyn PaginatedView = match HistoryMode::from_options(options) {
+        HistoryMode::List => &ServerPfpHistoryView,
+        HistoryMode::Gallery => &ServerPfpGalleryView,
+    };
+
+    let source = view.load(ctx, database, guild_id.get()).await?;
+
+    if source.entries.is_empty() {
+        interaction
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(format!(
+                        "{} has no recorded server icon history.",
+                        guild_name
+                    )),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    interaction
+        .create_response(
+            &ctx.http,
+            first_page_response(view, &source, guild_id.get()),
+        )
+        .await?;
+
+    Ok(())
+}
+/// Paginated view of a server's icon history, newest first.
+pub struct ServerPfpHistoryView;
 
-    match entries {
-        Ok(records) => {
-            if records.is_empty() {
-                interaction
-                    .create_response(
-                        &ctx.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(format!(
-                                "{} has no recorded server icon history.",
-                                guild_name
-                            )),
-                        ),
-                    )
-                    .await?;
-                return Ok(());
-            }
+#[async_trait]
+impl PaginatedView for ServerPfpHistoryView {
+    fn name(&self) -> &'static str {
+        "serverpfphistory"
+    }
 
-            let mut embed_entries: Vec<EmbedEntry> = Vec::new();
+    fn entries_per_page(&self) -> usize {
+        ENTRIES_PER_PAGE
+    }
 
-            for record in records {
-                let timestamp = record
-                    .changedAt
-                    .expect("changedAt should never be NULL in ServerPicture");
-                let dt =
-                    DateTime::from_timestamp(timestamp, 0).expect("Invalid timestamp in database");
-                let checksum = record
-                    .checksum
-                    .expect("checksum should never be NULL in ServerPicture");
-                let link = record
+    async fn load(
+        &self,
+        ctx: &Context,
+        database: &SqlitePool,
+        target_id: u64,
+    ) -> Result<PageSource, BotError> {
+        let guild_id = GuildId::new(target_id);
+        let cached_name = ctx.cache.guild(guild_id).map(|g| g.name.clone());
+        let guild_name = match cached_name {
+            Some(name) => name,
+            None => guild_id
+                .to_partial_guild(&ctx.http)
+                .await
+                .map(|guild| guild.name)
+                .unwrap_or_else(|_| "This server".to_string()),
+        };
+
+        let guild_id_i64 = i64::from(guild_id);
+        let entries = history::server_icons(database, guild_id_i64).await?;
+
+        let icons = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let dt = DateTime::from_timestamp(entry.changed_at, 0)?;
+                let checksum = entry.checksum;
+
+                // link can be NULL, so provide a fallback
+                let link = entry
                     .link
-                    .expect("link should never be NULL in ServerPicture");
+                    .clone()
+                    .unwrap_or_else(|| "No link available".to_string());
 
-                embed_entries.push(EmbedEntry {
+                Some(EmbedEntry {
                     title: format!("<t:{}:F>", dt.timestamp()),
                     content: format!("[Link]({})\nChecksum: {}", link, checksum),
                     inline: false,
-                });
-            }
-
-            send_paginated_response(ctx, interaction, &guild_name, guild_id, &embed_entries, 0)
-                .await?;
-        }
-        Err(_) => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("Failed to fetch server icon history."),
-                    ),
-                )
-                .await?;
-        }
+                    image: entry.link,
+                })
+            })
+            .collect();
+
+        Ok(PageSource {
+            title: format!("{} Server Icon History", guild_name),
+            entries: icons,
+        })
     }
-
-    Ok(())
 }
 
-/// Generates a paginated embed response for server icon history (for initial response).
-///
-/// # Arguments
-/// * `guild_name` - The name of the server
-/// * `guild_id` - The server's Guild ID
-/// * `icons` - Slice of icon history entries
-/// * `page` - The page number to display (0-indexed)
-///
-/// # Returns
-/// * `Result<CreateInteractionResponse, serenity::Error>` - The interaction response
-pub async fn get_paginated_embed_response(
-    guild_name: &str,
-    guild_id: GuildId,
-    icons: &[EmbedEntry],
-    page: usize,
-) -> Result<CreateInteractionResponse, serenity::Error> {
-    let total_pages = (icons.len() as f32 / ENTRIES_PER_PAGE as f32).ceil() as usize;
-    let start = page * ENTRIES_PER_PAGE;
-    let end = (start + ENTRIES_PER_PAGE).min(icons.len());
-
-    let embed = CreateEmbed::new()
-        .title(format!("{} Server Icon History", guild_name))
-        .fields(
-            icons[start..end]
-                .iter()
-                .map(|entry| (entry.title.clone(), entry.content.clone(), entry.inline)),
-        )
-        .footer(CreateEmbedFooter::new(format!(
-            "Page {} of {}",
-            page + 1,
-            total_pages
-        )));
-
-    let components = CreateActionRow::Buttons(vec![
-        CreateButton::new(format!("serverpfphistory_first_{}", guild_id))
-            .label("First")
-            .style(ButtonStyle::Primary)
-            .disabled(page == 0),
-        CreateButton::new(format!("serverpfphistory_back_{}_{}", page, guild_id))
-            .label("Back")
-            .style(ButtonStyle::Primary)
-            .disabled(page == 0),
-        CreateButton::new(format!("serverpfphistory_next_{}_{}", page, guild_id))
-            .label("Next")
-            .style(ButtonStyle::Primary)
-            .disabled(end == icons.len()),
-        CreateButton::new(format!("serverpfphistory_last_{}", guild_id))
-            .label("Last")
-            .style(ButtonStyle::Primary)
-            .disabled(end == icons.len()),
-    ]);
-
-    Ok(CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(embed)
-            .components(vec![components]),
-    ))
-}
+/// Gallery of a server's icon history, one icon per page.
+pub struct ServerPfpGalleryView;
 
-/// Generates a paginated embed response for server icon history (for editing existing message).
-///
-/// # Arguments
-/// * `guild_name` - The name of the server
-/// * `guild_id` - The server's Guild ID
-/// * `icons` - Slice of icon history entries
-/// * `page` - The page number to display (0-indexed)
-///
-/// # Returns
-/// * `Result<EditMessage, serenity::Error>` - The message edit builder
-pub async fn get_paginated_embed_edit_response(
-    guild_name: &str,
-    guild_id: GuildId,
-    icons: &[EmbedEntry],
-    page: usize,
-) -> Result<EditMessage, serenity::Error> {
-    let total_pages = (icons.len() as f32 / ENTRIES_PER_PAGE as f32).ceil() as usize;
-    let start = page * ENTRIES_PER_PAGE;
-    let end = (start + ENTRIES_PER_PAGE).min(icons.len());
-
-    let embed = CreateEmbed::new()
-        .title(format!("{} Server Icon History", guild_name))
-        .fields(
-            icons[start..end]
-                .iter()
-                .map(|entry| (entry.title.clone(), entry.content.clone(), entry.inline)),
-        )
-        .footer(CreateEmbedFooter::new(format!(
-            "Page {} of {}",
-            page + 1,
-            total_pages
-        )));
-
-    let components = CreateActionRow::Buttons(vec![
-        CreateButton::new(format!("serverpfphistory_first_{}", guild_id))
-            .label("First")
-            .style(ButtonStyle::Primary)
-            .disabled(page == 0),
-        CreateButton::new(format!("serverpfphistory_back_{}_{}", page, guild_id))
-            .label("Back")
-            .style(ButtonStyle::Primary)
-            .disabled(page == 0),
-        CreateButton::new(format!("serverpfphistory_next_{}_{}", page, guild_id))
-            .label("Next")
-            .style(ButtonStyle::Primary)
-            .disabled(end == icons.len()),
-        CreateButton::new(format!("serverpfphistory_last_{}", guild_id))
-            .label("Last")
-            .style(ButtonStyle::Primary)
-            .disabled(end == icons.len()),
-    ]);
-
-    Ok(EditMessage::new().embed(embed).components(vec![components]))
-}
+#[async_trait]
+impl PaginatedView for ServerPfpGalleryView {
+    fn name(&self) -> &'static str {
+        "serverpfpgallery"
+    }
 
-/// Sends a paginated response to a command interaction.
-///
-/// # Arguments
-/// * `ctx` - The Serenity context
-/// * `interaction` - The command interaction
-/// * `guild_name` - The name of the server
-/// * `guild_id` - The server's Guild ID
-/// * `icons` - Slice of icon history entries
-/// * `page` - The page number to display (0-indexed)
-///
-/// # Returns
-/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
-pub async fn send_paginated_response(
-    ctx: &Context,
-    interaction: &CommandInteraction,
-    guild_name: &str,
-    guild_id: GuildId,
-    icons: &[EmbedEntry],
-    page: usize,
-) -> Result<(), serenity::Error> {
-    let response = get_paginated_embed_response(guild_name, guild_id, icons, page).await?;
+    fn entries_per_page(&self) -> usize {
+        1
+    }
 
-    interaction.create_response(&ctx.http, response).await?;
+    async fn load(
+        &self,
+        ctx: &Context,
+        database: &SqlitePool,
+        target_id: u64,
+    ) -> Result<PageSource, BotError> {
+        ServerPfpHistoryView.load(ctx, database, target_id).await
+    }
 
-    Ok(())
+    fn render_page(
+        &self,
+        source: &PageSource,
+        target_id: u64,
+        page: usize,
+    ) -> CreateInteractionResponseMessage {
+        render_gallery_page(self.name(), source, target_id, page)
+    }
 }
 
 /// Registers the /serverpfphistory command with Discord.
@@ -268,4 +180,34 @@ pub async fn send_paginated_response(
 pub fn register() -> CreateCommand {
     CreateCommand::new("serverpfphistory")
         .description("Displays the server icon history for this server.")
+        .add_option(HistoryMode::option())
+}
+
+pub struct ServerPfpHistoryCommand;
+
+#[async_trait]
+impl SlashCommand for ServerPfpHistoryCommand {
+    fn name(&self) -> &'static str {
+        "serverpfphistory"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            &interaction.data.options(),
+        )
+        .await?;
+        Ok(())
+    }
 }
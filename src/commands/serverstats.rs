@@ -1,16 +1,24 @@
 // ABOUTME: Command to display statistics about server icon changes
-// ABOUTME: Shows average time between icon changes and total change count
-use chrono::{DateTime, Duration, Utc};
+// ABOUTME: Shows interval figures, reused icons, time-of-day patterns and a monthly histogram
+use chrono::{DateTime, Utc};
 use serenity::all::{
-    CommandInteraction, Context, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter,
-    CreateInteractionResponse, CreateInteractionResponseMessage,
+    CommandInteraction, Context, CreateAttachment, CreateEmbed, CreateEmbedAuthor,
+    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
 };
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
 use sqlx::SqlitePool;
 
+use crate::error::BotError;
+use crate::util::charts::{self, STATS_CHART_FILENAME};
+use crate::util::history;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::stats::HistoryStats;
+
 /// Handles the /serverstats command to display server icon change statistics.
 ///
-/// Calculates and displays average time between icon changes and total change count.
+/// Computes statistics over the full icon history and displays them as embed fields.
 ///
 /// # Arguments
 /// * `ctx` - The Serenity context
@@ -18,28 +26,14 @@ use sqlx::SqlitePool;
 /// * `database` - SQLite connection pool
 ///
 /// # Returns
-/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
-) -> Result<(), serenity::Error> {
+) -> Result<(), BotError> {
     // Get the guild (server) from the interaction
-    let guild_id = match interaction.guild_id {
-        Some(id) => i64::from(id),
-        None => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("This command can only be used in a server."),
-                    ),
-                )
-                .await?;
-            return Ok(());
-        }
-    };
+    let guild_id = i64::from(interaction.guild_id.ok_or(BotError::GuildOnly)?);
 
     let guild_name = interaction
         .guild_id
@@ -47,121 +41,56 @@ pub async fn run(
         .map(|g| g.name.clone())
         .unwrap_or_else(|| "This server".to_string());
 
-    let server_entry = sqlx::query!("SELECT * FROM Server WHERE serverId = ?", guild_id)
-        .fetch_one(database)
-        .await;
+    let record = sqlx::query!("SELECT * FROM Server WHERE serverId = ?", guild_id)
+        .fetch_optional(database)
+        .await?
+        .ok_or_else(|| BotError::NotTracked(guild_name.clone()))?;
 
-    match server_entry {
-        Ok(record) => {
-            let icons = sqlx::query!(
-                "SELECT * FROM ServerPicture WHERE serverId = ? ORDER BY changedAt DESC",
-                record.serverId
-            )
-            .fetch_all(database)
-            .await;
-
-            match icons {
-                Ok(entries) => {
-                    let change_count = entries.len();
-
-                    if change_count > 1 {
-                        // Total duration between consecutive icon changes (newest → oldest)
-                        let mut total_duration = Duration::zero();
-
-                        for window in entries.windows(2) {
-                            let newer = &window[0];
-                            let older = &window[1];
-
-                            let newer_dt =
-                                DateTime::<Utc>::from_timestamp(newer.changedAt.unwrap(), 0)
-                                    .expect("Invalid timestamp");
-                            let older_dt =
-                                DateTime::<Utc>::from_timestamp(older.changedAt.unwrap(), 0)
-                                    .expect("Invalid timestamp");
-
-                            let duration = newer_dt.signed_duration_since(older_dt);
-                            total_duration += duration;
-                        }
-
-                        let intervals = (change_count - 1) as f64;
-                        let average_duration = total_duration.num_seconds() as f64 / intervals;
-                        // Convert the average duration to seconds for display
-                        let average_duration_in_seconds = average_duration.round() as i32; // Round to the nearest whole number
-
-                        let average_duration_in_hours = average_duration_in_seconds / 3600;
-                        let average_duration_in_days = average_duration_in_hours / 24;
-
-                        let dt = DateTime::from_timestamp(record.trackedSince.unwrap(), 0).unwrap();
-                        let embed_author = CreateEmbedAuthor::new(guild_name.clone());
-                        let embed_footer =
-                            CreateEmbedFooter::new(format!("Monitored since {}", dt.to_rfc2822()));
-
-                        let embed = CreateEmbed::new()
-                            .title("Average times between server icon changes:")
-                            .author(embed_author)
-                            .footer(embed_footer)
-                            .fields(vec![
-                                ("Hours", format!("{average_duration_in_hours}"), true),
-                                ("Days", format!("{average_duration_in_days}"), true),
-                                ("", "".to_string(), false), // empty row for spacing
-                                (
-                                    "Changes since beginning of Monitoring",
-                                    format!("{change_count}"),
-                                    false,
-                                ),
-                            ]);
-
-                        // Respond with the average time
-                        interaction
-                            .create_response(
-                                &ctx.http,
-                                CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new().embed(embed),
-                                ),
-                            )
-                            .await?;
-                    } else {
-                        // Respond if there's not enough data to calculate an average
-                        interaction
-                            .create_response(
-                                &ctx.http,
-                                CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new().content(
-                                        "Not enough data to calculate average server icon changes.",
-                                    ),
-                                ),
-                            )
-                            .await?;
-                    }
-                }
-                Err(_) => {
-                    interaction
-                        .create_response(
-                            &ctx.http,
-                            CreateInteractionResponse::Message(
-                                CreateInteractionResponseMessage::new().content(
-                                    "No server icons have been recorded. Please wait at least 30 minutes and check again.",
-                                ),
-                            ),
-                        )
-                        .await?;
-                }
-            }
-        }
-        Err(_) => {
-            interaction
-                .create_response(
-                    &ctx.http,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new().content(format!(
-                            "{} is not currently being tracked. You can add it using /monitorserver",
-                            guild_name
-                        )),
+    let entries = history::server_icons(database, guild_id).await?;
+
+    let Some(stats) = HistoryStats::compute(&entries, Utc::now().timestamp()) else {
+        interaction
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(
+                        "No server icons have been recorded. Please wait at least 30 minutes and check again.",
                     ),
-                )
-                .await?;
-        }
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let embed_author = CreateEmbedAuthor::new(guild_name.clone());
+    let mut embed = CreateEmbed::new()
+        .title("Server icon statistics")
+        .author(embed_author)
+        .fields(stats.embed_fields());
+
+    if let Some(dt) = record
+        .trackedSince
+        .and_then(|tracked_since| DateTime::from_timestamp(tracked_since, 0))
+    {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "Monitored since {}",
+            dt.to_rfc2822()
+        )));
+    }
+
+    let mut message = CreateInteractionResponseMessage::new();
+    if let Some(png) = charts::stats_chart(stats).await {
+        embed = embed.image(format!("attachment://{}", STATS_CHART_FILENAME));
+        message = message.add_file(CreateAttachment::bytes(png, STATS_CHART_FILENAME));
     }
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(message.embed(embed)),
+        )
+        .await?;
+
     Ok(())
 }
 
@@ -173,3 +102,26 @@ pub fn register() -> CreateCommand {
     CreateCommand::new("serverstats")
         .description("Shows statistics for server icon changes in this server.")
 }
+
+pub struct ServerStatsCommand;
+
+#[async_trait]
+impl SlashCommand for ServerStatsCommand {
+    fn name(&self) -> &'static str {
+        "serverstats"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await?;
+        Ok(())
+    }
+}
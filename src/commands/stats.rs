@@ -1,142 +1,85 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Utc};
 use serenity::all::{
-    CommandInteraction, Context, CreateCommandOption, CreateEmbed, CreateEmbedAuthor,
-    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedValue,
+    CommandInteraction, Context, CreateAttachment, CreateCommandOption, CreateEmbed,
+    CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedValue,
 };
+use serenity::async_trait;
 use serenity::builder::CreateCommand;
 use serenity::model::application::ResolvedOption;
 use sqlx::SqlitePool;
 
+use crate::error::BotError;
+use crate::util::charts::{self, STATS_CHART_FILENAME};
+use crate::util::history;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+use crate::util::stats::HistoryStats;
+
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &SqlitePool,
     options: &[ResolvedOption<'_>],
-) -> Result<(), serenity::Error> {
-    if let Some(ResolvedOption {
+) -> Result<(), BotError> {
+    let Some(ResolvedOption {
         value: ResolvedValue::User(user, _),
         ..
     }) = options.first()
+    else {
+        return Err(BotError::InvalidInput("Invalid User ID.".to_string()));
+    };
+
+    let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
+                                      // trait for sqlx.
+
+    let record = sqlx::query!("SELECT * FROM User WHERE discordId = ?", user_id)
+        .fetch_optional(database)
+        .await?
+        .ok_or_else(|| BotError::NotTracked(user.name.clone()))?;
+
+    let entries = history::profile_pictures(database, user_id).await?;
+
+    let Some(stats) = HistoryStats::compute(&entries, Utc::now().timestamp()) else {
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("No Profile Pictures have been recorded for this User. Please wait at least 30 minutes and check again.")))
+            .await?;
+        return Ok(());
+    };
+
+    let embed_author = CreateEmbedAuthor::new(user.tag().to_string());
+    let mut embed = CreateEmbed::new()
+        .title("Profile picture statistics")
+        .author(embed_author)
+        .fields(stats.embed_fields());
+
+    if let Some(dt) = record
+        .trackedSince
+        .and_then(|tracked_since| DateTime::from_timestamp(tracked_since, 0))
     {
-        let user_id = i64::from(user.id); // Need to cast until I figure out how to implement the
-                                          // trait for sqlx.
-
-        let user_entry = sqlx::query!("SELECT * FROM User WHERE discordId = ?", user_id)
-            .fetch_one(database)
-            .await;
-
-        match user_entry {
-            Ok(record) => {
-                let pfps = sqlx::query!(
-                    "SELECT * FROM ProfilePicture WHERE userId = ?",
-                    record.discordId
-                )
-                .fetch_all(database)
-                .await;
-
-                match pfps {
-                    Ok(entries) => {
-                        let mut total_duration = Duration::zero(); // Total duration of all changes
-                        let mut count = 0; // Number of profile picture changes
-
-                        for entry in &entries {
-                            // Convert the i64 timestamp to DateTime<Utc>
-                            let dt = DateTime::<Utc>::from_timestamp(entry.changedAt.unwrap(), 0)
-                                .expect("Invalid timestamp");
-
-                            if count > 0 {
-                                // Calculate the duration between the current and previous entry
-                                let prev_entry = &entries[count - 1];
-                                // Use expect to handle the Option, providing a reason for potential panics
-                                let prev_dt = DateTime::<Utc>::from_timestamp(
-                                    prev_entry.changedAt.unwrap(),
-                                    0,
-                                )
-                                .expect("Invalid timestamp");
-                                let duration = dt.signed_duration_since(prev_dt);
-                                total_duration += duration;
-                            }
-                            count += 1;
-                        }
-
-                        if count > 1 {
-                            // Ensure there's more than one entry to calculate an average
-                            let average_duration =
-                                total_duration.num_seconds() as f64 / count as f64;
-                            // Convert the average duration to seconds for display
-                            let average_duration_in_seconds = average_duration.round() as i32; // Round to the nearest whole number
-
-                            let average_duration_in_hours = average_duration_in_seconds / 3600;
-                            let average_duration_in_days = average_duration_in_hours / 24;
-
-                            let dt =
-                                DateTime::from_timestamp(record.trackedSince.unwrap(), 0).unwrap();
-                            let embed_author = CreateEmbedAuthor::new(user.tag().to_string());
-                            let embed_footer = CreateEmbedFooter::new(format!(
-                                "Monitored since {}",
-                                dt.to_rfc2822()
-                            ));
-
-                            let embed = CreateEmbed::new()
-                                .title("Average times between profile picture changes:")
-                                .author(embed_author)
-                                .footer(embed_footer)
-                                .fields(vec![
-                                    ("Hours", format!("{average_duration_in_hours}"), true),
-                                    ("Days", format!("{average_duration_in_days}"), true),
-                                    ("", "".to_string(), false), // empty row for spacing
-                                    (
-                                        "Changes since beginning of Monitoring",
-                                        format!("{count}"),
-                                        false,
-                                    ),
-                                ]);
-
-                            // Respond with the average time
-                            interaction
-                                .create_response(
-                                    &ctx,
-                                    CreateInteractionResponse::Message(
-                                        CreateInteractionResponseMessage::new().embed(embed),
-                                    ),
-                                )
-                                .await
-                                .unwrap();
-                        } else {
-                            // Respond if there's not enough data to calculate an average
-                            interaction.create_response(
-                                &ctx,
-                                CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new()
-                                   .content("Not enough data to calculate an average time between profile picture changes.")
-                                )
-                            ).await.unwrap();
-                        }
-                    }
-                    Err(_) => {
-                        interaction
-                            .create_response(
-                                &ctx,
-                                CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new()
-                                        .content("No Profile Pictures have been recorded for this User. Please wait at least 30 minutes and check again.")))
-                            .await
-                            .unwrap();
-                    }
-                }
-            }
-            Err(_) => {
-                interaction
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content("User is not currently being tracked. You an add the user to the monitor list by using /monitor @user")))
-                    .await
-                    .unwrap();
-            }
-        }
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "Monitored since {}",
+            dt.to_rfc2822()
+        )));
+    }
+
+    let mut message = CreateInteractionResponseMessage::new();
+    if let Some(png) = charts::stats_chart(stats).await {
+        embed = embed.image(format!("attachment://{}", STATS_CHART_FILENAME));
+        message = message.add_file(CreateAttachment::bytes(png, STATS_CHART_FILENAME));
     }
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(message.embed(embed)),
+        )
+        .await?;
+
     Ok(())
 }
 
@@ -152,3 +95,32 @@ pub fn register() -> CreateCommand {
             .required(true),
         )
 }
+
+pub struct StatsCommand;
+
+#[async_trait]
+impl SlashCommand for StatsCommand {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            &interaction.data.options(),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+// ABOUTME: Admin command listing monitored users and the server itself when their checks keep failing
+// ABOUTME: Shows failures in a row, last success, last error and next check so dead entries can be removed
+use serenity::all::{
+    colours, CommandInteraction, Context, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, Permissions,
+};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::check_status::{self, EntityStatus, FAILING_THRESHOLD};
+use crate::util::permissions;
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Most failing users listed, the embed description is limited to 4096 characters.
+const MAX_LISTED_USERS: usize = 15;
+
+/// Longest last error shown per entity.
+const MAX_ERROR_LENGTH: usize = 100;
+
+/// Handles the /trackingerrors command.
+///
+/// Requires MANAGE_GUILD permission. Lists the users monitored in this server and the
+/// server itself if their checks failed at least [`FAILING_THRESHOLD`] times in a row,
+/// e.g. because the account was deleted or the bot was removed from the server.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+) -> Result<(), BotError> {
+    let guild_id = i64::from(interaction.guild_id.ok_or(BotError::GuildOnly)?);
+
+    permissions::require_manage_guild(interaction)?;
+
+    let users = check_status::failing_users_in_server(database, guild_id).await?;
+    let server = check_status::server_status(database, guild_id)
+        .await?
+        .filter(|status| status.consecutive_failures >= FAILING_THRESHOLD);
+
+    let mut embed = CreateEmbed::new()
+        .title("Tracking Errors")
+        .colour(colours::branding::YELLOW);
+
+    if users.is_empty() && server.is_none() {
+        embed = embed
+            .description("All monitored users and this server are checked successfully.")
+            .colour(colours::branding::GREEN);
+    } else {
+        let mut lines: Vec<String> = users
+            .iter()
+            .take(MAX_LISTED_USERS)
+            .map(|status| format!("<@{}>: {}", status.id, describe(status)))
+            .collect();
+        if users.len() > MAX_LISTED_USERS {
+            lines.push(format!("…and {} more", users.len() - MAX_LISTED_USERS));
+        }
+        if !lines.is_empty() {
+            lines.push(String::new());
+            lines.push(
+                "Use /removemonitor to stop monitoring users that no longer exist.".to_string(),
+            );
+        }
+
+        embed = embed.description(lines.join("\n"));
+
+        if let Some(server) = &server {
+            embed = embed.field("This server's icon", describe(server), false);
+        }
+    }
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Summarizes the check status of one entity on a single line.
+fn describe(status: &EntityStatus) -> String {
+    let last_success = match status.last_success_at {
+        Some(timestamp) => format!("<t:{}:R>", timestamp),
+        None => "never".to_string(),
+    };
+
+    let mut line = format!(
+        "{} failures in a row, last success {}",
+        status.consecutive_failures, last_success
+    );
+
+    if let Some(next_check_at) = status.next_check_at {
+        line.push_str(&format!(", next check <t:{}:R>", next_check_at));
+    }
+
+    if let Some(error) = &status.last_error {
+        line.push_str(&format!("\n-# {}", truncate(error, MAX_ERROR_LENGTH)));
+    }
+
+    line
+}
+
+/// Shortens text to at most `max_chars` characters, marking cut text with an ellipsis.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Registers the /trackingerrors command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("trackingerrors")
+        .description("Lists monitored users and this server when their checks keep failing.")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+}
+
+pub struct TrackingErrorsCommand;
+
+#[async_trait]
+impl SlashCommand for TrackingErrorsCommand {
+    fn name(&self) -> &'static str {
+        "trackingerrors"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(ctx, interaction, &state.database).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_never_succeeded() {
+        let status = EntityStatus {
+            id: 1,
+            consecutive_failures: 4,
+            last_success_at: None,
+            last_error: Some("Unknown User".to_string()),
+            next_check_at: Some(5000),
+        };
+
+        assert_eq!(
+            describe(&status),
+            "4 failures in a row, last success never, next check <t:5000:R>\n-# Unknown User"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdefghijkl", 5), "abcd…");
+    }
+}
@@ -0,0 +1,365 @@
+// ABOUTME: Command that finds every tracked user who has used a given profile picture
+// ABOUTME: Fingerprints an attachment, URL or checksum and lists the matching users and time periods
+use std::net::IpAddr;
+use std::time::Duration;
+
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommandOption, CreateEmbed,
+    CreateEmbedFooter, EditInteractionResponse, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+use crate::error::BotError;
+use crate::util::discord::CdnClient;
+use crate::util::external::image_url::{self, DownloadError};
+use crate::util::fingerprint;
+use crate::util::history::{self, PictureMatch, PictureUse};
+use crate::util::slash_command::SlashCommand;
+use crate::util::state::BotState;
+
+/// Largest image accepted for a lookup.
+const MAX_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Most periods listed in the reply.
+const MAX_LISTED: usize = 20;
+
+/// Why the image of a lookup could not be used.
+enum FetchError {
+    TooLarge,
+    Failed(String),
+}
+
+/// How long downloading an image from a user-supplied URL may take.
+const URL_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Where the image of a lookup is downloaded from.
+enum Download {
+    /// A Discord attachment, fetched through the CDN client
+    Attachment(String),
+    /// A URL the user typed, fetched without following redirects or reaching private hosts
+    Url(String),
+}
+
+impl Download {
+    fn url(&self) -> &str {
+        match self {
+            Download::Attachment(url) | Download::Url(url) => url,
+        }
+    }
+}
+
+/// The picture a lookup is made for.
+enum Query {
+    Image(Vec<u8>),
+    Checksum(String),
+}
+
+/// Handles the /whoused command.
+///
+/// Exactly one of the `image`, `url` and `checksum` options has to be given. Images are matched
+/// by checksum and, where perceptual hashes are stored, by visual similarity. The reply is
+/// ephemeral so the looked up users are not alerted.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction
+/// * `database` - SQLite connection pool
+/// * `cdn` - Client downloading attached images
+/// * `options` - The resolved command options
+///
+/// # Returns
+/// * `Result<(), BotError>` - Ok if successful, error otherwise
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &SqlitePool,
+    cdn: &CdnClient,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BotError> {
+    let mut attachment = None;
+    let mut url = None;
+    let mut checksum = None;
+    for option in options {
+        match (option.name, &option.value) {
+            ("image", ResolvedValue::Attachment(value)) => attachment = Some(*value),
+            ("url", ResolvedValue::String(value)) => url = Some(*value),
+            ("checksum", ResolvedValue::String(value)) => checksum = Some(*value),
+            _ => {}
+        }
+    }
+
+    let download = match (attachment, url, checksum) {
+        (Some(attachment), None, None) => {
+            if attachment.size as usize > MAX_IMAGE_BYTES {
+                return Err(BotError::InvalidInput(
+                    "The image is larger than 8 MiB.".to_string(),
+                ));
+            }
+            Some(Download::Attachment(attachment.url.clone()))
+        }
+        (None, Some(url), None) => {
+            if !is_public_https_url(url) {
+                return Err(BotError::InvalidInput(
+                    "Please provide a public https:// image URL.".to_string(),
+                ));
+            }
+            Some(Download::Url(url.to_string()))
+        }
+        (None, None, Some(checksum)) => {
+            if !fingerprint::is_checksum(checksum.trim()) {
+                return Err(BotError::InvalidInput(
+                    "A checksum is the 40 character SHA-1 shown by /pfphistory.".to_string(),
+                ));
+            }
+            None
+        }
+        _ => {
+            return Err(BotError::InvalidInput(
+                "Provide exactly one of `image`, `url` or `checksum`.".to_string(),
+            ))
+        }
+    };
+
+    interaction.defer_ephemeral(&ctx.http).await?;
+
+    let query = match download {
+        Some(download) => match fetch_image(cdn, &download).await {
+            Ok(bytes) => Query::Image(bytes),
+            Err(FetchError::TooLarge) => {
+                interaction
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new().content("The image is larger than 8 MiB."),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Err(FetchError::Failed(e)) => {
+                warn!(url = %download.url(), error = %e, "Failed to download image for lookup");
+                interaction
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .content("The image could not be downloaded."),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => Query::Checksum(checksum.unwrap_or_default().trim().to_lowercase()),
+    };
+
+    let (checksum, perceptual_hash) = match query {
+        Query::Image(bytes) => (
+            fingerprint::checksum(&bytes),
+            fingerprint::perceptual_hash(&bytes),
+        ),
+        Query::Checksum(checksum) => {
+            // Reuse the hash stored with an archived copy so similar pictures are found too
+            let perceptual_hash = sqlx::query_scalar!(
+                "SELECT perceptualHash FROM ProfilePicture WHERE checksum = ? AND perceptualHash IS NOT NULL LIMIT 1",
+                checksum
+            )
+            .fetch_optional(database)
+            .await?
+            .flatten();
+            (checksum, perceptual_hash)
+        }
+    };
+
+    let uses = history::picture_uses(database, &checksum, perceptual_hash.as_deref()).await?;
+
+    interaction
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().embed(result_embed(
+                &uses,
+                &checksum,
+                perceptual_hash.is_some(),
+            )),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Downloads the image of a lookup, refusing anything larger than `MAX_IMAGE_BYTES`.
+async fn fetch_image(cdn: &CdnClient, download: &Download) -> Result<Vec<u8>, FetchError> {
+    match download {
+        Download::Attachment(url) => match cdn.download(url).await {
+            Ok(bytes) if bytes.len() > MAX_IMAGE_BYTES => Err(FetchError::TooLarge),
+            Ok(bytes) => Ok(bytes),
+            Err(e) => Err(FetchError::Failed(e.to_string())),
+        },
+        Download::Url(url) => {
+            match image_url::download(url, MAX_IMAGE_BYTES, URL_DOWNLOAD_TIMEOUT).await {
+                Ok(bytes) => Ok(bytes),
+                Err(DownloadError::TooLarge) => Err(FetchError::TooLarge),
+                Err(e) => Err(FetchError::Failed(e.to_string())),
+            }
+        }
+    }
+}
+
+/// Builds the reply listing the found periods.
+fn result_embed(uses: &[PictureUse], checksum: &str, searched_similar: bool) -> CreateEmbed {
+    let mut embed = CreateEmbed::new().title("Who used this picture");
+
+    if uses.is_empty() {
+        embed = embed.description("No tracked user has used this picture.");
+    } else {
+        let mut lines: Vec<String> = uses.iter().take(MAX_LISTED).map(describe_use).collect();
+        if uses.len() > MAX_LISTED {
+            lines.push(format!("…and {} more", uses.len() - MAX_LISTED));
+        }
+        embed = embed.description(lines.join("\n"));
+
+        if let Some(link) = uses
+            .iter()
+            .find_map(|picture_use| picture_use.link.as_ref())
+        {
+            embed = embed.thumbnail(link);
+        }
+    }
+
+    let footer = if searched_similar {
+        format!("Checksum: {}", checksum)
+    } else {
+        format!("Checksum: {} · only exact copies were searched", checksum)
+    };
+
+    embed.footer(CreateEmbedFooter::new(footer))
+}
+
+/// One line per period: the user, when they used the picture and how well it matches.
+fn describe_use(picture_use: &PictureUse) -> String {
+    let until = match picture_use.until {
+        Some(until) => format!("<t:{}:d>", until),
+        None => "now".to_string(),
+    };
+    let matched = match picture_use.matched {
+        PictureMatch::Exact => "exact".to_string(),
+        PictureMatch::Similar(distance) => format!("similar ({} bits differ)", distance),
+    };
+    let picture = match &picture_use.link {
+        Some(link) => format!(" · [picture]({})", link),
+        None => String::new(),
+    };
+
+    format!(
+        "<@{}> <t:{}:d> – {} · {}{}",
+        picture_use.user_id, picture_use.from, until, matched, picture
+    )
+}
+
+/// Whether a URL is https and does not point at a local or private address.
+fn is_public_https_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    if url.scheme() != "https" || host.eq_ignore_ascii_case("localhost") {
+        return false;
+    }
+
+    // Host names are vetted once they are resolved, right before downloading
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => image_url::is_public(ip),
+        Err(_) => true,
+    }
+}
+
+/// Registers the /whoused command with Discord.
+///
+/// # Returns
+/// * `CreateCommand` - The command builder for registration
+pub fn register() -> CreateCommand {
+    CreateCommand::new("whoused")
+        .description("Finds every tracked user who has used a profile picture.")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Attachment,
+            "image",
+            "The picture to look up.",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "url",
+            "Link to the picture to look up.",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "checksum",
+            "Checksum of an archived picture, as shown by /pfphistory.",
+        ))
+        .default_member_permissions(Permissions::MODERATE_MEMBERS)
+        .dm_permission(false)
+}
+
+pub struct WhoUsedCommand;
+
+#[async_trait]
+impl SlashCommand for WhoUsedCommand {
+    fn name(&self) -> &'static str {
+        "whoused"
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        run(
+            ctx,
+            interaction,
+            &state.database,
+            &state.cdn,
+            &interaction.data.options(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_https_url() {
+        assert!(is_public_https_url("https://i.ibb.co/abc/pfp.png"));
+        assert!(!is_public_https_url("http://i.ibb.co/abc/pfp.png"));
+        assert!(!is_public_https_url("https://localhost/metrics"));
+        assert!(!is_public_https_url("https://127.0.0.1/pfp.png"));
+        assert!(!is_public_https_url("https://192.168.1.10/pfp.png"));
+        assert!(!is_public_https_url("https://[::1]/pfp.png"));
+        assert!(!is_public_https_url("https://[::ffff:10.0.0.1]/pfp.png"));
+        assert!(!is_public_https_url("https://[fd00::1]/pfp.png"));
+        assert!(!is_public_https_url("not a url"));
+    }
+
+    #[test]
+    fn test_describe_use() {
+        let picture_use = PictureUse {
+            user_id: 42,
+            checksum: "abc".to_string(),
+            link: Some("https://i.ibb.co/abc/pfp.png".to_string()),
+            from: 1_700_000_000,
+            until: None,
+            matched: PictureMatch::Similar(3),
+        };
+
+        assert_eq!(
+            describe_use(&picture_use),
+            "<@42> <t:1700000000:d> – now · similar (3 bits differ) · [picture](https://i.ibb.co/abc/pfp.png)"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+// ABOUTME: Crate-wide error type returned by commands and component handlers
+// ABOUTME: Logs failures and turns them into friendly ephemeral replies for the user
+use std::fmt;
+
+use serenity::all::{
+    CommandInteraction, ComponentInteraction, Context, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+};
+
+use tracing::{error, info, warn};
+
+use crate::util::storage::StorageError;
+
+/// Error returned by slash commands, component handlers and paginated views.
+#[derive(Debug)]
+pub enum BotError {
+    /// A request to the Discord API failed.
+    Discord(Box<serenity::Error>),
+    /// A database query failed.
+    Database(sqlx::Error),
+    /// The image store failed.
+    Storage(StorageError),
+    /// The requested user or server is not on the monitoring list.
+    NotTracked(String),
+    /// The member is not allowed to do this; holds the reason shown to them.
+    Permission(String),
+    /// The command only works inside a server.
+    GuildOnly,
+    /// An option or button carried a value that cannot be used.
+    InvalidInput(String),
+    /// No command is registered under this name.
+    UnknownCommand(String),
+}
+
+impl BotError {
+    /// The message shown to the member whose interaction failed.
+    ///
+    /// Internal failures get a generic message; details are only logged.
+    pub fn user_message(&self) -> String {
+        match self {
+            BotError::Discord(_) => {
+                "Discord did not accept the request. Please try again in a moment.".to_string()
+            }
+            BotError::Database(_) => {
+                "The database is not available right now. Please try again in a moment.".to_string()
+            }
+            BotError::Storage(_) => {
+                "The image host is not available right now. Please try again later.".to_string()
+            }
+            BotError::NotTracked(target) => format!(
+                "{} is not being monitored. Use /monitor or /monitorserver to start tracking.",
+                target
+            ),
+            BotError::Permission(reason) => reason.clone(),
+            BotError::GuildOnly => "This command can only be used in a server.".to_string(),
+            BotError::InvalidInput(reason) => reason.clone(),
+            BotError::UnknownCommand(name) => format!("{} is not implemented :(", name),
+        }
+    }
+
+    /// Short name of the variant, used as a metric label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BotError::Discord(_) => "discord",
+            BotError::Database(_) => "database",
+            BotError::Storage(_) => "storage",
+            BotError::NotTracked(_) => "not_tracked",
+            BotError::Permission(_) => "permission",
+            BotError::GuildOnly => "guild_only",
+            BotError::InvalidInput(_) => "invalid_input",
+            BotError::UnknownCommand(_) => "unknown_command",
+        }
+    }
+
+    /// Whether the error is caused by the request rather than a failure of the bot.
+    fn is_expected(&self) -> bool {
+        matches!(
+            self,
+            BotError::NotTracked(_)
+                | BotError::Permission(_)
+                | BotError::GuildOnly
+                | BotError::InvalidInput(_)
+        )
+    }
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Discord(err) => write!(f, "Discord error: {}", err),
+            BotError::Database(err) => write!(f, "Database error: {}", err),
+            BotError::Storage(err) => write!(f, "Storage error: {}", err),
+            BotError::NotTracked(target) => write!(f, "Not tracked: {}", target),
+            BotError::Permission(reason) => write!(f, "Permission denied: {}", reason),
+            BotError::GuildOnly => write!(f, "Used outside of a server"),
+            BotError::InvalidInput(reason) => write!(f, "Invalid input: {}", reason),
+            BotError::UnknownCommand(name) => write!(f, "Unknown command: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for BotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BotError::Discord(err) => Some(err.as_ref()),
+            BotError::Database(err) => Some(err),
+            BotError::Storage(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serenity::Error> for BotError {
+    fn from(err: serenity::Error) -> Self {
+        BotError::Discord(Box::new(err))
+    }
+}
+
+impl From<sqlx::Error> for BotError {
+    fn from(err: sqlx::Error) -> Self {
+        BotError::Database(err)
+    }
+}
+
+impl From<StorageError> for BotError {
+    fn from(err: StorageError) -> Self {
+        BotError::Storage(err)
+    }
+}
+
+/// Logs a failed command and tells the member what went wrong.
+///
+/// Replies ephemerally, or with an ephemeral follow-up if the command already responded.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction that failed
+/// * `error` - The error the command returned
+pub async fn report_command_error(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    error: &BotError,
+) {
+    log_error(error);
+
+    let content = error.user_message();
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content.clone())
+            .ephemeral(true),
+    );
+
+    if interaction
+        .create_response(&ctx.http, response)
+        .await
+        .is_err()
+    {
+        if let Err(why) = interaction
+            .create_followup(
+                &ctx.http,
+                CreateInteractionResponseFollowup::new()
+                    .content(content)
+                    .ephemeral(true),
+            )
+            .await
+        {
+            warn!(error = ?why, "Cannot respond to slash command");
+        }
+    }
+}
+
+/// Logs a failed button click and tells the member what went wrong.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `component` - The component interaction that failed
+/// * `error` - The error the handler returned
+pub async fn report_component_error(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    error: &BotError,
+) {
+    log_error(error);
+
+    let content = error.user_message();
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content.clone())
+            .ephemeral(true),
+    );
+
+    if component
+        .create_response(&ctx.http, response)
+        .await
+        .is_err()
+    {
+        if let Err(why) = component
+            .create_followup(
+                &ctx.http,
+                CreateInteractionResponseFollowup::new()
+                    .content(content)
+                    .ephemeral(true),
+            )
+            .await
+        {
+            warn!(error = ?why, "Cannot respond to component");
+        }
+    }
+}
+
+fn log_error(error: &BotError) {
+    if error.is_expected() {
+        info!(reason = %error, "Interaction rejected");
+    } else {
+        error!(error = ?error, "Interaction failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_errors_hide_details() {
+        let error = BotError::from(sqlx::Error::RowNotFound);
+        assert!(!error.user_message().contains("RowNotFound"));
+        assert!(!error.is_expected());
+    }
+
+    #[test]
+    fn test_not_tracked_names_target() {
+        let error = BotError::NotTracked("SomeUser".to_string());
+        assert!(error
+            .user_message()
+            .starts_with("SomeUser is not being monitored"));
+        assert!(error.is_expected());
+    }
+
+    #[test]
+    fn test_permission_shows_reason() {
+        let error =
+            BotError::Permission("You need 'Manage Server' permission to use this command.".into());
+        assert_eq!(
+            error.user_message(),
+            "You need 'Manage Server' permission to use this command."
+        );
+    }
+
+    #[test]
+    fn test_unknown_command_message() {
+        let error = BotError::UnknownCommand("foo".to_string());
+        assert_eq!(error.user_message(), "foo is not implemented :(");
+    }
+}
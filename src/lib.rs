@@ -0,0 +1,7 @@
+// ABOUTME: Library crate of PFP Checker holding everything except the Discord event loop
+// ABOUTME: Exposes the tracking core, commands and maintenance CLI so integration tests can drive them directly
+pub mod cli;
+pub mod commands;
+pub mod db;
+pub mod error;
+pub mod util;
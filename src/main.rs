@@ -1,23 +1,32 @@
-mod commands;
-mod db;
-mod util;
-
-use serenity::all::UserId;
+use clap::Parser;
+use pfp_checker::cli::{self, Cli, CliCommand};
+use pfp_checker::error::{self, BotError};
+use pfp_checker::{commands, db, util};
 use std::sync::Arc;
 use tokio::task;
 use util::config::Config;
-use util::objects;
-use util::pagination::parse_pagination_button;
-
+use util::discord::{CdnClient, HttpDiscord};
+use util::health::Health;
+use util::pagination::PaginationRouter;
+use util::retention::PruneScope;
+use util::slash_command::CommandRegistry;
+use util::state::BotState;
+use util::storage::ImgBBStore;
+use util::update_runs::RunTrigger;
+
+use chrono::Utc;
 use serenity::async_trait;
-use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage};
-use serenity::model::application::{Command, Interaction};
+use serenity::gateway::{ConnectionStage, ShardStageUpdateEvent};
+use serenity::model::application::{Command, ComponentInteraction, Interaction};
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
 use tokio::time::{interval, Duration};
+use tracing::{error, info, info_span, warn, Instrument};
 
 struct Handler {
-    database: Arc<sqlx::SqlitePool>,
+    state: BotState,
+    commands: CommandRegistry,
+    pagination: PaginationRouter,
 }
 
 #[async_trait]
@@ -25,345 +34,274 @@ impl EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
             Interaction::Command(command) => {
-                let content = match command.data.name.as_str() {
-                    "ping" => {
-                        commands::ping::run(&ctx, &command).await.unwrap();
-                        None
-                    }
-                    "monitor" => {
-                        commands::monitor::run(
-                            &ctx,
-                            &command,
-                            &self.database,
-                            &command.data.options(),
-                        )
-                        .await
-                        .unwrap();
-                        util::chron_update::update_monitored_users(&ctx.http, &self.database).await;
-                        None
-                    }
-                    "removemonitor" => {
-                        commands::removemonitor::run(
-                            &ctx,
-                            &command,
-                            &self.database,
-                            &command.data.options(),
-                        )
-                        .await
-                        .unwrap();
-                        None
-                    }
-                    "pfphistory" => {
-                        commands::pfphistory::run(
-                            &ctx,
-                            &command,
-                            &self.database,
-                            &command.data.options(),
-                        )
-                        .await
-                        .unwrap();
-                        None
-                    }
-                    "usernamehistory" => {
-                        commands::usernamehistory::run(
-                            &ctx,
-                            &command,
-                            &self.database,
-                            &command.data.options(),
-                        )
-                        .await
-                        .unwrap();
-                        None
-                    }
-                    "stats" => {
-                        commands::stats::run(
-                            &ctx,
-                            &command,
-                            &self.database,
-                            &command.data.options(),
-                        )
-                        .await
-                        .unwrap();
-                        None
-                    }
-                    "monitorserver" => {
-                        commands::monitorserver::run(&ctx, &command, &self.database)
-                            .await
-                            .unwrap();
-                        util::chron_update::update_monitored_servers(&ctx.http, &self.database)
-                            .await;
-                        None
-                    }
-                    "removemonitorserver" => {
-                        commands::removemonitorserver::run(&ctx, &command, &self.database)
-                            .await
-                            .unwrap();
-                        None
-                    }
-                    "serverpfphistory" => {
-                        commands::serverpfphistory::run(&ctx, &command, &self.database)
-                            .await
-                            .unwrap();
-                        None
-                    }
-                    "serverstats" => {
-                        commands::serverstats::run(&ctx, &command, &self.database)
-                            .await
-                            .unwrap();
-                        None
-                    }
-                    _ => Some(format!("{} is not implemented :(", command.data.name)),
-                };
-
-                if let Some(content) = content {
-                    let data = CreateInteractionResponseMessage::new().content(content);
-                    let builder = CreateInteractionResponse::Message(data);
-                    if let Err(why) = command.create_response(&ctx.http, builder).await {
-                        println!("Cannot respond to slash command: {why}");
-                    }
-                }
+                self.commands.dispatch(&ctx, &command, &self.state).await;
             }
             Interaction::Component(component) => {
                 let custom_id = &component.data.custom_id;
-                let mut sender_message = component.message.to_owned();
-                if custom_id.starts_with("pfphistory_") {
-                    if let Ok(button) = parse_pagination_button(custom_id) {
-                        let user_id = UserId::new(button.target_id);
-
-                        // Fetch the user and pfps data again
-                        let user = user_id.to_user(&ctx.http).await.unwrap();
-                        let pfps = fetch_profile_pictures(&self.database, i64::from(user_id))
-                            .await
-                            .unwrap();
-
-                        let new_page = button
-                            .resolve_new_page(pfps.len(), commands::pfphistory::ENTRIES_PER_PAGE);
-
-                        let response = commands::pfphistory::get_paginated_embed_edit_response(
-                            &user, &pfps, new_page,
+                let span = info_span!(
+                    "component",
+                    custom_id = %custom_id,
+                    user_id = %component.user.id,
+                );
+
+                async {
+                    let result = if custom_id.starts_with(util::confirmation::CUSTOM_ID_PREFIX) {
+                        self.handle_confirmation(&ctx, &component).await
+                    } else if custom_id.starts_with(commands::restoremonitor::UNDO_BUTTON_PREFIX) {
+                        commands::restoremonitor::handle_undo_button(
+                            &ctx,
+                            &component,
+                            &self.state.database,
                         )
                         .await
-                        .unwrap();
-
-                        if let Err(why) = component
-                            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+                    } else {
+                        self.pagination
+                            .dispatch(&ctx, &component, &self.state.database)
                             .await
-                        {
-                            println!("Cannot respond to slash command: {why}")
-                        }
+                    };
 
-                        if let Err(why) = sender_message.edit(&ctx.http, response).await {
-                            println!("Cannot respond to slash command: {why}");
-                        }
+                    if let Err(why) = result {
+                        error::report_component_error(&ctx, &component, &why).await;
                     }
                 }
+                .instrument(span)
+                .await;
+            }
+            _ => {}
+        }
+    }
 
-                if custom_id.starts_with("usernamehistory_") {
-                    if let Ok(button) = parse_pagination_button(custom_id) {
-                        let user_id = UserId::new(button.target_id);
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        let connected = event.new == ConnectionStage::Connected;
+        info!(shard = %event.shard_id, stage = %event.new, "Gateway stage changed");
+        self.state
+            .health
+            .set_gateway_connected(connected, Utc::now().timestamp());
+    }
 
-                        // Fetch the user and pfps data again
-                        let user = user_id.to_user(&ctx.http).await.unwrap();
-                        let pfps = fetch_usernames(&self.database, i64::from(user_id))
-                            .await
-                            .unwrap();
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!(user = %ready.user.name, "Connected to the gateway");
+        self.state
+            .health
+            .set_gateway_connected(true, Utc::now().timestamp());
+
+        match Command::set_global_commands(&ctx.http, self.commands.definitions()).await {
+            Ok(_) => info!("Updated global application commands"),
+            Err(why) => error!(error = ?why, "Failed to update global application commands"),
+        }
 
-                        let new_page = button.resolve_new_page(
-                            pfps.len(),
-                            commands::usernamehistory::ENTRIES_PER_PAGE,
-                        );
+        let database_clone = Arc::clone(&self.state.database);
+        let health = Arc::clone(&self.state.health);
+        let image_store_clone = Arc::clone(&self.state.image_store);
+        let cdn = Arc::clone(&self.state.cdn);
 
-                        let response =
-                            commands::usernamehistory::get_paginated_embed_edit_response(
-                                &user, &pfps, new_page,
-                            )
-                            .await
-                            .unwrap();
+        let retention = self.state.retention;
+        let removal_grace_days = self.state.removal_grace_days;
+        let retention_database = Arc::clone(&self.state.database);
+        let retention_image_store = Arc::clone(&self.state.image_store);
 
-                        if let Err(why) = component
-                            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
-                            .await
-                        {
-                            println!("Cannot respond to slash command: {why}")
-                        }
+        let retention_scheduler = task::spawn(async move {
+            let mut interval = interval(Duration::from_secs(24 * 60 * 60));
 
-                        if let Err(why) = sender_message.edit(&ctx.http, response).await {
-                            println!("Cannot respond to slash command: {why}");
-                        }
-                    }
+            loop {
+                interval.tick().await;
+                match util::retention::prune(
+                    &retention_database,
+                    retention_image_store.as_ref(),
+                    retention,
+                    PruneScope::All,
+                    false,
+                )
+                .await
+                {
+                    Ok(report) => info!(
+                        rows = report.total_rows(),
+                        images = report.images,
+                        failed_image_deletions = report.failed_image_deletions,
+                        "Retention pruned history"
+                    ),
+                    Err(why) => error!(error = ?why, "Retention pruning failed"),
                 }
 
-                if custom_id.starts_with("serverpfphistory_") {
-                    if let Ok(button) = parse_pagination_button(custom_id) {
-                        let guild_id = serenity::all::GuildId::new(button.target_id);
-
-                        // Fetch the guild and server icons data again
-                        let guild = guild_id.to_partial_guild(&ctx.http).await.unwrap();
-                        let icons = fetch_server_icons(&self.database, i64::from(guild_id))
-                            .await
-                            .unwrap();
-
-                        let new_page = button.resolve_new_page(
-                            icons.len(),
-                            commands::serverpfphistory::ENTRIES_PER_PAGE,
-                        );
-
-                        let response =
-                            commands::serverpfphistory::get_paginated_embed_edit_response(
-                                &guild.name,
-                                guild_id,
-                                &icons,
-                                new_page,
-                            )
-                            .await
-                            .unwrap();
+                match util::retention::purge_removed_users(
+                    &retention_database,
+                    retention_image_store.as_ref(),
+                    removal_grace_days,
+                )
+                .await
+                {
+                    Ok(report) => info!(
+                        rows = report.total_rows(),
+                        images = report.images,
+                        failed_image_deletions = report.failed_image_deletions,
+                        "Purged history of removed users"
+                    ),
+                    Err(why) => error!(error = ?why, "Purging removed users failed"),
+                }
 
-                        if let Err(why) = component
-                            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
-                            .await
-                        {
-                            println!("Cannot respond to slash command: {why}")
-                        }
+                match util::image_deletion::retry_pending(
+                    &retention_database,
+                    retention_image_store.as_ref(),
+                )
+                .await
+                {
+                    Ok(report) => info!(
+                        deleted = report.deleted,
+                        failed = report.failed,
+                        "Retried queued image deletions"
+                    ),
+                    Err(why) => error!(error = ?why, "Retrying image deletions failed"),
+                }
 
-                        if let Err(why) = sender_message.edit(&ctx.http, response).await {
-                            println!("Cannot respond to slash command: {why}");
-                        }
-                    }
+                match util::update_runs::prune_runs(&retention_database, Utc::now().timestamp())
+                    .await
+                {
+                    Ok(runs) => info!(runs, "Pruned old update passes"),
+                    Err(why) => error!(error = ?why, "Pruning update passes failed"),
                 }
             }
-            _ => {}
-        }
-    }
-
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
-
-        let _ = Command::set_global_commands(
-            &ctx.http,
-            vec![
-                commands::ping::register(),
-                commands::monitor::register(),
-                commands::removemonitor::register(),
-                commands::pfphistory::register(),
-                commands::usernamehistory::register(),
-                commands::stats::register(),
-                commands::monitorserver::register(),
-                commands::removemonitorserver::register(),
-                commands::serverpfphistory::register(),
-                commands::serverstats::register(),
-            ],
-        )
-        .await
-        .unwrap();
-
-        println!("Updated Global Application Commands");
-
-        let database_clone = Arc::clone(&self.database);
+        });
 
         let update_scheduler = task::spawn(async move {
-            let mut interval = interval(Duration::from_secs(30 * 60));
+            let mut interval = interval(Duration::from_secs(
+                util::chron_update::UPDATE_INTERVAL_SECS,
+            ));
+
+            let discord = HttpDiscord::new(&ctx.http, &cdn);
 
             loop {
                 interval.tick().await;
-                util::chron_update::update_monitored_users(&ctx.http, &database_clone).await;
-                util::chron_update::update_monitored_servers(&ctx.http, &database_clone).await;
+                let users_checked = util::chron_update::update_monitored_users(
+                    &discord,
+                    &database_clone,
+                    image_store_clone.as_ref(),
+                    RunTrigger::Schedule,
+                )
+                .await;
+                util::alerts::run(ctx.http.as_ref(), &database_clone, Utc::now().timestamp()).await;
+                let servers_checked = util::chron_update::update_monitored_servers(
+                    &discord,
+                    &database_clone,
+                    image_store_clone.as_ref(),
+                    RunTrigger::Schedule,
+                )
+                .await;
+
+                // A failed pass leaves readiness to go stale
+                if users_checked && servers_checked {
+                    health.record_update_pass(Utc::now().timestamp());
+                } else {
+                    warn!("Update pass failed, not refreshing readiness");
+                }
             }
         });
 
-        let _ = update_scheduler.await;
+        let _ = tokio::join!(update_scheduler, retention_scheduler);
     }
 }
 
-async fn fetch_usernames(
-    database: &sqlx::SqlitePool,
-    user_id: i64,
-) -> Result<Vec<objects::EmbedEntry>, sqlx::Error> {
-    let entries = sqlx::query!("SELECT * FROM UsernameChange WHERE userId = ?", user_id)
-        .fetch_all(database)
-        .await?;
-
-    Ok(entries
-        .into_iter()
-        .map(|entry| {
-            let tracking_start_date = entry.changedAt.unwrap();
-            let dt = chrono::DateTime::from_timestamp(tracking_start_date, 0).unwrap();
-            objects::EmbedEntry {
-                title: format!("Username first recorded <t:{}:R>", dt.timestamp()),
-                content: entry.username.unwrap().to_string(),
-                inline: false,
-            }
-        })
-        .collect())
-}
+impl Handler {
+    /// Runs the destructive action behind a Confirm/Cancel prompt once it is confirmed.
+    async fn handle_confirmation(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+    ) -> Result<(), BotError> {
+        let button = util::confirmation::parse_confirmation_button(&component.data.custom_id)
+            .map_err(|why| BotError::InvalidInput(format!("Invalid confirmation button: {why}")))?;
+
+        if !util::confirmation::resolve(ctx, component, &button).await? {
+            return Ok(());
+        }
 
-async fn fetch_profile_pictures(
-    database: &sqlx::SqlitePool,
-    user_id: i64,
-) -> Result<Vec<objects::EmbedEntry>, sqlx::Error> {
-    let entries = sqlx::query!("SELECT * FROM ProfilePicture WHERE userId = ?", user_id)
-        .fetch_all(database)
-        .await?;
-
-    Ok(entries
-        .into_iter()
-        .map(|entry| {
-            let tracking_start_date = entry.changedAt.unwrap();
-            let dt = chrono::DateTime::from_timestamp(tracking_start_date, 0).unwrap();
-            objects::EmbedEntry {
-                title: format!("Profile Picture first recorded <t:{}:R>", dt.timestamp()),
-                content: format!(
-                    "Link: [Look at the previous picture]({})\nChecksum: {}",
-                    entry.link.unwrap(),
-                    entry.checksum.unwrap()
-                ),
-                inline: false,
+        match button.action.as_str() {
+            commands::removemonitor::CONFIRMATION_ACTION => {
+                commands::removemonitor::confirm(
+                    ctx,
+                    component,
+                    &self.state.database,
+                    self.state.removal_grace_days,
+                    button.target_id,
+                )
+                .await
             }
-        })
-        .collect())
-}
-
-async fn fetch_server_icons(
-    database: &sqlx::SqlitePool,
-    server_id: i64,
-) -> Result<Vec<objects::EmbedEntry>, sqlx::Error> {
-    let entries = sqlx::query!(
-        "SELECT * FROM ServerPicture WHERE serverId = ? ORDER BY changedAt DESC",
-        server_id
-    )
-    .fetch_all(database)
-    .await?;
-
-    Ok(entries
-        .into_iter()
-        .filter_map(|entry| {
-            // changedAt and checksum are in PRIMARY KEY, so they're NOT NULL
-            let tracking_start_date = entry.changedAt?;
-            let checksum = entry.checksum?;
-            let dt = chrono::DateTime::from_timestamp(tracking_start_date, 0)?;
-
-            // link can be NULL, so provide a fallback
-            let link = entry
-                .link
-                .unwrap_or_else(|| "No link available".to_string());
-
-            Some(objects::EmbedEntry {
-                title: format!("<t:{}:F>", dt.timestamp()),
-                content: format!("[Link]({})\nChecksum: {}", link, checksum),
-                inline: false,
-            })
-        })
-        .collect())
+            commands::removemonitorserver::CONFIRMATION_ACTION => {
+                commands::removemonitorserver::confirm(
+                    ctx,
+                    component,
+                    &self.state.database,
+                    button.target_id,
+                )
+                .await
+            }
+            _ => Err(BotError::InvalidInput(format!(
+                "Unknown confirmation action: {}",
+                button.action
+            ))),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
+    let command = Cli::parse().command.unwrap_or(CliCommand::Run);
+
+    if let CliCommand::Run = command {
+        run_bot().await;
+        return;
+    }
+
+    // Maintenance subcommands log like the bot and report the outcome on the terminal
+    util::logging::init(util::logging::LogFormat::Text);
+    if let Err(why) = cli::run(command).await {
+        eprintln!("{why}");
+        std::process::exit(1);
+    }
+}
+
+/// Connects to Discord and runs the bot until the client stops.
+async fn run_bot() {
     let config = Config::from_env().expect("Failed to load configuration.");
+    util::logging::init(config.log_format);
 
     let database = db::connection::establish_connection(&config.database_url)
         .await
         .expect("Failed to establish database connection.");
 
-    let handler = Handler { database };
+    let image_store =
+        Arc::new(ImgBBStore::new(config.imgbb_key).with_upload_url(config.imgbb_upload_url));
+
+    let cdn = Arc::new(CdnClient::new(
+        config.discord_cdn_url,
+        util::discord::DOWNLOAD_TIMEOUT,
+    ));
+
+    let health = Arc::new(Health::new(
+        util::chron_update::UPDATE_INTERVAL_SECS as i64,
+        config.ready_max_missed_passes,
+    ));
+
+    let http_database = Arc::clone(&database);
+    let http_health = Arc::clone(&health);
+    let http_addr = config.http_addr;
+    task::spawn(async move {
+        if let Err(why) = util::http::serve(http_addr, http_database, http_health).await {
+            error!(error = ?why, "HTTP server failed");
+        }
+    });
+
+    let handler = Handler {
+        state: BotState {
+            database,
+            image_store,
+            cdn,
+            retention: config.retention,
+            removal_grace_days: config.removal_grace_days,
+            health,
+        },
+        commands: commands::registry(),
+        pagination: commands::paginated_views(),
+    };
 
     // Build our client.
     let mut client = Client::builder(config.discord_token, GatewayIntents::empty())
@@ -372,6 +310,6 @@ async fn main() {
         .expect("Error creating client");
 
     if let Err(why) = client.start().await {
-        println!("Client error: {why:?}");
+        error!(error = ?why, "Client error");
     }
 }
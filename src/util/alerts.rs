@@ -0,0 +1,505 @@
+// ABOUTME: Background job alerting moderators when a tracked user takes on another tracked user's avatar or name
+// ABOUTME: Scans newly recorded history for exact and near matches and posts them to each server's alert channel
+use std::collections::HashSet;
+
+use serenity::all::{colours, ChannelId, CreateActionRow, CreateEmbed, CreateMessage, Http};
+use sqlx::SqlitePool;
+use tracing::{error, info, warn};
+
+use crate::util::history::{self, PictureMatch};
+use crate::util::pagination::open_view_button;
+
+/// Pictures or names used by more accounts than this are common, such as Discord's default
+/// avatars, and are not alerted on.
+pub const COMMON_MATCH_USERS: usize = 5;
+
+/// What a newly recorded entry matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchedValue {
+    Avatar {
+        checksum: String,
+        link: Option<String>,
+        matched: PictureMatch,
+    },
+    Name {
+        name: String,
+        other_name: String,
+    },
+}
+
+/// A user recorded with an avatar or display name that another tracked user had before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimilarityMatch {
+    pub user_id: i64,
+    /// Unix timestamp of the new record.
+    pub changed_at: i64,
+    pub other_user_id: i64,
+    /// Unix timestamp of when the other user started using the avatar or name.
+    pub other_from: i64,
+    /// Unix timestamp of the other user's next change, `None` if still in use.
+    pub other_until: Option<i64>,
+    pub value: MatchedValue,
+}
+
+/// Channel a server receives alerts in, `None` if alerts are off.
+pub async fn fetch_alert_channel(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT channelId FROM AlertChannel WHERE serverId = ?",
+        server_id
+    )
+    .fetch_optional(database)
+    .await
+    .map(Option::flatten)
+}
+
+/// Sends the alerts of a server to a channel, replacing any earlier channel.
+pub async fn set_alert_channel(
+    database: &SqlitePool,
+    server_id: i64,
+    channel_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO AlertChannel (serverId, channelId) VALUES (?, ?)",
+        server_id,
+        channel_id
+    )
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+/// Turns alerts off for a server.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - Whether alerts were on
+pub async fn clear_alert_channel(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM AlertChannel WHERE serverId = ?", server_id)
+        .execute(database)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Alert channels of the servers a user is monitored in.
+pub async fn alert_channels_for_user(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let channels = sqlx::query_scalar!(
+        r#"SELECT AlertChannel.channelId AS "channel_id!: i64" FROM AlertChannel
+        JOIN TrackedUserServer ON TrackedUserServer.serverId = AlertChannel.serverId
+        WHERE TrackedUserServer.userId = ? AND AlertChannel.channelId IS NOT NULL"#,
+        user_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(channels)
+}
+
+/// How far the alert job has scanned the history, `None` before the very first scan.
+pub async fn scan_position(database: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar!("SELECT scannedUntil FROM AlertScan WHERE id = 1")
+        .fetch_optional(database)
+        .await
+}
+
+/// Records that the history up to and including `position` has been scanned.
+pub async fn set_scan_position(database: &SqlitePool, position: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO AlertScan (id, scannedUntil) VALUES (1, ?)",
+        position
+    )
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+/// Matches found by one scan of the history.
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub matches: Vec<SimilarityMatch>,
+    /// Latest `changedAt` among the scanned records, `None` if nothing new was recorded
+    pub scanned_until: Option<i64>,
+}
+
+/// Finds avatars and display names recorded in `(since, until]` that another user had before.
+///
+/// Avatars match by checksum or perceptual hash, names exactly or after [`normalize_name`];
+/// both are looked up through indexes, so a scan only reads the new records and their matches.
+/// Returning to one's own earlier avatar or name is not a new match, and values shared by more
+/// than [`COMMON_MATCH_USERS`] other users are skipped.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `since` - Exclusive start of the scanned period as a Unix timestamp
+/// * `until` - Inclusive end of the scanned period as a Unix timestamp
+pub async fn find_matches(
+    database: &SqlitePool,
+    since: i64,
+    until: i64,
+) -> Result<Scan, sqlx::Error> {
+    let mut scan = Scan::default();
+    avatar_matches(database, since, until, &mut scan).await?;
+    name_matches(database, since, until, &mut scan).await?;
+    Ok(scan)
+}
+
+async fn avatar_matches(
+    database: &SqlitePool,
+    since: i64,
+    until: i64,
+    scan: &mut Scan,
+) -> Result<(), sqlx::Error> {
+    let records = sqlx::query!(
+        r#"SELECT userId AS "user_id!: i64", checksum AS "checksum!: String", changedAt AS "changed_at!: i64",
+            link, perceptualHash AS perceptual_hash,
+            EXISTS (
+                SELECT 1 FROM ProfilePicture AS earlier
+                WHERE earlier.userId = ProfilePicture.userId AND earlier.checksum = ProfilePicture.checksum
+                    AND earlier.changedAt < ProfilePicture.changedAt
+            ) AS "reverted!: bool"
+        FROM ProfilePicture WHERE changedAt > ? AND changedAt <= ? ORDER BY changedAt"#,
+        since,
+        until
+    )
+    .fetch_all(database)
+    .await?;
+
+    for record in records {
+        scan.scanned_until = scan.scanned_until.max(Some(record.changed_at));
+        if record.reverted {
+            continue;
+        }
+
+        let mut uses = history::picture_uses(
+            database,
+            &record.checksum,
+            record.perceptual_hash.as_deref(),
+        )
+        .await?;
+
+        // Best match per other user, uses come sorted by match quality
+        let mut seen = HashSet::from([record.user_id]);
+        uses.retain(|picture_use| seen.insert(picture_use.user_id));
+
+        if uses.len() > COMMON_MATCH_USERS {
+            continue;
+        }
+
+        scan.matches
+            .extend(uses.into_iter().map(|other| SimilarityMatch {
+                user_id: record.user_id,
+                changed_at: record.changed_at,
+                other_user_id: other.user_id,
+                other_from: other.from,
+                other_until: other.until,
+                value: MatchedValue::Avatar {
+                    checksum: record.checksum.clone(),
+                    link: record.link.clone(),
+                    matched: other.matched,
+                },
+            }));
+    }
+
+    Ok(())
+}
+
+async fn name_matches(
+    database: &SqlitePool,
+    since: i64,
+    until: i64,
+    scan: &mut Scan,
+) -> Result<(), sqlx::Error> {
+    fill_normalized_names(database).await?;
+
+    let names = sqlx::query!(
+        r#"SELECT userId AS "user_id!: i64", username AS "username!: String", changedAt AS "changed_at!: i64",
+            normalizedName AS "normalized_name!: String",
+            EXISTS (
+                SELECT 1 FROM UsernameChange AS earlier
+                WHERE earlier.userId = UsernameChange.userId AND earlier.normalizedName = UsernameChange.normalizedName
+                    AND earlier.changedAt < UsernameChange.changedAt
+            ) AS "reverted!: bool"
+        FROM UsernameChange
+        WHERE changedAt > ? AND changedAt <= ? AND username IS NOT NULL AND normalizedName IS NOT NULL
+        ORDER BY changedAt"#,
+        since,
+        until
+    )
+    .fetch_all(database)
+    .await?;
+
+    for record in names {
+        scan.scanned_until = scan.scanned_until.max(Some(record.changed_at));
+        if record.normalized_name.is_empty() || record.reverted {
+            continue;
+        }
+
+        let other_users = sqlx::query_scalar!(
+            "SELECT COUNT(DISTINCT userId) FROM UsernameChange WHERE normalizedName = ? AND userId != ?",
+            record.normalized_name,
+            record.user_id
+        )
+        .fetch_one(database)
+        .await?;
+        if other_users as usize > COMMON_MATCH_USERS {
+            continue;
+        }
+
+        let mut candidates = sqlx::query!(
+            r#"SELECT userId AS "user_id!: i64", username AS "username!: String", changedAt AS "changed_at!: i64",
+                (SELECT MIN(next.changedAt) FROM UsernameChange AS next
+                    WHERE next.userId = UsernameChange.userId AND next.changedAt > UsernameChange.changedAt
+                ) AS "next_changed_at: i64"
+            FROM UsernameChange
+            WHERE normalizedName = ? AND userId != ? AND username IS NOT NULL AND changedAt IS NOT NULL"#,
+            record.normalized_name,
+            record.user_id
+        )
+        .fetch_all(database)
+        .await?;
+
+        // Exact matches first, then the earliest use per other user
+        candidates.sort_by_key(|other| (other.username != record.username, other.changed_at));
+        let mut seen = HashSet::new();
+        candidates.retain(|other| seen.insert(other.user_id));
+
+        scan.matches
+            .extend(candidates.into_iter().map(|other| SimilarityMatch {
+                user_id: record.user_id,
+                changed_at: record.changed_at,
+                other_user_id: other.user_id,
+                other_from: other.changed_at,
+                other_until: other.next_changed_at,
+                value: MatchedValue::Name {
+                    name: record.username.clone(),
+                    other_name: other.username,
+                },
+            }));
+    }
+
+    Ok(())
+}
+
+/// Names normalized per transaction when filling in the normalized names.
+const NORMALIZE_BATCH: i64 = 1000;
+
+/// Stores the normalized form of names recorded without one, such as before it was stored or
+/// by an import.
+async fn fill_normalized_names(database: &SqlitePool) -> Result<(), sqlx::Error> {
+    loop {
+        let names = sqlx::query!(
+            r#"SELECT rowid AS "rowid!: i64", username AS "username!: String" FROM UsernameChange
+            WHERE normalizedName IS NULL AND username IS NOT NULL LIMIT ?"#,
+            NORMALIZE_BATCH
+        )
+        .fetch_all(database)
+        .await?;
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        let mut transaction = database.begin().await?;
+        for name in names {
+            let normalized = normalize_name(&name.username);
+            sqlx::query!(
+                "UPDATE UsernameChange SET normalizedName = ? WHERE rowid = ?",
+                normalized,
+                name.rowid
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+    }
+}
+
+/// Reduces a display name to what it looks like, so look-alike variations compare equal.
+///
+/// Case, punctuation, spaces and common digit substitutions are ignored, e.g.
+/// `J0hn_Doe` and `john.doe` normalize to the same name.
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'l',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            other => other,
+        })
+        .collect()
+}
+
+/// Scans the history recorded since the last run and posts the matches to the alert channels.
+///
+/// The first run only records its position, so existing history does not trigger alerts.
+/// The position only moves after a successful scan, and only up to the latest record scanned,
+/// so a failed scan is repeated and records stored late are not skipped. Failures are logged.
+///
+/// # Arguments
+/// * `http` - Discord HTTP client used to post the alerts
+/// * `database` - SQLite connection pool
+/// * `now` - Current Unix timestamp, the end of the scanned period
+pub async fn run(http: &Http, database: &SqlitePool, now: i64) {
+    let since = match scan_position(database).await {
+        Ok(Some(since)) => since,
+        Ok(None) => {
+            if let Err(e) = set_scan_position(database, now).await {
+                error!(error = ?e, "Failed to record the alert scan position");
+            }
+            return;
+        }
+        Err(e) => {
+            error!(error = ?e, "Failed to load the alert scan position");
+            return;
+        }
+    };
+
+    let Scan {
+        matches,
+        scanned_until,
+    } = match find_matches(database, since, now).await {
+        Ok(scan) => scan,
+        Err(e) => {
+            error!(error = ?e, "Failed to look for similar accounts");
+            return;
+        }
+    };
+
+    let mut sent = 0;
+    for found in &matches {
+        let channels = match alert_channels_for_user(database, found.user_id).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                error!(error = ?e, user_id = found.user_id, "Failed to load alert channels");
+                continue;
+            }
+        };
+
+        for channel_id in channels {
+            match ChannelId::new(channel_id as u64)
+                .send_message(http, alert_message(found))
+                .await
+            {
+                Ok(_) => sent += 1,
+                Err(e) => {
+                    warn!(error = %e, channel_id, "Failed to send similarity alert")
+                }
+            }
+        }
+    }
+
+    if let Some(scanned_until) = scanned_until {
+        if let Err(e) = set_scan_position(database, scanned_until).await {
+            error!(error = ?e, "Failed to record the alert scan position");
+        }
+    }
+
+    info!(
+        matches = matches.len(),
+        sent, "Finished similarity alert scan"
+    );
+}
+
+/// Builds the alert for a match, with buttons opening both accounts' histories.
+pub fn alert_message(found: &SimilarityMatch) -> CreateMessage {
+    let period = match found.other_until {
+        Some(until) => format!("from <t:{}:d> to <t:{}:d>", found.other_from, until),
+        None => format!("since <t:{}:d>", found.other_from),
+    };
+
+    let (description, view, thumbnail) = match &found.value {
+        MatchedValue::Avatar {
+            checksum,
+            link,
+            matched,
+        } => {
+            let how = match matched {
+                PictureMatch::Exact => "the same profile picture".to_string(),
+                PictureMatch::Similar(distance) => format!(
+                    "a profile picture similar ({} bits differ) to the one",
+                    distance
+                ),
+            };
+            (
+                format!(
+                    "<@{}> now uses {} <@{}> used {}.\nChecksum: {}",
+                    found.user_id, how, found.other_user_id, period, checksum
+                ),
+                "pfphistory",
+                link.clone(),
+            )
+        }
+        MatchedValue::Name { name, other_name } => {
+            let how = if name == other_name {
+                format!("the display name **{}**", name)
+            } else {
+                format!(
+                    "the display name **{}**, resembling **{}**",
+                    name, other_name
+                )
+            };
+            (
+                format!(
+                    "<@{}> now uses {}, which <@{}> used {}.",
+                    found.user_id, how, found.other_user_id, period
+                ),
+                "usernamehistory",
+                None,
+            )
+        }
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title("Possible ban evasion")
+        .description(description)
+        .field(
+            "Accounts",
+            format!(
+                "<@{}> `{}`\n<@{}> `{}`",
+                found.user_id, found.user_id, found.other_user_id, found.other_user_id
+            ),
+            false,
+        )
+        .colour(colours::branding::YELLOW);
+    if let Some(thumbnail) = thumbnail {
+        embed = embed.thumbnail(thumbnail);
+    }
+
+    CreateMessage::new()
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(vec![
+            open_view_button(view, found.user_id as u64, "History of the new account"),
+            open_view_button(
+                view,
+                found.other_user_id as u64,
+                "History of the earlier account",
+            ),
+        ])])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("J0hn_Doe"), "johndoe");
+        assert_eq!(normalize_name("john.doe"), "johndoe");
+        assert_ne!(normalize_name("john"), normalize_name("joan"));
+        assert_eq!(normalize_name("__"), "");
+    }
+}
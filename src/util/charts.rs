@@ -0,0 +1,344 @@
+// ABOUTME: Renders the charts attached to /stats and /serverstats as PNG images
+// ABOUTME: Draws changes per month, an interval histogram and a weekday/hour heatmap with plotters' bitmap backend
+use std::error::Error;
+use std::fmt;
+use std::sync::Once;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::FontStyle;
+use tracing::{error, warn};
+
+use crate::util::stats::HistoryStats;
+
+/// Name of the attachment the stats embeds show.
+pub const STATS_CHART_FILENAME: &str = "stats.png";
+
+const WIDTH: u32 = 800;
+const PANEL_HEIGHT: u32 = 280;
+
+/// Embedded so rendering does not depend on fonts installed on the host.
+const FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+const BACKGROUND: RGBColor = RGBColor(43, 45, 49);
+const TEXT: RGBColor = RGBColor(219, 222, 225);
+const GRID: RGBColor = RGBColor(78, 80, 88);
+/// Discord's blurple.
+const ACCENT: RGBColor = RGBColor(88, 101, 242);
+
+/// Upper bounds in seconds and labels of the interval histogram buckets.
+const INTERVAL_BUCKETS: [(i64, &str); 9] = [
+    (3600, "<1h"),
+    (6 * 3600, "1-6h"),
+    (86400, "6-24h"),
+    (3 * 86400, "1-3d"),
+    (7 * 86400, "3-7d"),
+    (14 * 86400, "1-2w"),
+    (30 * 86400, "2-4w"),
+    (90 * 86400, "1-3mo"),
+    (i64::MAX, "3mo+"),
+];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+static REGISTER_FONT: Once = Once::new();
+
+/// Rendering a chart failed.
+#[derive(Debug)]
+pub struct ChartError(String);
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Chart error: {}", self.0)
+    }
+}
+
+impl Error for ChartError {}
+
+impl<E: Error + Send + Sync> From<DrawingAreaErrorKind<E>> for ChartError {
+    fn from(err: DrawingAreaErrorKind<E>) -> Self {
+        ChartError(err.to_string())
+    }
+}
+
+impl From<png::EncodingError> for ChartError {
+    fn from(err: png::EncodingError) -> Self {
+        ChartError(err.to_string())
+    }
+}
+
+/// Renders changes per month, the interval histogram and the weekday/hour heatmap into one PNG.
+///
+/// # Arguments
+/// * `stats` - Statistics of a user's or server's picture history
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The encoded PNG
+/// * `Err(ChartError)` - Drawing or encoding failed
+pub fn render_stats(stats: &HistoryStats) -> Result<Vec<u8>, ChartError> {
+    register_font();
+
+    let height = PANEL_HEIGHT * 3;
+    let mut pixels = vec![0; (WIDTH * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, height)).into_drawing_area();
+        root.fill(&BACKGROUND)?;
+
+        let panels = root.split_evenly((3, 1));
+        draw_changes_per_month(&panels[0], &stats.per_month)?;
+        draw_interval_histogram(&panels[1], &stats.interval_secs)?;
+        draw_heatmap(&panels[2], &stats.by_weekday_hour)?;
+
+        root.present()?;
+    }
+
+    encode_png(&pixels, WIDTH, height)
+}
+
+/// Makes the embedded font available as `sans-serif` to everything drawn with plotters.
+pub fn register_font() {
+    REGISTER_FONT.call_once(|| {
+        if plotters::style::register_font("sans-serif", FontStyle::Normal, FONT).is_err() {
+            error!("Embedded chart font could not be loaded");
+        }
+    });
+}
+
+/// Renders the stats chart on a blocking thread; failures are logged and give `None`.
+pub async fn stats_chart(stats: HistoryStats) -> Option<Vec<u8>> {
+    match tokio::task::spawn_blocking(move || render_stats(&stats)).await {
+        Ok(Ok(png)) => Some(png),
+        Ok(Err(e)) => {
+            warn!(error = %e, "Failed to render stats chart");
+            None
+        }
+        Err(e) => {
+            warn!(error = %e, "Stats chart rendering panicked");
+            None
+        }
+    }
+}
+
+fn draw_changes_per_month(
+    area: &DrawingArea<BitMapBackend<'_>, Shift>,
+    per_month: &[(String, usize)],
+) -> Result<(), ChartError> {
+    let counts: Vec<usize> = per_month.iter().map(|(_, count)| *count).collect();
+    let labels: Vec<&str> = per_month.iter().map(|(month, _)| month.as_str()).collect();
+
+    draw_bars(area, "Changes per month", &counts, &labels)
+}
+
+fn draw_interval_histogram(
+    area: &DrawingArea<BitMapBackend<'_>, Shift>,
+    interval_secs: &[i64],
+) -> Result<(), ChartError> {
+    let mut counts = [0; INTERVAL_BUCKETS.len()];
+    for interval in interval_secs {
+        let bucket = INTERVAL_BUCKETS
+            .iter()
+            .position(|(limit, _)| interval < limit)
+            .unwrap_or(INTERVAL_BUCKETS.len() - 1);
+        counts[bucket] += 1;
+    }
+    let labels: Vec<&str> = INTERVAL_BUCKETS.iter().map(|(_, label)| *label).collect();
+
+    draw_bars(area, "Time between changes", &counts, &labels)
+}
+
+/// Draws a bar per count, labelled below; long series only get every few labels.
+fn draw_bars(
+    area: &DrawingArea<BitMapBackend<'_>, Shift>,
+    caption: &str,
+    counts: &[usize],
+    labels: &[&str],
+) -> Result<(), ChartError> {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, ("sans-serif", 20).into_font().color(&TEXT))
+        .margin(12)
+        .x_label_area_size(28)
+        .y_label_area_size(40)
+        .build_cartesian_2d(
+            // Segmented ranges include their end; a single bar gets a second, empty slot
+            (0..counts.len().saturating_sub(1).max(1)).into_segmented(),
+            0..max + max / 10 + 1,
+        )?;
+
+    let label_step = counts.len().div_ceil(12).max(1);
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .bold_line_style(GRID)
+        .light_line_style(TRANSPARENT)
+        .axis_style(GRID)
+        .label_style(("sans-serif", 13).into_font().color(&TEXT))
+        .x_labels(counts.len().max(1))
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(index) if index % label_step == 0 => {
+                labels.get(*index).copied().unwrap_or_default().to_string()
+            }
+            _ => String::new(),
+        })
+        .y_labels(5)
+        .y_label_formatter(&|count| count.to_string())
+        .draw()?;
+
+    chart.draw_series(counts.iter().enumerate().map(|(index, count)| {
+        let mut bar = Rectangle::new(
+            [
+                (SegmentValue::Exact(index), 0),
+                (SegmentValue::Exact(index + 1), *count),
+            ],
+            ACCENT.filled(),
+        );
+        bar.set_margin(0, 0, 4, 4);
+        bar
+    }))?;
+
+    Ok(())
+}
+
+fn draw_heatmap(
+    area: &DrawingArea<BitMapBackend<'_>, Shift>,
+    by_weekday_hour: &[[usize; 24]; 7],
+) -> Result<(), ChartError> {
+    let max = by_weekday_hour
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(
+            "Changes by weekday and hour (UTC)",
+            ("sans-serif", 20).into_font().color(&TEXT),
+        )
+        .margin(12)
+        .x_label_area_size(28)
+        .y_label_area_size(40)
+        .build_cartesian_2d((0usize..23).into_segmented(), (0usize..6).into_segmented())?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .axis_style(GRID)
+        .label_style(("sans-serif", 13).into_font().color(&TEXT))
+        .x_labels(24)
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(hour) if hour % 2 == 0 => format!("{:02}", hour),
+            _ => String::new(),
+        })
+        .y_labels(7)
+        // Monday is drawn at the top
+        .y_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(row) => WEEKDAYS
+                .get(6usize.wrapping_sub(*row))
+                .copied()
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    chart.draw_series(
+        by_weekday_hour
+            .iter()
+            .enumerate()
+            .flat_map(|(weekday, hours)| {
+                hours.iter().enumerate().map(move |(hour, count)| {
+                    let mut cell = Rectangle::new(
+                        [
+                            (SegmentValue::Exact(hour), SegmentValue::Exact(6 - weekday)),
+                            (
+                                SegmentValue::Exact(hour + 1),
+                                SegmentValue::Exact(7 - weekday),
+                            ),
+                        ],
+                        heat_color(*count, max).filled(),
+                    );
+                    cell.set_margin(1, 1, 1, 1);
+                    cell
+                })
+            }),
+    )?;
+
+    Ok(())
+}
+
+/// Blends from the grid color to the accent color by the share of the maximum.
+fn heat_color(count: usize, max: usize) -> RGBColor {
+    let share = count as f64 / max as f64;
+    let blend = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * share).round() as u8;
+
+    if count == 0 {
+        BACKGROUND
+    } else {
+        RGBColor(
+            blend(GRID.0, ACCENT.0),
+            blend(GRID.1, ACCENT.1),
+            blend(GRID.2, ACCENT.2),
+        )
+    }
+}
+
+/// Encodes an RGB buffer as PNG.
+fn encode_png(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, ChartError> {
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(pixels)?;
+    }
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::history::PictureRecord;
+
+    fn stats(timestamps: &[i64]) -> HistoryStats {
+        let records: Vec<PictureRecord> = timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, changed_at)| PictureRecord {
+                checksum: format!("checksum{}", index),
+                changed_at: *changed_at,
+                link: None,
+            })
+            .collect();
+
+        HistoryStats::compute(&records, 1_800_000_000).unwrap()
+    }
+
+    #[test]
+    fn test_renders_png() {
+        let png = render_stats(&stats(&[
+            1_700_000_000,
+            1_700_050_000,
+            1_703_000_000,
+            1_710_000_000,
+        ]))
+        .unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_renders_single_record() {
+        assert!(render_stats(&stats(&[1_700_000_000])).is_ok());
+    }
+
+    #[test]
+    fn test_heat_color() {
+        assert_eq!(heat_color(0, 5), BACKGROUND);
+        assert_eq!(heat_color(5, 5), ACCENT);
+    }
+}
@@ -0,0 +1,219 @@
+// ABOUTME: Per-entity check status of monitored users and servers
+// ABOUTME: Records successes and failures, backs off entities that keep failing, and lists them for admins
+use sqlx::SqlitePool;
+
+use crate::util::chron_update::UPDATE_INTERVAL_SECS;
+
+/// Longest time a failing entity is skipped between two checks.
+pub const MAX_BACKOFF_SECS: i64 = 24 * 60 * 60;
+
+/// Failures in a row after which an entity is listed as failing.
+pub const FAILING_THRESHOLD: i64 = 3;
+
+/// Table holding the status of one kind of monitored entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusTable {
+    pub table: &'static str,
+    pub id_column: &'static str,
+}
+
+pub const USER_STATUS: StatusTable = StatusTable {
+    table: "User",
+    id_column: "discordId",
+};
+
+pub const SERVER_STATUS: StatusTable = StatusTable {
+    table: "Server",
+    id_column: "serverId",
+};
+
+/// Check status of a monitored user or server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityStatus {
+    pub id: i64,
+    pub consecutive_failures: i64,
+    pub last_success_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub next_check_at: Option<i64>,
+}
+
+/// How long to skip an entity after its n-th failure in a row.
+///
+/// A single failure is retried on the next pass; after that the delay doubles
+/// from one update interval up to [`MAX_BACKOFF_SECS`].
+pub fn backoff_secs(consecutive_failures: i64) -> i64 {
+    if consecutive_failures < 2 {
+        return 0;
+    }
+
+    let doublings = (consecutive_failures - 2).min(16) as u32;
+    (UPDATE_INTERVAL_SECS as i64)
+        .saturating_mul(1_i64 << doublings)
+        .min(MAX_BACKOFF_SECS)
+}
+
+/// Records a successful check and ends any backoff.
+pub async fn record_success(
+    database: &SqlitePool,
+    status: StatusTable,
+    entity_id: i64,
+    now: i64,
+) -> Result<(), sqlx::Error> {
+    let query = format!(
+        "UPDATE {} SET lastCheckedAt = ?, lastSuccessAt = ?, consecutiveFailures = 0, lastError = NULL, nextCheckAt = NULL WHERE {} = ?",
+        status.table, status.id_column
+    );
+
+    sqlx::query(&query)
+        .bind(now)
+        .bind(now)
+        .bind(entity_id)
+        .execute(database)
+        .await?;
+
+    Ok(())
+}
+
+/// Records a failed check and schedules the next one according to [`backoff_secs`].
+///
+/// # Returns
+/// * `Result<i64, sqlx::Error>` - Number of failures in a row, including this one
+pub async fn record_failure(
+    database: &SqlitePool,
+    status: StatusTable,
+    entity_id: i64,
+    now: i64,
+    error: &str,
+) -> Result<i64, sqlx::Error> {
+    let query = format!(
+        "UPDATE {} SET lastCheckedAt = ?, consecutiveFailures = COALESCE(consecutiveFailures, 0) + 1, lastError = ? WHERE {} = ? RETURNING consecutiveFailures",
+        status.table, status.id_column
+    );
+
+    let failures = sqlx::query_scalar::<_, i64>(&query)
+        .bind(now)
+        .bind(error)
+        .bind(entity_id)
+        .fetch_optional(database)
+        .await?
+        .unwrap_or(0);
+
+    let backoff = backoff_secs(failures);
+    let next_check_at = (backoff > 0).then_some(now + backoff);
+
+    let query = format!(
+        "UPDATE {} SET nextCheckAt = ? WHERE {} = ?",
+        status.table, status.id_column
+    );
+    sqlx::query(&query)
+        .bind(next_check_at)
+        .bind(entity_id)
+        .execute(database)
+        .await?;
+
+    Ok(failures)
+}
+
+/// Monitored users added in a server whose checks failed at least [`FAILING_THRESHOLD`] times in a row.
+pub async fn failing_users_in_server(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Vec<EntityStatus>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT User.discordId AS "id!: i64", User.consecutiveFailures AS "consecutive_failures!: i64",
+            User.lastSuccessAt AS "last_success_at: i64", User.lastError AS "last_error: String",
+            User.nextCheckAt AS "next_check_at: i64"
+        FROM User
+        JOIN TrackedUserServer ON TrackedUserServer.userId = User.discordId
+        WHERE TrackedUserServer.serverId = ? AND User.removedAt IS NULL AND User.consecutiveFailures >= ?
+        ORDER BY User.consecutiveFailures DESC"#,
+        server_id,
+        FAILING_THRESHOLD
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| EntityStatus {
+            id: row.id,
+            consecutive_failures: row.consecutive_failures,
+            last_success_at: row.last_success_at,
+            last_error: row.last_error,
+            next_check_at: row.next_check_at,
+        })
+        .collect())
+}
+
+/// Check status of a monitored user, `None` if they are not monitored.
+pub async fn user_status(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<Option<EntityStatus>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT discordId AS "id!: i64", consecutiveFailures AS "consecutive_failures: i64",
+            lastSuccessAt AS "last_success_at: i64", lastError AS "last_error: String",
+            nextCheckAt AS "next_check_at: i64"
+        FROM User WHERE discordId = ? AND removedAt IS NULL"#,
+        user_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    Ok(row.map(|row| EntityStatus {
+        id: row.id,
+        consecutive_failures: row.consecutive_failures.unwrap_or(0),
+        last_success_at: row.last_success_at,
+        last_error: row.last_error,
+        next_check_at: row.next_check_at,
+    }))
+}
+
+/// Check status of a monitored server, `None` if it is not monitored.
+pub async fn server_status(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Option<EntityStatus>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT serverId AS "id!: i64", consecutiveFailures AS "consecutive_failures: i64",
+            lastSuccessAt AS "last_success_at: i64", lastError AS "last_error: String",
+            nextCheckAt AS "next_check_at: i64"
+        FROM Server WHERE serverId = ?"#,
+        server_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    Ok(row.map(|row| EntityStatus {
+        id: row.id,
+        consecutive_failures: row.consecutive_failures.unwrap_or(0),
+        last_success_at: row.last_success_at,
+        last_error: row.last_error,
+        next_check_at: row.next_check_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_failure_is_retried_next_pass() {
+        assert_eq!(backoff_secs(0), 0);
+        assert_eq!(backoff_secs(1), 0);
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let interval = UPDATE_INTERVAL_SECS as i64;
+        assert_eq!(backoff_secs(2), interval);
+        assert_eq!(backoff_secs(3), interval * 2);
+        assert_eq!(backoff_secs(4), interval * 4);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        assert_eq!(backoff_secs(10), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(i64::MAX), MAX_BACKOFF_SECS);
+    }
+}
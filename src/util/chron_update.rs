@@ -1,21 +1,73 @@
 // ABOUTME: Scheduled update functions for monitoring user profile pictures and server icons
-// ABOUTME: Checks for changes, calculates checksums, uploads new images to imgbb, and stores history
+// ABOUTME: Checks for changes, fingerprints images, uploads new images to the image store, and stores history
 use std::future::Future;
 use std::pin::Pin;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
 use chrono::{DateTime, Utc};
-use serenity::all::{GuildId, Http, UserId};
-use sha1::{Digest, Sha1};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::util::alerts;
+use crate::util::check_status::{self, StatusTable, SERVER_STATUS, USER_STATUS};
+use crate::util::discord::DiscordSource;
+use crate::util::fingerprint;
+use crate::util::image_deletion;
+use crate::util::metrics::metrics;
+use crate::util::storage::ImageStore;
+use crate::util::update_runs::{self, RunCounts, RunTrigger};
+
+/// Seconds between two scheduled update passes.
+pub const UPDATE_INTERVAL_SECS: u64 = 30 * 60;
+
+/// Result of checking a single entity during an update pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The image is the same as the latest record.
+    Unchanged,
+    /// The image changed back to one that was archived before.
+    Reverted,
+    /// A new image was archived or a new username recorded.
+    Archived,
+    /// The entity has no image or username.
+    Skipped,
+    /// Fetching, downloading, archiving or storing failed, with the reason and the error.
+    Failed(&'static str, String),
+}
 
-use crate::util::{config::Config, external::imgbb};
+impl CheckOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckOutcome::Unchanged => "unchanged",
+            CheckOutcome::Reverted => "reverted",
+            CheckOutcome::Archived => "archived",
+            CheckOutcome::Skipped => "skipped",
+            CheckOutcome::Failed(..) => "failed",
+        }
+    }
+
+    fn is_change(&self) -> bool {
+        matches!(self, CheckOutcome::Reverted | CheckOutcome::Archived)
+    }
+
+    /// The outcome of a pass over a single entity, `Failed` if the pass could not load it.
+    fn of_single(outcomes: Option<Vec<CheckOutcome>>) -> CheckOutcome {
+        outcomes
+            .and_then(|outcomes| outcomes.into_iter().next())
+            .unwrap_or_else(|| {
+                CheckOutcome::Failed("database", "Failed to load the entity".to_string())
+            })
+    }
+}
 
 /// Generic helper for monitoring entities (users or servers) and tracking image changes
+///
+/// # Returns
+/// * `Option<Vec<CheckOutcome>>` - The outcome per checked entity, `None` if the entities could not be loaded
 #[allow(clippy::too_many_arguments)]
 async fn update_monitored_entity<'a, FetchIds, GetImageUrl, FormatId>(
-    client: &'a Http,
+    discord: &'a dyn DiscordSource,
     database: &'a sqlx::SqlitePool,
-    config: &'a Config,
+    image_store: &'a dyn ImageStore,
     fetch_entity_ids: FetchIds,
     get_image_url: GetImageUrl,
     format_entity_id: FormatId,
@@ -23,243 +75,353 @@ async fn update_monitored_entity<'a, FetchIds, GetImageUrl, FormatId>(
     id_column_name: &'static str,
     filename_prefix: &'static str,
     entity_type_name: &'static str,
-) where
+    status: StatusTable,
+    trigger: RunTrigger,
+) -> Option<Vec<CheckOutcome>>
+where
     FetchIds: Fn(
         &'a sqlx::SqlitePool,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<i64>, sqlx::Error>> + Send + 'a>>,
-    GetImageUrl: Fn(&'a Http, i64) -> Pin<Box<dyn Future<Output = Option<String>> + Send + 'a>>,
+    GetImageUrl: Fn(
+        &'a dyn DiscordSource,
+        i64,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<Option<String>, serenity::Error>> + Send + 'a>,
+    >,
     FormatId: Fn(i64) -> String,
 {
-    let entity_ids = fetch_entity_ids(database).await;
-
-    if let Ok(entries) = entity_ids {
-        println!("Updating {} {}...", entries.len(), entity_type_name);
-
-        for entity_id in entries {
-            let entity_id_str = format_entity_id(entity_id);
-            println!("Updating {} for {}...", entity_type_name, entity_id_str);
-
-            // Get the image URL for this entity
-            let image_url = match get_image_url(client, entity_id).await {
-                Some(url) => url,
-                None => {
-                    println!(
-                        "{} {} has no image, skipping...",
-                        entity_type_name, entity_id_str
-                    );
-                    continue;
-                }
-            };
+    let pass = async {
+        let started = Instant::now();
+
+        let entries = match fetch_entity_ids(database).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!(error = ?e, "Failed to load monitored entities");
+                return None;
+            }
+        };
+
+        // Started only once the pass can run, so no run is left unfinished
+        let run_id = begin_run(database, entity_type_name, trigger).await;
+
+        info!(count = entries.len(), "Starting update pass");
+
+        let mut counts = RunCounts::default();
+        let mut outcomes = Vec::with_capacity(entries.len());
+
+        for entity_id in &entries {
+            let entity_id = *entity_id;
+            let entity_started = Instant::now();
+            let span = info_span!("check_entity", entity_id = %format_entity_id(entity_id));
+
+            let outcome = async {
+                let outcome = match get_image_url(discord, entity_id).await {
+                    Ok(Some(image_url)) => {
+                        check_entity(
+                            discord,
+                            database,
+                            image_store,
+                            entity_id,
+                            &image_url,
+                            table_name,
+                            id_column_name,
+                            filename_prefix,
+                        )
+                        .await
+                    }
+                    Ok(None) => CheckOutcome::Skipped,
+                    Err(e) => {
+                        warn!(reason = "discord", error = ?e, "Unable to retrieve {}", entity_type_name);
+                        CheckOutcome::Failed("discord", e.to_string())
+                    }
+                };
 
-            // Download image
-            let response = match reqwest::get(&image_url).await {
-                Ok(r) => r,
-                Err(e) => {
-                    println!("Failed to download image for {}: {:?}", entity_id_str, e);
-                    continue;
-                }
-            };
+                record_check_status(database, status, entity_id, &outcome).await;
+                outcome
+            }
+            .instrument(span.clone())
+            .await;
+
+            counts.checked += 1;
+            metrics()
+                .entities_checked
+                .with_label_values(&[entity_type_name])
+                .inc();
+            if outcome.is_change() {
+                counts.changes += 1;
+                metrics()
+                    .changes_detected
+                    .with_label_values(&[entity_type_name])
+                    .inc();
+            } else if let CheckOutcome::Failed(reason, _) = &outcome {
+                counts.failures += 1;
+                metrics()
+                    .check_failures
+                    .with_label_values(&[entity_type_name, *reason])
+                    .inc();
+            }
+
+            span.in_scope(|| {
+                debug!(
+                    outcome = outcome.as_str(),
+                    duration_ms = entity_started.elapsed().as_millis() as u64,
+                    "Checked entity"
+                );
+            });
+            outcomes.push(outcome);
+        }
+
+        metrics()
+            .pass_duration
+            .with_label_values(&[entity_type_name])
+            .observe(started.elapsed().as_secs_f64());
+        end_run(database, run_id, counts).await;
+        info!(
+            count = entries.len(),
+            changes = counts.changes,
+            failures = counts.failures,
+            duration_ms = started.elapsed().as_millis() as u64,
+            "Finished update pass"
+        );
+        Some(outcomes)
+    };
+
+    pass.instrument(info_span!("update_pass", entity_type = entity_type_name))
+        .await
+}
+
+/// Downloads the current image of one entity and records it if it changed.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend that archives new images
+/// * `entity_id` - ID of the user or server
+/// * `image_url` - Discord CDN URL of the current image
+/// * `table_name` - History table of the entity type
+/// * `id_column_name` - Column of the history table holding the entity ID
+/// * `filename_prefix` - Prefix of the archived file name
+///
+/// # Returns
+/// * `CheckOutcome` - What happened to the entity, failures are logged
+async fn check_entity(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    entity_id: i64,
+    image_url: &str,
+    table_name: &'static str,
+    id_column_name: &'static str,
+    filename_prefix: &'static str,
+) -> CheckOutcome {
+    // Download image
+    let bytes = match discord.fetch_image(image_url).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!(reason = "download", error = ?e, "Failed to download image");
+            return CheckOutcome::Failed("download", e.to_string());
+        }
+    };
+
+    let checksum = fingerprint::checksum(&bytes);
+
+    // Check if this checksum already exists for this entity
+    let check_query = format!(
+        "SELECT checksum FROM {} WHERE checksum = ? AND {} = ?",
+        table_name, id_column_name
+    );
+
+    let already_existing_record = match sqlx::query_scalar::<_, String>(&check_query)
+        .bind(&checksum)
+        .bind(entity_id)
+        .fetch_optional(database)
+        .await
+    {
+        Ok(record) => record,
+        Err(e) => {
+            error!(reason = "database", error = ?e, "Failed to look up existing checksum");
+            return CheckOutcome::Failed("database", e.to_string());
+        }
+    };
 
-            let bytes = match response.bytes().await {
-                Ok(b) => b,
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    let (link, outcome) = match already_existing_record {
+        Some(_) => {
+            // Check if same image as last change
+            let last_check_query = format!(
+                "SELECT CASE WHEN (SELECT checksum FROM {} WHERE {} = ? ORDER BY changedAt DESC LIMIT 1) = ? THEN 1 ELSE 0 END AS equals",
+                table_name, id_column_name
+            );
+
+            let last_change_equals_now = match sqlx::query_scalar::<_, i32>(&last_check_query)
+                .bind(entity_id)
+                .bind(&checksum)
+                .fetch_one(database)
+                .await
+            {
+                Ok(result) => result,
                 Err(e) => {
-                    println!("Failed to read image bytes for {}: {:?}", entity_id_str, e);
-                    continue;
+                    error!(reason = "database", error = ?e, "Failed to check last change");
+                    return CheckOutcome::Failed("database", e.to_string());
                 }
             };
 
-            // Compute SHA1 checksum
-            let mut hasher = Sha1::new();
-            hasher.update(&bytes);
-            let result = hasher.finalize();
-            let checksum = format!("{:x}", result);
+            if last_change_equals_now == 1 {
+                return CheckOutcome::Unchanged;
+            }
+
+            info!(checksum = %checksum, "Image changed back to a previously archived one");
 
-            // Check if this checksum already exists for this entity
-            let check_query = format!(
-                "SELECT checksum FROM {} WHERE checksum = ? AND {} = ?",
+            // Get the link of the earlier record of this image
+            let link_query = format!(
+                "SELECT link FROM {} WHERE checksum = ? AND {} = ? ORDER BY changedAt DESC LIMIT 1",
                 table_name, id_column_name
             );
 
-            let already_existing_record = match sqlx::query_scalar::<_, String>(&check_query)
+            match sqlx::query_scalar::<_, Option<String>>(&link_query)
                 .bind(&checksum)
                 .bind(entity_id)
-                .fetch_optional(database)
+                .fetch_one(database)
                 .await
             {
-                Ok(record) => record,
+                Ok(link) => (link, CheckOutcome::Reverted),
                 Err(e) => {
-                    eprintln!(
-                        "Database error checking existing {} for {}: {:?}",
-                        entity_type_name, entity_id_str, e
-                    );
-                    continue;
+                    error!(reason = "database", error = ?e, "Failed to fetch existing image link");
+                    return CheckOutcome::Failed("database", e.to_string());
                 }
-            };
+            }
+        }
+        None => {
+            info!(checksum = %checksum, "Archiving new image");
 
-            match already_existing_record {
-                Some(_) => {
-                    // Check if same image as last change
-                    let last_check_query = format!(
-                        "SELECT CASE WHEN (SELECT checksum FROM {} WHERE {} = ? ORDER BY changedAt DESC LIMIT 1) = ? THEN 1 ELSE 0 END AS equals",
-                        table_name, id_column_name
-                    );
-
-                    let last_change_equals_now =
-                        match sqlx::query_scalar::<_, i32>(&last_check_query)
-                            .bind(entity_id)
-                            .bind(&checksum)
-                            .fetch_one(database)
-                            .await
-                        {
-                            Ok(result) => result,
-                            Err(e) => {
-                                eprintln!(
-                                    "Database error checking last change for {}: {:?}",
-                                    entity_id_str, e
-                                );
-                                continue;
-                            }
-                        };
-
-                    if last_change_equals_now == 1 {
-                        continue;
-                    } else {
-                        println!(
-                            "Updating {} for {} with checksum {} (used previously)",
-                            entity_type_name, entity_id, checksum
-                        );
-
-                        // Get existing image link
-                        let link_query = format!(
-                            "SELECT link FROM {} WHERE {} = ? ORDER BY changedAt DESC LIMIT 1",
-                            table_name, id_column_name
-                        );
-
-                        let existing_image =
-                            match sqlx::query_scalar::<_, Option<String>>(&link_query)
-                                .bind(entity_id)
-                                .fetch_one(database)
-                                .await
-                            {
-                                Ok(link) => link,
-                                Err(e) => {
-                                    eprintln!(
-                                        "Database error fetching existing image for {}: {:?}",
-                                        entity_id_str, e
-                                    );
-                                    continue;
-                                }
-                            };
-
-                        let image_url = existing_image;
-
-                        let now = SystemTime::now();
-                        let dt: DateTime<Utc> = now.into();
-                        let timestamp = dt.timestamp();
-
-                        // Insert new record with existing link
-                        let insert_query = format!(
-                            "INSERT INTO {} (checksum, {}, changedAt, link) VALUES (?, ?, ?, ?)",
-                            table_name, id_column_name
-                        );
-
-                        if let Err(e) = sqlx::query(&insert_query)
-                            .bind(&checksum)
-                            .bind(entity_id)
-                            .bind(timestamp)
-                            .bind(image_url)
-                            .execute(database)
-                            .await
-                        {
-                            eprintln!(
-                                "Database error inserting {} record for {}: {:?}",
-                                entity_type_name, entity_id_str, e
-                            );
-                        }
-                    }
-                }
-                None => {
-                    let now = SystemTime::now();
-                    let dt: DateTime<Utc> = now.into();
-                    let timestamp = dt.timestamp();
-
-                    println!(
-                        "Writing new {} for {} at {} with checksum {}",
-                        entity_type_name,
-                        entity_id,
-                        dt.to_rfc2822(),
-                        checksum
-                    );
-
-                    let filename = format!("{}{}_{}.png", filename_prefix, entity_id, timestamp);
-
-                    let image_url = match imgbb::upload_image(
-                        bytes.to_vec(),
-                        filename,
-                        &config.imgbb_key,
-                    )
-                    .await
-                    {
-                        Ok(url) => url,
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to upload {} image for {}: {:?}",
-                                entity_type_name, entity_id_str, e
-                            );
-                            continue;
-                        }
-                    };
-
-                    // Insert new record
-                    let insert_query = format!(
-                        "INSERT INTO {} (checksum, {}, changedAt, link) VALUES (?, ?, ?, ?)",
-                        table_name, id_column_name
-                    );
-
-                    if let Err(e) = sqlx::query(&insert_query)
-                        .bind(&checksum)
-                        .bind(entity_id)
-                        .bind(timestamp)
-                        .bind(image_url)
-                        .execute(database)
-                        .await
-                    {
-                        eprintln!(
-                            "Database error inserting new {} record for {}: {:?}",
-                            entity_type_name, entity_id_str, e
-                        );
-                    }
+            let filename = format!("{}{}_{}.png", filename_prefix, entity_id, timestamp);
+
+            let image = match image_store.upload(bytes.to_vec(), filename).await {
+                Ok(image) => image,
+                Err(e) => {
+                    warn!(reason = "upload", error = ?e, "Failed to archive image");
+                    return CheckOutcome::Failed("upload", e.to_string());
                 }
+            };
+
+            // Keep the delete link so the image can be removed again by /optout or a cleanup
+            if let Err(e) = image_deletion::record_upload(database, &image).await {
+                error!(reason = "database", error = ?e, "Failed to record delete link");
+                return CheckOutcome::Failed("database", e.to_string());
             }
+
+            (Some(image.link), CheckOutcome::Archived)
         }
+    };
+
+    let perceptual_hash = fingerprint::perceptual_hash(&bytes);
+
+    // Insert new record
+    let insert_query = format!(
+        "INSERT INTO {} (checksum, {}, changedAt, link, perceptualHash) VALUES (?, ?, ?, ?, ?)",
+        table_name, id_column_name
+    );
+
+    if let Err(e) = sqlx::query(&insert_query)
+        .bind(&checksum)
+        .bind(entity_id)
+        .bind(timestamp)
+        .bind(link)
+        .bind(perceptual_hash)
+        .execute(database)
+        .await
+    {
+        error!(reason = "database", error = ?e, "Failed to insert history record");
+        return CheckOutcome::Failed("database", e.to_string());
     }
+
+    outcome
 }
 
-pub async fn update_monitored_users(client: &Http, database: &sqlx::SqlitePool) {
-    let config = Config::from_env().expect("Unable to load configuration.");
+/// Checks the profile pictures and usernames of all due users.
+///
+/// # Returns
+/// * `bool` - Whether the passes could load and check their users; single failing users still count as success
+pub async fn update_monitored_users(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    trigger: RunTrigger,
+) -> bool {
+    let (pictures, usernames) = update_users(discord, database, image_store, trigger, None).await;
+    pictures.is_some() && usernames.is_some()
+}
 
+/// Checks the profile picture and username of a single user right away, ignoring any backoff.
+///
+/// # Arguments
+/// * `discord` - Where users, servers and their images are fetched from
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend that archives new pictures
+/// * `user_id` - The monitored user to check
+///
+/// # Returns
+/// * `CheckOutcome` - The profile picture outcome, or the username failure if only that check failed
+pub async fn check_user_now(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    user_id: i64,
+) -> CheckOutcome {
+    let (pictures, usernames) = update_users(
+        discord,
+        database,
+        image_store,
+        RunTrigger::Command,
+        Some(user_id),
+    )
+    .await;
+
+    match (
+        CheckOutcome::of_single(pictures),
+        CheckOutcome::of_single(usernames),
+    ) {
+        (picture, username @ CheckOutcome::Failed(..))
+            if !matches!(picture, CheckOutcome::Failed(..)) =>
+        {
+            username
+        }
+        (picture, _) => picture,
+    }
+}
+
+/// Runs the profile picture and username passes over all due users, or only `only_user_id`.
+///
+/// # Returns
+/// * `(Option<Vec<CheckOutcome>>, Option<Vec<CheckOutcome>>)` - Outcomes of the picture and the username pass
+async fn update_users(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    trigger: RunTrigger,
+    only_user_id: Option<i64>,
+) -> (Option<Vec<CheckOutcome>>, Option<Vec<CheckOutcome>>) {
     // Update profile pictures using the generic helper
-    update_monitored_entity(
-        client,
+    let pictures = update_monitored_entity(
+        discord,
         database,
-        &config,
-        |db| {
+        image_store,
+        move |db| {
             Box::pin(async move {
-                sqlx::query_scalar::<_, i64>("SELECT discordId FROM User")
-                    .fetch_all(db)
-                    .await
+                match only_user_id {
+                    Some(user_id) => Ok(vec![user_id]),
+                    None => due_user_ids(db).await,
+                }
             })
         },
-        |client, user_id| {
+        |discord, user_id| {
             Box::pin(async move {
-                let user_id_obj = UserId::new(user_id.try_into().unwrap());
-                match user_id_obj.to_user(client).await {
-                    Ok(user) => Some(user.face()),
-                    Err(_) => {
-                        println!("Unable to retrieve User {}", user_id);
-                        None
-                    }
-                }
+                discord
+                    .fetch_user(user_id as u64)
+                    .await
+                    .map(|user| Some(user.avatar_url))
             })
         },
         |id| format!("{}", id),
@@ -267,110 +429,316 @@ pub async fn update_monitored_users(client: &Http, database: &sqlx::SqlitePool)
         "userId",
         "pfp_",
         "profile picture",
+        USER_STATUS,
+        trigger,
     )
     .await;
 
     // Update usernames (this logic is unique to users, so keep it here)
-    let users_to_check = sqlx::query!("SELECT discordId FROM User")
-        .fetch_all(database)
+    let usernames = update_usernames(discord, database, trigger, only_user_id)
+        .instrument(info_span!("update_pass", entity_type = "username"))
         .await;
 
-    if let Ok(entries) = users_to_check {
-        for entry in entries {
-            let user_id = UserId::new(entry.discordId.try_into().unwrap());
-
-            let user = match user_id.to_user(client).await {
-                Ok(u) => u,
-                Err(_) => {
-                    println!(
-                        "Unable to retrieve User {} for username update",
-                        entry.discordId
-                    );
-                    continue;
-                }
-            };
+    (pictures, usernames)
+}
 
-            let tag = &user.name;
-            println!("Updating Usernames for {user_id} ({tag})...");
+/// Monitored users that are not backed off.
+async fn due_user_ids(database: &sqlx::SqlitePool) -> Result<Vec<i64>, sqlx::Error> {
+    let now = Utc::now().timestamp();
 
-            let username = &user.global_name;
+    sqlx::query_scalar!(
+        r#"SELECT discordId AS "discord_id!: i64" FROM User WHERE removedAt IS NULL AND (nextCheckAt IS NULL OR nextCheckAt <= ?)"#,
+        now
+    )
+    .fetch_all(database)
+    .await
+}
 
-            match username {
-                Some(username) => {
-                    let already_existing_record = sqlx::query!(
-                        "SELECT username FROM UsernameChange WHERE username = ? AND userId = ?",
-                        username,
-                        entry.discordId
-                    )
-                    .fetch_optional(database)
-                    .await
-                    .unwrap();
-
-                    match already_existing_record {
-                        Some(_) => {
-                            // Still same username
-                            continue;
-                        }
-                        None => {
-                            let now = SystemTime::now();
-                            let dt: DateTime<Utc> = now.into();
-                            let timestamp = dt.timestamp();
-
-                            sqlx::query!(
-                                "INSERT INTO UsernameChange (changedAt, username, userId) VALUES (?, ?, ?)",
-                                timestamp,
-                                username,
-                                entry.discordId
-                            )
-                            .execute(database)
-                            .await
-                            .unwrap();
-
-                            println!("Updated username for {} to {}", entry.discordId, username);
-                        }
-                    }
-                }
-                None => {
-                    continue;
-                }
-            }
+/// Records a new username for every due user whose display name changed, or only for `only_user_id`.
+///
+/// # Returns
+/// * `Option<Vec<CheckOutcome>>` - The outcome per checked user, `None` if the users could not be loaded
+async fn update_usernames(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    trigger: RunTrigger,
+    only_user_id: Option<i64>,
+) -> Option<Vec<CheckOutcome>> {
+    let started = Instant::now();
+
+    let entries = match only_user_id {
+        Some(user_id) => Ok(vec![user_id]),
+        None => due_user_ids(database).await,
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!(error = ?e, "Failed to load monitored users");
+            return None;
         }
+    };
+
+    let run_id = begin_run(database, "username", trigger).await;
+
+    info!(count = entries.len(), "Starting update pass");
+
+    let mut counts = RunCounts::default();
+    let mut outcomes = Vec::with_capacity(entries.len());
+
+    for discord_id in &entries {
+        let outcome = check_username(discord, database, *discord_id)
+            .instrument(info_span!("check_entity", entity_id = discord_id))
+            .await;
+
+        counts.checked += 1;
+        metrics()
+            .entities_checked
+            .with_label_values(&["username"])
+            .inc();
+        if outcome.is_change() {
+            counts.changes += 1;
+            metrics()
+                .changes_detected
+                .with_label_values(&["username"])
+                .inc();
+        } else if let CheckOutcome::Failed(reason, _) = &outcome {
+            counts.failures += 1;
+            metrics()
+                .check_failures
+                .with_label_values(&["username", *reason])
+                .inc();
+        }
+        outcomes.push(outcome);
+    }
+
+    metrics()
+        .pass_duration
+        .with_label_values(&["username"])
+        .observe(started.elapsed().as_secs_f64());
+    end_run(database, run_id, counts).await;
+    info!(
+        count = entries.len(),
+        changes = counts.changes,
+        failures = counts.failures,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "Finished update pass"
+    );
+    Some(outcomes)
+}
+
+/// Records the current display name of one user if it was not seen before.
+///
+/// # Returns
+/// * `CheckOutcome` - Whether a new username was recorded, failures are logged
+async fn check_username(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    discord_id: i64,
+) -> CheckOutcome {
+    let user = match discord.fetch_user(discord_id as u64).await {
+        Ok(u) => u,
+        Err(e) => {
+            warn!(reason = "discord", error = ?e, "Unable to retrieve user for username update");
+            return CheckOutcome::Failed("discord", e.to_string());
+        }
+    };
+
+    let username = match &user.global_name {
+        Some(username) => username,
+        None => return CheckOutcome::Skipped,
+    };
+
+    let already_existing_record = match sqlx::query!(
+        "SELECT username FROM UsernameChange WHERE username = ? AND userId = ?",
+        username,
+        discord_id
+    )
+    .fetch_optional(database)
+    .await
+    {
+        Ok(record) => record,
+        Err(e) => {
+            error!(reason = "database", error = ?e, "Failed to look up username");
+            return CheckOutcome::Failed("database", e.to_string());
+        }
+    };
+
+    if already_existing_record.is_some() {
+        // Still same username
+        return CheckOutcome::Unchanged;
+    }
+
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    let normalized_name = alerts::normalize_name(username);
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO UsernameChange (changedAt, username, userId, normalizedName) VALUES (?, ?, ?, ?)",
+        timestamp,
+        username,
+        discord_id,
+        normalized_name
+    )
+    .execute(database)
+    .await
+    {
+        error!(reason = "database", error = ?e, "Failed to insert username record");
+        return CheckOutcome::Failed("database", e.to_string());
+    }
+
+    info!(username = %username, "Recorded new username");
+    CheckOutcome::Archived
+}
+
+/// Records the outcome of a check in the entity's check status, logging instead of failing the pass.
+///
+/// Entities that cannot be fetched from Discord or whose image cannot be downloaded count
+/// as failing and are backed off, as do users without an image. Servers without an icon
+/// are fine, since setting one is optional. Database and upload failures are problems of
+/// the bot rather than the entity and leave the status untouched.
+async fn record_check_status(
+    database: &sqlx::SqlitePool,
+    status: StatusTable,
+    entity_id: i64,
+    outcome: &CheckOutcome,
+) {
+    let now = Utc::now().timestamp();
+
+    let result = match outcome {
+        CheckOutcome::Failed("discord" | "download", error) => {
+            check_status::record_failure(database, status, entity_id, now, error).await
+        }
+        CheckOutcome::Skipped if status == USER_STATUS => {
+            check_status::record_failure(database, status, entity_id, now, "no image").await
+        }
+        CheckOutcome::Failed(..) => return,
+        _ => check_status::record_success(database, status, entity_id, now)
+            .await
+            .map(|_| 0),
+    };
+
+    match result {
+        Ok(failures) if failures >= check_status::FAILING_THRESHOLD => {
+            warn!(failures, "Entity keeps failing, backing off");
+        }
+        Ok(_) => {}
+        Err(e) => error!(error = ?e, "Failed to record check status"),
+    }
+}
+
+/// Records the start of an update pass, logging instead of failing the pass.
+async fn begin_run(
+    database: &sqlx::SqlitePool,
+    entity_type: &str,
+    trigger: RunTrigger,
+) -> Option<i64> {
+    match update_runs::start_run(database, entity_type, trigger, Utc::now().timestamp()).await {
+        Ok(run_id) => Some(run_id),
+        Err(e) => {
+            error!(error = ?e, "Failed to record start of update pass");
+            None
+        }
+    }
+}
+
+/// Records the end of an update pass started with [`begin_run`].
+async fn end_run(database: &sqlx::SqlitePool, run_id: Option<i64>, counts: RunCounts) {
+    let Some(run_id) = run_id else {
+        return;
+    };
+
+    if let Err(e) = update_runs::finish_run(database, run_id, Utc::now().timestamp(), counts).await
+    {
+        error!(error = ?e, "Failed to record end of update pass");
     }
 }
 
 /// Updates server icon records for all monitored servers.
 ///
 /// Fetches icon data for each server in the Server table, computes checksums,
-/// and stores new icons in the database. Uses imgbb for uploading new icons.
+/// and stores new icons in the database. Uses the image store for archiving new icons.
 ///
 /// # Arguments
-/// * `client` - The Discord HTTP client
+/// * `discord` - Where users, servers and their images are fetched from
 /// * `database` - SQLite connection pool
-pub async fn update_monitored_servers(client: &Http, database: &sqlx::SqlitePool) {
-    let config = Config::from_env().expect("Unable to load configuration.");
+/// * `image_store` - Backend that archives new icons
+/// * `trigger` - What started the pass, recorded in the UpdateRun table
+///
+/// # Returns
+/// * `bool` - Whether the pass could load and check its servers; single failing servers still count as success
+pub async fn update_monitored_servers(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    trigger: RunTrigger,
+) -> bool {
+    update_servers(discord, database, image_store, trigger, None)
+        .await
+        .is_some()
+}
+
+/// Checks the icon of a single server right away, ignoring any backoff.
+///
+/// # Arguments
+/// * `discord` - Where users, servers and their images are fetched from
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend that archives new icons
+/// * `server_id` - The monitored server to check
+///
+/// # Returns
+/// * `CheckOutcome` - What happened to the server icon
+pub async fn check_server_now(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    server_id: i64,
+) -> CheckOutcome {
+    CheckOutcome::of_single(
+        update_servers(
+            discord,
+            database,
+            image_store,
+            RunTrigger::Command,
+            Some(server_id),
+        )
+        .await,
+    )
+}
 
+/// Runs the server icon pass over all due servers, or only `only_server_id`.
+async fn update_servers(
+    discord: &dyn DiscordSource,
+    database: &sqlx::SqlitePool,
+    image_store: &dyn ImageStore,
+    trigger: RunTrigger,
+    only_server_id: Option<i64>,
+) -> Option<Vec<CheckOutcome>> {
     // Update server icons using the generic helper
     update_monitored_entity(
-        client,
+        discord,
         database,
-        &config,
-        |db| {
+        image_store,
+        move |db| {
             Box::pin(async move {
-                sqlx::query_scalar::<_, i64>("SELECT serverId FROM Server")
-                    .fetch_all(db)
-                    .await
+                if let Some(server_id) = only_server_id {
+                    return Ok(vec![server_id]);
+                }
+
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT serverId FROM Server WHERE nextCheckAt IS NULL OR nextCheckAt <= ?",
+                )
+                .bind(Utc::now().timestamp())
+                .fetch_all(db)
+                .await
             })
         },
-        |client, server_id| {
+        |discord, server_id| {
             Box::pin(async move {
-                let guild_id = GuildId::new(server_id.try_into().unwrap());
-                match guild_id.to_partial_guild(client).await {
-                    Ok(guild) => guild.icon_url(),
-                    Err(_) => {
-                        println!("Unable to retrieve Server {}", server_id);
-                        None
-                    }
-                }
+                discord
+                    .fetch_guild(server_id as u64)
+                    .await
+                    .map(|guild| guild.icon_url)
             })
         },
         |id| format!("{}", id),
@@ -378,6 +746,8 @@ pub async fn update_monitored_servers(client: &Http, database: &sqlx::SqlitePool
         "serverId",
         "server_icon_",
         "server icon",
+        SERVER_STATUS,
+        trigger,
     )
-    .await;
+    .await
 }
@@ -1,10 +1,26 @@
 use dotenvy::dotenv;
 use std::env;
+use std::net::SocketAddr;
+
+use crate::util::discord::DISCORD_CDN_URL;
+use crate::util::external::imgbb;
+use crate::util::logging::LogFormat;
+use crate::util::retention::RetentionPolicy;
+
+/// Port of the metrics and health endpoints when `HTTP_ADDR` is not set.
+const DEFAULT_HTTP_PORT: u16 = 9100;
 
 pub struct Config {
     pub discord_token: String,
     pub database_url: String,
     pub imgbb_key: String,
+    pub imgbb_upload_url: String,
+    pub discord_cdn_url: String,
+    pub retention: RetentionPolicy,
+    pub removal_grace_days: i64,
+    pub log_format: LogFormat,
+    pub http_addr: SocketAddr,
+    pub ready_max_missed_passes: i64,
 }
 
 impl Config {
@@ -13,8 +29,65 @@ impl Config {
 
         Ok(Config {
             discord_token: env::var("DISCORD_TOKEN")?,
-            database_url: env::var("DATABASE_URL")?,
+            database_url: database_url()?,
             imgbb_key: env::var("IMGBB_KEY")?,
+            imgbb_upload_url: env::var("IMGBB_UPLOAD_URL")
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .unwrap_or_else(|| imgbb::DEFAULT_UPLOAD_URL.to_string()),
+            discord_cdn_url: env::var("DISCORD_CDN_URL")
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| DISCORD_CDN_URL.to_string()),
+            retention: RetentionPolicy {
+                max_age_days: optional_number("RETENTION_MAX_AGE_DAYS"),
+                max_entries: optional_number("RETENTION_MAX_ENTRIES"),
+            },
+            removal_grace_days: optional_days("REMOVAL_GRACE_DAYS").unwrap_or(7),
+            log_format: env::var("LOG_FORMAT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
+            http_addr: http_addr(),
+            ready_max_missed_passes: optional_number("READY_MAX_MISSED_PASSES").unwrap_or(3),
         })
     }
 }
+
+/// Path of the SQLite database, from `DATABASE_URL`.
+///
+/// Only needs the environment, so the maintenance subcommands can use it without Discord or ImgBB credentials.
+pub fn database_url() -> Result<String, env::VarError> {
+    dotenv().ok();
+
+    env::var("DATABASE_URL")
+}
+
+/// Address of the metrics and health endpoints, from `HTTP_ADDR`.
+///
+/// Only needs the environment, so the healthcheck mode can use it without a full configuration.
+pub fn http_addr() -> SocketAddr {
+    dotenv().ok();
+
+    env::var("HTTP_ADDR")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], DEFAULT_HTTP_PORT)))
+}
+
+/// Reads an optional positive number, treating unset or invalid values as disabled.
+fn optional_number(key: &str) -> Option<i64> {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .filter(|value| *value > 0)
+}
+
+/// Reads an optional number of days, where 0 is a valid value; unset or invalid values are `None`.
+fn optional_days(key: &str) -> Option<i64> {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .filter(|value| *value >= 0)
+}
@@ -0,0 +1,361 @@
+// ABOUTME: Reusable Confirm/Cancel button flow for destructive commands
+// ABOUTME: Encodes the pending action in button custom_ids, expires stale prompts, and validates clicks
+use std::fmt;
+use std::num::ParseIntError;
+
+use chrono::Utc;
+use serenity::all::{
+    ActionRowComponent, ButtonKind, ButtonStyle, CommandInteraction, ComponentInteraction, Context,
+    CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse, Message, MessageInteractionMetadata,
+};
+use tokio::time::{sleep, Duration};
+use tracing::warn;
+
+/// Prefix shared by the custom_ids of all confirmation buttons.
+pub const CUSTOM_ID_PREFIX: &str = "confirm_";
+
+/// How long a confirmation prompt accepts clicks.
+pub const CONFIRMATION_TIMEOUT_SECS: i64 = 60;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfirmationButton {
+    pub confirmed: bool,
+    pub action: String,
+    pub target_id: u64,
+    pub expires_at: i64,
+}
+
+impl ConfirmationButton {
+    /// Whether the prompt this button belongs to has timed out.
+    pub fn is_expired(&self, now: i64) -> bool {
+        now > self.expires_at
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfirmationParseError {
+    InvalidFormat(String),
+    InvalidTargetId(ParseIntError),
+    InvalidExpiry(ParseIntError),
+    UnknownChoice(String),
+}
+
+impl fmt::Display for ConfirmationParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfirmationParseError::InvalidFormat(msg) => write!(f, "Invalid format: {}", msg),
+            ConfirmationParseError::InvalidTargetId(err) => write!(f, "Invalid target ID: {}", err),
+            ConfirmationParseError::InvalidExpiry(err) => write!(f, "Invalid expiry: {}", err),
+            ConfirmationParseError::UnknownChoice(choice) => {
+                write!(f, "Unknown choice: {}", choice)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfirmationParseError {}
+
+/// Parses a confirmation button custom_id.
+///
+/// Format: `confirm_{yes|no}_{action}_{target_id}_{expires_at}`
+///
+/// # Arguments
+/// * `custom_id` - The button's custom_id string
+///
+/// # Returns
+/// * `Ok(ConfirmationButton)` - Successfully parsed button data
+/// * `Err(ConfirmationParseError)` - Parsing failed
+pub fn parse_confirmation_button(
+    custom_id: &str,
+) -> Result<ConfirmationButton, ConfirmationParseError> {
+    let parts: Vec<&str> = custom_id.split('_').collect();
+
+    if parts.len() != 5 || format!("{}_", parts[0]) != CUSTOM_ID_PREFIX {
+        return Err(ConfirmationParseError::InvalidFormat(format!(
+            "Expected 5 parts starting with {}, got {}",
+            CUSTOM_ID_PREFIX, custom_id
+        )));
+    }
+
+    let confirmed = match parts[1] {
+        "yes" => true,
+        "no" => false,
+        other => return Err(ConfirmationParseError::UnknownChoice(other.to_string())),
+    };
+
+    let target_id = parts[3]
+        .parse::<u64>()
+        .map_err(ConfirmationParseError::InvalidTargetId)?;
+    let expires_at = parts[4]
+        .parse::<i64>()
+        .map_err(ConfirmationParseError::InvalidExpiry)?;
+
+    Ok(ConfirmationButton {
+        confirmed,
+        action: parts[2].to_string(),
+        target_id,
+        expires_at,
+    })
+}
+
+/// Builds the Confirm/Cancel button row for a pending action.
+///
+/// # Arguments
+/// * `action` - Name of the action to run on confirmation, must not contain `_`
+/// * `target_id` - ID of the user or server the action applies to
+/// * `expires_at` - Unix timestamp after which clicks are rejected
+pub fn confirmation_buttons(action: &str, target_id: u64, expires_at: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!(
+            "{}yes_{}_{}_{}",
+            CUSTOM_ID_PREFIX, action, target_id, expires_at
+        ))
+        .label("Confirm")
+        .style(ButtonStyle::Danger),
+        CreateButton::new(format!(
+            "{}no_{}_{}_{}",
+            CUSTOM_ID_PREFIX, action, target_id, expires_at
+        ))
+        .label("Cancel")
+        .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// Replies to a command with a confirmation prompt for a destructive action.
+///
+/// The prompt's buttons are removed once [`CONFIRMATION_TIMEOUT_SECS`] have passed
+/// without an answer. Clicks are routed back through the handler's component branch
+/// and validated with [`resolve`].
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `interaction` - The command interaction to respond to
+/// * `embed` - Description of what will be deleted
+/// * `action` - Name of the action to run on confirmation
+/// * `target_id` - ID of the user or server the action applies to
+/// * `ephemeral` - Whether only the invoking member sees the prompt
+///
+/// # Returns
+/// * `Result<(), serenity::Error>` - Ok if successful, error otherwise
+pub async fn request_confirmation(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    embed: CreateEmbed,
+    action: &str,
+    target_id: u64,
+    ephemeral: bool,
+) -> Result<(), serenity::Error> {
+    let expires_at = Utc::now().timestamp() + CONFIRMATION_TIMEOUT_SECS;
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .components(vec![confirmation_buttons(action, target_id, expires_at)])
+                    .ephemeral(ephemeral),
+            ),
+        )
+        .await?;
+
+    // Expire the buttons if nobody answered in time
+    let http = ctx.http.clone();
+    let interaction = interaction.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_secs(CONFIRMATION_TIMEOUT_SECS as u64)).await;
+
+        let still_pending = match interaction.get_response(&http).await {
+            Ok(message) => has_confirmation_buttons(&message),
+            Err(_) => false,
+        };
+
+        if still_pending {
+            if let Err(why) = interaction
+                .edit_response(
+                    &http,
+                    EditInteractionResponse::new()
+                        .content("This confirmation has expired. Nothing was deleted.")
+                        .embeds(vec![])
+                        .components(vec![]),
+                )
+                .await
+            {
+                warn!(error = ?why, "Cannot expire confirmation");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Whether a message still shows unanswered Confirm/Cancel buttons.
+fn has_confirmation_buttons(message: &Message) -> bool {
+    message
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .any(|component| match component {
+            ActionRowComponent::Button(button) => match &button.data {
+                ButtonKind::NonLink { custom_id, .. } => custom_id.starts_with(CUSTOM_ID_PREFIX),
+                _ => false,
+            },
+            _ => false,
+        })
+}
+
+/// Whether the member who clicked a component is the one who ran the command
+/// that created its message.
+pub fn clicked_by_invoker(component: &ComponentInteraction) -> bool {
+    match component.message.interaction_metadata.as_deref() {
+        Some(MessageInteractionMetadata::Command(metadata)) => {
+            metadata.user.id == component.user.id
+        }
+        _ => false,
+    }
+}
+
+/// Handles the generic part of a confirmation click.
+///
+/// Rejects clicks from other members and on expired prompts, and closes the prompt
+/// on Cancel. The caller runs the action only if this returns `Ok(true)` and must
+/// then respond to the component itself.
+///
+/// # Arguments
+/// * `ctx` - The Serenity context
+/// * `component` - The button interaction
+/// * `button` - The parsed confirmation button
+///
+/// # Returns
+/// * `Result<bool, serenity::Error>` - Whether the action was confirmed and should run
+pub async fn resolve(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    button: &ConfirmationButton,
+) -> Result<bool, serenity::Error> {
+    if !clicked_by_invoker(component) {
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Only the member who ran the command can answer this.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(false);
+    }
+
+    let content = if button.is_expired(Utc::now().timestamp()) {
+        "This confirmation has expired. Nothing was deleted."
+    } else if !button.confirmed {
+        "Cancelled. Nothing was deleted."
+    } else {
+        return Ok(true);
+    };
+
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .embeds(vec![])
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_confirm_button() {
+        let button = parse_confirmation_button("confirm_yes_removemonitor_123456_1700000000");
+        assert_eq!(
+            button,
+            Ok(ConfirmationButton {
+                confirmed: true,
+                action: "removemonitor".to_string(),
+                target_id: 123456,
+                expires_at: 1700000000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_cancel_button() {
+        let button = parse_confirmation_button("confirm_no_optout_42_1700000000").unwrap();
+        assert!(!button.confirmed);
+        assert_eq!(button.action, "optout");
+        assert_eq!(button.target_id, 42);
+    }
+
+    #[test]
+    fn test_parse_invalid_choice() {
+        let result = parse_confirmation_button("confirm_maybe_optout_42_1700000000");
+        assert!(matches!(
+            result,
+            Err(ConfirmationParseError::UnknownChoice(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_wrong_prefix() {
+        let result = parse_confirmation_button("pfphistory_next_2_555666777");
+        assert!(matches!(
+            result,
+            Err(ConfirmationParseError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_invalid_target_id() {
+        let result = parse_confirmation_button("confirm_yes_optout_abc_1700000000");
+        assert!(matches!(
+            result,
+            Err(ConfirmationParseError::InvalidTargetId(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_invalid_expiry() {
+        let result = parse_confirmation_button("confirm_yes_optout_42_soon");
+        assert!(matches!(
+            result,
+            Err(ConfirmationParseError::InvalidExpiry(_))
+        ));
+    }
+
+    #[test]
+    fn test_buttons_round_trip() {
+        let row = confirmation_buttons("removemonitorserver", 987654321, 1700000000);
+        let json = serde_json::to_value(&row).unwrap();
+        let custom_ids: Vec<&str> = json["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|button| button["custom_id"].as_str().unwrap())
+            .collect();
+
+        let confirm = parse_confirmation_button(custom_ids[0]).unwrap();
+        let cancel = parse_confirmation_button(custom_ids[1]).unwrap();
+        assert!(confirm.confirmed);
+        assert!(!cancel.confirmed);
+        assert_eq!(confirm.action, "removemonitorserver");
+        assert_eq!(cancel.target_id, 987654321);
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let button = parse_confirmation_button("confirm_yes_optout_42_1000").unwrap();
+        assert!(!button.is_expired(1000));
+        assert!(button.is_expired(1001));
+    }
+}
@@ -0,0 +1,138 @@
+// ABOUTME: Abstraction over the Discord API calls made by the update passes
+// ABOUTME: Defines the DiscordSource trait and its implementation on top of serenity's Http
+use std::time::Duration;
+
+use serenity::all::{GuildId, Http, UserId};
+use serenity::async_trait;
+
+/// Base URL of Discord's CDN, which avatar and icon URLs start with.
+pub const DISCORD_CDN_URL: &str = "https://cdn.discordapp.com";
+
+/// How long downloading an avatar or icon may take before it counts as failed.
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The parts of a Discord user the update passes record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscordUser {
+    pub id: u64,
+    /// CDN URL of the avatar, or of the default avatar if the user has none.
+    pub avatar_url: String,
+    pub global_name: Option<String>,
+}
+
+/// The parts of a Discord server the update passes record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscordGuild {
+    pub id: u64,
+    pub name: String,
+    /// CDN URL of the icon, `None` if the server has no icon.
+    pub icon_url: Option<String>,
+}
+
+/// Where the update passes get users, servers and their images from.
+#[async_trait]
+pub trait DiscordSource: Send + Sync {
+    /// Fetches a user by ID.
+    async fn fetch_user(&self, user_id: u64) -> Result<DiscordUser, serenity::Error>;
+
+    /// Fetches a server by ID; the bot has to be a member of it.
+    async fn fetch_guild(&self, guild_id: u64) -> Result<DiscordGuild, serenity::Error>;
+
+    /// Downloads an avatar or icon from the CDN.
+    async fn fetch_image(&self, url: &str) -> Result<Vec<u8>, serenity::Error>;
+}
+
+/// Discord's REST API, with images downloaded through a [`CdnClient`].
+pub struct HttpDiscord<'a> {
+    http: &'a Http,
+    cdn: &'a CdnClient,
+}
+
+impl<'a> HttpDiscord<'a> {
+    pub fn new(http: &'a Http, cdn: &'a CdnClient) -> Self {
+        HttpDiscord { http, cdn }
+    }
+}
+
+#[async_trait]
+impl DiscordSource for HttpDiscord<'_> {
+    async fn fetch_user(&self, user_id: u64) -> Result<DiscordUser, serenity::Error> {
+        let user = UserId::new(user_id).to_user(self.http).await?;
+
+        Ok(DiscordUser {
+            id: user.id.get(),
+            avatar_url: user.face(),
+            global_name: user.global_name,
+        })
+    }
+
+    async fn fetch_guild(&self, guild_id: u64) -> Result<DiscordGuild, serenity::Error> {
+        let guild = GuildId::new(guild_id).to_partial_guild(self.http).await?;
+
+        Ok(DiscordGuild {
+            id: guild.id.get(),
+            icon_url: guild.icon_url(),
+            name: guild.name,
+        })
+    }
+
+    async fn fetch_image(&self, url: &str) -> Result<Vec<u8>, serenity::Error> {
+        self.cdn.download(url).await
+    }
+}
+
+/// Downloads avatars and icons, from Discord's CDN or a stand-in for it.
+pub struct CdnClient {
+    base_url: String,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl CdnClient {
+    pub fn new(base_url: impl Into<String>, timeout: Duration) -> Self {
+        CdnClient {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            timeout,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Points a Discord CDN URL at the configured base URL; other URLs are left alone.
+    pub fn resolve(&self, url: &str) -> String {
+        match url.strip_prefix(DISCORD_CDN_URL) {
+            Some(path) => format!("{}{}", self.base_url, path),
+            None => url.to_string(),
+        }
+    }
+
+    /// Downloads an image, failing on error statuses and timeouts.
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, serenity::Error> {
+        let response = self
+            .client
+            .get(self.resolve(url))
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cdn_urls_are_rewritten() {
+        let cdn = CdnClient::new("http://127.0.0.1:8080/", DOWNLOAD_TIMEOUT);
+
+        assert_eq!(
+            cdn.resolve("https://cdn.discordapp.com/avatars/1/abc.png?size=1024"),
+            "http://127.0.0.1:8080/avatars/1/abc.png?size=1024"
+        );
+        assert_eq!(
+            cdn.resolve("https://example.com/avatar.png"),
+            "https://example.com/avatar.png"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+// ABOUTME: Downloads images from user-supplied URLs without reaching into private networks
+// ABOUTME: Resolves and vets the host first, pins the vetted addresses, refuses redirects and caps the body size
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::{redirect, Client, StatusCode, Url};
+
+#[derive(Debug)]
+pub enum DownloadError {
+    /// Not an https URL with a host
+    InvalidUrl,
+    /// The host resolves to a loopback, private or otherwise non-public address
+    NonPublicAddress(IpAddr),
+    Resolve(std::io::Error),
+    /// Answered with anything but a success status, including redirects
+    Status(StatusCode),
+    /// The body is larger than allowed
+    TooLarge,
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::InvalidUrl => write!(f, "Not a public https URL"),
+            DownloadError::NonPublicAddress(ip) => write!(f, "Host resolves to {}", ip),
+            DownloadError::Resolve(err) => write!(f, "Resolve error: {}", err),
+            DownloadError::Status(status) => write!(f, "Unexpected status: {}", status),
+            DownloadError::TooLarge => write!(f, "The image is too large"),
+            DownloadError::Request(err) => write!(f, "Request error: {}", err),
+        }
+    }
+}
+
+impl Error for DownloadError {}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        DownloadError::Request(err)
+    }
+}
+
+/// Downloads an image from a URL a user handed in.
+///
+/// The host is resolved up front and every address has to be public; the request is then
+/// pinned to those addresses so a second lookup cannot point it elsewhere. Redirects are
+/// not followed, and the download stops as soon as the body exceeds `max_bytes`.
+///
+/// # Arguments
+/// * `url` - https URL of the image
+/// * `max_bytes` - Largest accepted body
+/// * `timeout` - How long the whole request may take
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The body
+/// * `Err(DownloadError)` - The URL or its host is refused, or the download failed or was too large
+pub async fn download(
+    url: &str,
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, DownloadError> {
+    let url = Url::parse(url).map_err(|_| DownloadError::InvalidUrl)?;
+    if url.scheme() != "https" {
+        return Err(DownloadError::InvalidUrl);
+    }
+    let host = url
+        .host_str()
+        .ok_or(DownloadError::InvalidUrl)?
+        .trim_matches(['[', ']'])
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or(DownloadError::InvalidUrl)?;
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(DownloadError::Resolve)?
+        .collect();
+    if addresses.is_empty() {
+        return Err(DownloadError::InvalidUrl);
+    }
+    if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+        return Err(DownloadError::NonPublicAddress(address.ip()));
+    }
+
+    fetch(url, &host, &addresses, max_bytes, timeout).await
+}
+
+/// Requests the URL from the given addresses only, enforcing the size limit while streaming.
+async fn fetch(
+    url: Url,
+    host: &str,
+    addresses: &[SocketAddr],
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, DownloadError> {
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .resolve_to_addrs(host, addresses)
+        .timeout(timeout)
+        .build()?;
+
+    let mut response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(DownloadError::Status(response.status()));
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Err(DownloadError::TooLarge);
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(DownloadError::TooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+/// Whether an address is reachable on the public internet.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(a == 0 // "this network"
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || (a == 100 && (64..128).contains(&b)) // carrier-grade NAT, 100.64.0.0/10
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments, 192.0.0.0/24
+        || (a == 198 && (b == 18 || b == 19)) // benchmarking, 198.18.0.0/15
+        || a >= 240) // reserved, 240.0.0.0/4
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    if let Some(mapped) = ip.to_ipv4_mapped() {
+        return is_public_v4(mapped);
+    }
+
+    let segments = ip.segments();
+    // NAT64 (64:ff9b::/96) and 6to4 (2002::/16) reach the embedded IPv4 address
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    if segments[0] == 0x2002 {
+        let [a, b] = segments[1].to_be_bytes();
+        let [c, d] = segments[2].to_be_bytes();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || segments[..6] == [0; 6] // IPv4-compatible, ::/96
+        || (segments[0] & 0xfe00) == 0xfc00 // unique local, fc00::/7
+        || (segments[0] & 0xffc0) == 0xfe80 // link-local, fe80::/10
+        || (segments[0] & 0xffc0) == 0xfec0 // site-local, fec0::/10
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)) // documentation, 2001:db8::/32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{header, StatusCode as HttpStatus};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+
+    #[test]
+    fn test_public_addresses() {
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{} is public", ip);
+        }
+
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "192.0.0.170",
+            "198.18.0.1",
+            "224.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::127.0.0.1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+            "2002:c0a8:0101::1",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} is not public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_refuses_urls() {
+        for url in [
+            "not a url",
+            "http://i.ibb.co/abc/pfp.png",
+            "https://127.0.0.1/pfp.png",
+            "https://[::ffff:10.0.0.1]/pfp.png",
+            "https://localhost/metrics",
+        ] {
+            assert!(
+                download(url, 1024, Duration::from_secs(5)).await.is_err(),
+                "{} is refused",
+                url
+            );
+        }
+    }
+
+    /// Serves a small image, a large one streamed without Content-Length and a redirect
+    async fn stand_in() -> SocketAddr {
+        let app = Router::new()
+            .route("/small.png", get(|| async { vec![1_u8; 16] }))
+            .route(
+                "/large.png",
+                get(|| async {
+                    let chunks = (0..8).map(|_| Ok::<_, std::io::Error>(vec![0_u8; 512]));
+                    Body::from_stream(futures::stream::iter(chunks))
+                }),
+            )
+            .route(
+                "/redirect.png",
+                get(|| async {
+                    (HttpStatus::FOUND, [(header::LOCATION, "/small.png")]).into_response()
+                }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        address
+    }
+
+    async fn fetch_from(address: SocketAddr, path: &str) -> Result<Vec<u8>, DownloadError> {
+        let url = Url::parse(&format!("http://images.test:{}{}", address.port(), path)).unwrap();
+        fetch(url, "images.test", &[address], 1024, Duration::from_secs(5)).await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_limits() {
+        let address = stand_in().await;
+
+        assert_eq!(
+            fetch_from(address, "/small.png").await.unwrap(),
+            vec![1; 16]
+        );
+        assert!(matches!(
+            fetch_from(address, "/large.png").await,
+            Err(DownloadError::TooLarge)
+        ));
+        assert!(matches!(
+            fetch_from(address, "/redirect.png").await,
+            Err(DownloadError::Status(StatusCode::FOUND))
+        ));
+    }
+}
@@ -2,12 +2,17 @@ use reqwest::{multipart, Client};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::util::metrics::metrics;
 
 // Custom error type for ImgBB operations
 #[derive(Debug)]
 pub enum ImgBBError {
     RequestError(reqwest::Error),
     ParseError(String),
+    Rejected(String),
 }
 
 impl fmt::Display for ImgBBError {
@@ -15,6 +20,7 @@ impl fmt::Display for ImgBBError {
         match self {
             ImgBBError::RequestError(err) => write!(f, "Request error: {}", err),
             ImgBBError::ParseError(err) => write!(f, "Parse error: {}", err),
+            ImgBBError::Rejected(err) => write!(f, "Rejected: {}", err),
         }
     }
 }
@@ -38,15 +44,35 @@ pub struct ImgBBData {
     pub id: String,
     pub url: String,
     pub display_url: String,
+    /// Page that deletes the image, `https://ibb.co/<id>/<delete hash>`
+    #[serde(default)]
+    pub delete_url: Option<String>,
 }
 
+/// The ImgBB upload endpoint used unless `IMGBB_UPLOAD_URL` points elsewhere.
+pub const DEFAULT_UPLOAD_URL: &str = "https://api.imgbb.com/1/upload";
+
+/// Uploads an image to ImgBB.
+///
+/// # Arguments
+/// * `image_data` - Bytes of the image
+/// * `filename` - Name of the uploaded file
+/// * `api_key` - ImgBB API key
+/// * `upload_url` - Upload endpoint, normally `DEFAULT_UPLOAD_URL`
+/// * `timeout` - How long to wait for the whole request
+///
+/// # Returns
+/// * `Ok(ImgBBData)` - Link to the uploaded image and the link that deletes it
+/// * `Err(ImgBBError)` - The request failed, timed out, was answered with an error status or could not be parsed
 pub async fn upload_image(
     image_data: Vec<u8>,
     filename: String,
     api_key: &str,
-) -> Result<String, ImgBBError> {
+    upload_url: &str,
+    timeout: Duration,
+) -> Result<ImgBBData, ImgBBError> {
     let client = Client::new();
-    let link = format!("https://api.imgbb.com/1/upload?key={}", api_key);
+    let link = format!("{}?key={}", upload_url, api_key);
 
     let part = multipart::Part::bytes(image_data)
         .file_name(filename)
@@ -55,7 +81,15 @@ pub async fn upload_image(
 
     let form_multipart = multipart::Form::new().part("image", part);
 
-    let response = client.post(&link).multipart(form_multipart).send().await?; // This will convert reqwest::Error to ImgBBError
+    let timer = metrics().imgbb_upload_duration.start_timer();
+    let response = client
+        .post(&link)
+        .multipart(form_multipart)
+        .timeout(timeout)
+        .send()
+        .await;
+    timer.observe_duration();
+    let response = response?; // This will convert reqwest::Error to ImgBBError
 
     if response.status().is_success() {
         let body = response.text().await?;
@@ -64,9 +98,127 @@ pub async fn upload_image(
         let imgbb_response: ImgBBResponse =
             serde_json::from_str(&body).map_err(|e| ImgBBError::ParseError(e.to_string()))?;
 
-        println!("{}", imgbb_response.data.url);
-        Ok(imgbb_response.data.url)
+        debug!(url = %imgbb_response.data.url, "Uploaded image to ImgBB");
+        Ok(imgbb_response.data)
     } else {
         Err(ImgBBError::from(response.error_for_status().err().unwrap()))
     }
 }
+
+#[derive(Deserialize, Debug)]
+struct DeleteResponse {
+    status_code: u16,
+}
+
+/// Deletes an image through the delete link ImgBB handed out on upload.
+///
+/// The API has no delete endpoint, so this does what the delete page does: it reads the
+/// page's `auth_token` and posts the deletion to the site's `/json` endpoint.
+///
+/// # Arguments
+/// * `delete_url` - The `delete_url` of the upload response
+/// * `timeout` - How long to wait for each request
+///
+/// # Returns
+/// * `Ok(())` - The image was deleted
+/// * `Err(ImgBBError)` - A request failed, the page had no token or ImgBB refused the deletion
+pub async fn delete_image(delete_url: &str, timeout: Duration) -> Result<(), ImgBBError> {
+    let url = reqwest::Url::parse(delete_url).map_err(|e| ImgBBError::ParseError(e.to_string()))?;
+    let (id, hash) = match url
+        .path_segments()
+        .map(|segments| segments.collect::<Vec<_>>())
+        .as_deref()
+    {
+        Some([id, hash]) if !id.is_empty() && !hash.is_empty() => {
+            (id.to_string(), hash.to_string())
+        }
+        _ => {
+            return Err(ImgBBError::ParseError(format!(
+                "Not a delete link: {}",
+                delete_url
+            )))
+        }
+    };
+
+    let client = Client::new();
+    let page = client
+        .get(url.clone())
+        .timeout(timeout)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let auth_token = auth_token(&page)
+        .ok_or_else(|| ImgBBError::ParseError("Delete page has no auth_token".to_string()))?;
+
+    let endpoint = url
+        .join("/json")
+        .map_err(|e| ImgBBError::ParseError(e.to_string()))?;
+    let pathname = url.path().to_string();
+    let body = client
+        .post(endpoint)
+        .form(&[
+            ("auth_token", auth_token.as_str()),
+            ("pathname", pathname.as_str()),
+            ("action", "delete"),
+            ("single", "true"),
+            ("delete", "image"),
+            ("from", "resource"),
+            ("deleting[id]", id.as_str()),
+            ("deleting[hash]", hash.as_str()),
+        ])
+        .timeout(timeout)
+        .send()
+        .await?
+        .text()
+        .await?;
+
+    let response: DeleteResponse =
+        serde_json::from_str(&body).map_err(|e| ImgBBError::ParseError(e.to_string()))?;
+    if response.status_code != 200 {
+        return Err(ImgBBError::Rejected(format!(
+            "Deletion answered with status {}",
+            response.status_code
+        )));
+    }
+
+    debug!(id = %id, "Deleted image from ImgBB");
+    Ok(())
+}
+
+/// Extracts the value of `auth_token` from a page's inline configuration.
+fn auth_token(page: &str) -> Option<String> {
+    let after = &page[page.find("auth_token")? + "auth_token".len()..];
+    let start = after.find(['"', '\''])?;
+    let quote = after[start..].chars().next()?;
+    // Only `=`, `:` and spaces may separate the key from its value
+    if !after[..start]
+        .chars()
+        .all(|c| c == '=' || c == ':' || c.is_whitespace())
+    {
+        return None;
+    }
+    let value = &after[start + 1..];
+    let end = value.find(quote)?;
+    Some(value[..end].to_string()).filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_token() {
+        assert_eq!(
+            auth_token(r#"<script>PF.obj.config.auth_token="0a1b2c";</script>"#).as_deref(),
+            Some("0a1b2c")
+        );
+        assert_eq!(
+            auth_token("config = { auth_token : 'xyz' }").as_deref(),
+            Some("xyz")
+        );
+        assert_eq!(auth_token("<p>No token here</p>"), None);
+        assert_eq!(auth_token(r#"auth_token(); var x = "y""#), None);
+    }
+}
@@ -1 +1,2 @@
+pub mod image_url;
 pub mod imgbb;
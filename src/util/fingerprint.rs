@@ -0,0 +1,113 @@
+// ABOUTME: Fingerprints of archived images for exact and near-duplicate matching
+// ABOUTME: Computes the SHA-1 checksum and a 64-bit difference hash and compares perceptual hashes
+use image::imageops::FilterType;
+use sha1::{Digest, Sha1};
+
+/// Largest number of differing bits for two perceptual hashes to count as the same picture.
+///
+/// Re-encoding, resizing or small edits usually stay well below this, unrelated pictures
+/// differ in about half of the 64 bits.
+pub const NEAR_MATCH_DISTANCE: u32 = 6;
+
+/// SHA-1 checksum of the raw image bytes as lowercase hex, identifying exactly identical files.
+pub fn checksum(bytes: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Difference hash of an image as 16 hex digits.
+///
+/// The image is shrunk to 9x8 grayscale pixels and every bit records whether a pixel is
+/// darker than its right neighbour, so the hash survives re-encoding and rescaling.
+///
+/// # Returns
+/// * `Some(String)` - The hash
+/// * `None` - The bytes are not a supported image format
+pub fn perceptual_hash(bytes: &[u8]) -> Option<String> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let pixels = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if pixels.get_pixel(x, y)[0] < pixels.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Some(format!("{:016x}", hash))
+}
+
+/// Number of bits in which two perceptual hashes differ, `None` if either is malformed.
+pub fn hash_distance(a: &str, b: &str) -> Option<u32> {
+    let a = u64::from_str_radix(a, 16).ok()?;
+    let b = u64::from_str_radix(b, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
+
+/// Whether a string looks like a SHA-1 checksum as stored in the history tables.
+pub fn is_checksum(value: &str) -> bool {
+    value.len() == 40 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    fn encode(image: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    fn gradient(width: u32, height: u32, flipped: bool) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            let value = if flipped { 255 - value } else { value };
+            Rgb([value, value / 2, 255 - value])
+        })
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert!(is_checksum(&checksum(b"abc")));
+        assert!(!is_checksum("not a checksum"));
+    }
+
+    #[test]
+    fn test_reencoded_image_is_near_match() {
+        let png = encode(&gradient(128, 128, false), ImageFormat::Png);
+        let smaller_jpeg = encode(&gradient(64, 64, false), ImageFormat::Jpeg);
+
+        assert_ne!(checksum(&png), checksum(&smaller_jpeg));
+        let distance = hash_distance(
+            &perceptual_hash(&png).unwrap(),
+            &perceptual_hash(&smaller_jpeg).unwrap(),
+        )
+        .unwrap();
+        assert!(distance <= NEAR_MATCH_DISTANCE, "distance {}", distance);
+    }
+
+    #[test]
+    fn test_different_image_is_not_near_match() {
+        let original = perceptual_hash(&encode(&gradient(64, 64, false), ImageFormat::Png));
+        let flipped = perceptual_hash(&encode(&gradient(64, 64, true), ImageFormat::Png));
+
+        let distance = hash_distance(&original.unwrap(), &flipped.unwrap()).unwrap();
+        assert!(distance > NEAR_MATCH_DISTANCE, "distance {}", distance);
+    }
+
+    #[test]
+    fn test_undecodable_image_has_no_hash() {
+        assert_eq!(perceptual_hash(b"not an image"), None);
+        assert_eq!(hash_distance("zz", "00"), None);
+    }
+}
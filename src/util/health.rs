@@ -0,0 +1,166 @@
+// ABOUTME: Liveness and readiness state of the bot for container orchestration
+// ABOUTME: Tracks the gateway connection and the last finished update pass, and probes a running bot over HTTP
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+
+/// Shared health state, updated by the event handler and the update scheduler.
+pub struct Health {
+    gateway_connected: AtomicBool,
+    /// Unix timestamp of the first `ready` event, 0 before it.
+    ready_since: AtomicI64,
+    /// Unix timestamp of the last finished update pass, 0 before the first one.
+    last_update_pass: AtomicI64,
+    /// Seconds an update pass may lie back before the bot counts as stuck.
+    max_pass_age: i64,
+}
+
+impl Health {
+    /// Creates the health state.
+    ///
+    /// # Arguments
+    /// * `update_interval_secs` - Seconds between two update passes
+    /// * `max_missed_passes` - Update intervals without a finished pass after which the bot is not ready
+    pub fn new(update_interval_secs: i64, max_missed_passes: i64) -> Self {
+        Health {
+            gateway_connected: AtomicBool::new(false),
+            ready_since: AtomicI64::new(0),
+            last_update_pass: AtomicI64::new(0),
+            max_pass_age: update_interval_secs * max_missed_passes,
+        }
+    }
+
+    /// Records whether the gateway connection is currently up.
+    pub fn set_gateway_connected(&self, connected: bool, now: i64) {
+        self.gateway_connected.store(connected, Ordering::SeqCst);
+        if connected {
+            let _ = self
+                .ready_since
+                .compare_exchange(0, now, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    /// Records that an update pass finished successfully.
+    pub fn record_update_pass(&self, now: i64) {
+        self.last_update_pass.store(now, Ordering::SeqCst);
+    }
+
+    /// Whether the bot is serving Discord and its scheduler is making progress.
+    ///
+    /// Before the first update pass has finished, the time of the first `ready` event
+    /// counts as the last pass so a starting bot is not reported as stuck.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The bot is ready
+    /// * `Err(String)` - Why the bot is not ready
+    pub fn readiness(&self, now: i64) -> Result<(), String> {
+        if !self.gateway_connected.load(Ordering::SeqCst) {
+            return Err("gateway not connected".to_string());
+        }
+
+        let last_pass = match self.last_update_pass.load(Ordering::SeqCst) {
+            0 => self.ready_since.load(Ordering::SeqCst),
+            timestamp => timestamp,
+        };
+
+        if now - last_pass > self.max_pass_age {
+            return Err(format!("no update pass finished for {}s", now - last_pass));
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the database answers queries.
+pub async fn check_database(database: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1").execute(database).await.map(|_| ())
+}
+
+/// Asks a running bot whether it is ready, for Docker's HEALTHCHECK.
+///
+/// # Arguments
+/// * `addr` - Address the bot's HTTP server listens on; unspecified addresses are probed on loopback
+///
+/// # Returns
+/// * `Ok(())` - `/readyz` answered with a success status
+/// * `Err(String)` - The bot is not ready or not reachable
+pub async fn probe(addr: SocketAddr) -> Result<(), String> {
+    let url = format!("http://{}/readyz", probe_addr(addr));
+
+    let response = reqwest::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| format!("{} is not reachable: {}", url, e))?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(format!("{} answered {}: {}", url, status, body))
+    }
+}
+
+/// Replaces a wildcard listen address with loopback so it can be connected to.
+fn probe_addr(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_ready_before_gateway() {
+        let health = Health::new(1800, 3);
+        assert!(health.readiness(1000).is_err());
+    }
+
+    #[test]
+    fn test_ready_while_first_pass_runs() {
+        let health = Health::new(1800, 3);
+        health.set_gateway_connected(true, 1000);
+        assert!(health.readiness(1000 + 1800 * 3).is_ok());
+        assert!(health.readiness(1001 + 1800 * 3).is_err());
+    }
+
+    #[test]
+    fn test_stale_update_pass() {
+        let health = Health::new(1800, 3);
+        health.set_gateway_connected(true, 1000);
+        health.record_update_pass(2000);
+        assert!(health.readiness(2000 + 5400).is_ok());
+        assert!(health.readiness(2001 + 5400).is_err());
+    }
+
+    #[test]
+    fn test_reconnect_keeps_ready_since() {
+        let health = Health::new(1800, 3);
+        health.set_gateway_connected(true, 1000);
+        health.set_gateway_connected(false, 2000);
+        assert_eq!(
+            health.readiness(2000),
+            Err("gateway not connected".to_string())
+        );
+        health.set_gateway_connected(true, 9000);
+        assert!(health.readiness(9000).is_err());
+    }
+
+    #[test]
+    fn test_probe_addr_uses_loopback() {
+        let addr: SocketAddr = "0.0.0.0:9100".parse().unwrap();
+        assert_eq!(probe_addr(addr), "127.0.0.1:9100".parse().unwrap());
+
+        let addr: SocketAddr = "10.0.0.5:8080".parse().unwrap();
+        assert_eq!(probe_addr(addr), addr);
+    }
+}
@@ -0,0 +1,231 @@
+// ABOUTME: Queries for the recorded history of monitored users and servers
+// ABOUTME: Loads archived profile pictures, server icons and usernames, and finds pictures shared across users
+use sqlx::SqlitePool;
+
+use crate::util::fingerprint::{self, NEAR_MATCH_DISTANCE};
+
+/// An archived profile picture or server icon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureRecord {
+    pub checksum: String,
+    /// Unix timestamp of when the image was first seen.
+    pub changed_at: i64,
+    /// Link on the image store, `None` if archiving failed.
+    pub link: Option<String>,
+}
+
+/// A recorded display name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsernameRecord {
+    pub username: String,
+    /// Unix timestamp of when the name was first seen.
+    pub changed_at: i64,
+}
+
+/// How closely a found picture matches the one looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PictureMatch {
+    /// The very same file.
+    Exact,
+    /// A visually similar image whose perceptual hash differs in this many bits.
+    Similar(u32),
+}
+
+/// A period in which a user had a particular profile picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureUse {
+    pub user_id: i64,
+    pub checksum: String,
+    pub link: Option<String>,
+    /// Unix timestamp of when the user switched to the picture.
+    pub from: i64,
+    /// Unix timestamp of the user's next change, `None` if the picture is still in use.
+    pub until: Option<i64>,
+    pub matched: PictureMatch,
+}
+
+/// Archived profile pictures of a user, oldest first.
+pub async fn profile_pictures(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<PictureRecord>, sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT checksum, changedAt, link FROM ProfilePicture WHERE userId = ? ORDER BY changedAt",
+        user_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            Some(PictureRecord {
+                checksum: entry.checksum?,
+                changed_at: entry.changedAt?,
+                link: entry.link,
+            })
+        })
+        .collect())
+}
+
+/// Archived icons of a server, newest first.
+pub async fn server_icons(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Vec<PictureRecord>, sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT checksum, changedAt, link FROM ServerPicture WHERE serverId = ? ORDER BY changedAt DESC",
+        server_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    // changedAt and checksum are in PRIMARY KEY, so they're NOT NULL
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            Some(PictureRecord {
+                checksum: entry.checksum?,
+                changed_at: entry.changedAt?,
+                link: entry.link,
+            })
+        })
+        .collect())
+}
+
+/// Recorded usernames of a user, oldest first.
+pub async fn usernames(
+    database: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<UsernameRecord>, sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT username, changedAt FROM UsernameChange WHERE userId = ? ORDER BY changedAt",
+        user_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            Some(UsernameRecord {
+                username: entry.username?,
+                changed_at: entry.changedAt?,
+            })
+        })
+        .collect())
+}
+
+/// Most records with a similar perceptual hash compared per lookup.
+///
+/// Bounds the work for pictures that share bytes of their hash with very many records, such as
+/// Discord's default avatars.
+const MAX_SIMILAR_CANDIDATES: i64 = 500;
+
+/// A stored profile picture with the time the user changed it again.
+struct PictureUseRow {
+    user_id: i64,
+    checksum: String,
+    changed_at: i64,
+    link: Option<String>,
+    perceptual_hash: Option<String>,
+    next_changed_at: Option<i64>,
+}
+
+/// Finds every period in which any user had the given profile picture.
+///
+/// Records with the same checksum match exactly. With a perceptual hash, records whose own hash
+/// is within [`NEAR_MATCH_DISTANCE`] bits match as similar; records archived before perceptual
+/// hashes were stored can only match exactly. Similar records are looked up through the bytes
+/// their hash shares with the searched one, at most [`MAX_SIMILAR_CANDIDATES`] of them.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `checksum` - SHA-1 checksum of the picture
+/// * `perceptual_hash` - Perceptual hash of the picture, if it could be computed
+///
+/// # Returns
+/// * `Result<Vec<PictureUse>, sqlx::Error>` - Exact matches first, then by similarity and time
+pub async fn picture_uses(
+    database: &SqlitePool,
+    checksum: &str,
+    perceptual_hash: Option<&str>,
+) -> Result<Vec<PictureUse>, sqlx::Error> {
+    let mut rows = sqlx::query_as!(
+        PictureUseRow,
+        r#"SELECT userId AS "user_id!: i64", checksum AS "checksum!: String", changedAt AS "changed_at!: i64",
+            link, perceptualHash AS perceptual_hash,
+            (SELECT MIN(next.changedAt) FROM ProfilePicture AS next
+                WHERE next.userId = ProfilePicture.userId AND next.changedAt > ProfilePicture.changedAt
+            ) AS "next_changed_at: i64"
+        FROM ProfilePicture WHERE checksum = ?"#,
+        checksum
+    )
+    .fetch_all(database)
+    .await?;
+
+    // Hashes within NEAR_MATCH_DISTANCE bits share at least one byte, see the indexes on them
+    let bytes: Option<Vec<&str>> = perceptual_hash
+        .filter(|hash| hash.len() == 16 && hash.is_ascii())
+        .map(|hash| (0..16).step_by(2).map(|i| &hash[i..i + 2]).collect());
+    if let Some(bytes) = bytes {
+        rows.extend(
+            sqlx::query_as!(
+                PictureUseRow,
+                r#"SELECT userId AS "user_id!: i64", checksum AS "checksum!: String", changedAt AS "changed_at!: i64",
+                    link, perceptualHash AS perceptual_hash,
+                    (SELECT MIN(next.changedAt) FROM ProfilePicture AS next
+                        WHERE next.userId = ProfilePicture.userId AND next.changedAt > ProfilePicture.changedAt
+                    ) AS "next_changed_at: i64"
+                FROM ProfilePicture
+                WHERE checksum != ?1 AND (substr(perceptualHash, 1, 2) = ?2 OR substr(perceptualHash, 3, 2) = ?3
+                    OR substr(perceptualHash, 5, 2) = ?4 OR substr(perceptualHash, 7, 2) = ?5
+                    OR substr(perceptualHash, 9, 2) = ?6 OR substr(perceptualHash, 11, 2) = ?7
+                    OR substr(perceptualHash, 13, 2) = ?8 OR substr(perceptualHash, 15, 2) = ?9)
+                LIMIT ?10"#,
+                checksum,
+                bytes[0],
+                bytes[1],
+                bytes[2],
+                bytes[3],
+                bytes[4],
+                bytes[5],
+                bytes[6],
+                bytes[7],
+                MAX_SIMILAR_CANDIDATES
+            )
+            .fetch_all(database)
+            .await?,
+        );
+    }
+
+    let mut uses: Vec<PictureUse> = rows
+        .into_iter()
+        .filter_map(|entry| {
+            let matched = if entry.checksum == checksum {
+                PictureMatch::Exact
+            } else {
+                let distance = fingerprint::hash_distance(
+                    perceptual_hash?,
+                    entry.perceptual_hash.as_deref()?,
+                )?;
+                if distance > NEAR_MATCH_DISTANCE {
+                    return None;
+                }
+                PictureMatch::Similar(distance)
+            };
+
+            Some(PictureUse {
+                user_id: entry.user_id,
+                checksum: entry.checksum,
+                link: entry.link,
+                from: entry.changed_at,
+                until: entry.next_changed_at,
+                matched,
+            })
+        })
+        .collect();
+
+    uses.sort_by_key(|picture_use| (picture_use.matched, picture_use.from));
+    Ok(uses)
+}
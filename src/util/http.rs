@@ -0,0 +1,83 @@
+// ABOUTME: Small HTTP server for operational endpoints next to the Discord gateway connection
+// ABOUTME: Serves Prometheus metrics on /metrics, liveness on /healthz and readiness on /readyz
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::util::health::{check_database, Health};
+use crate::util::metrics::metrics;
+
+/// Content type of the Prometheus text exposition format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Clone)]
+struct HttpState {
+    database: Arc<SqlitePool>,
+    health: Arc<Health>,
+}
+
+/// Serves the operational endpoints until the process exits.
+///
+/// # Arguments
+/// * `addr` - Address to listen on
+/// * `database` - SQLite connection pool, for pool metrics and the liveness check
+/// * `health` - Health state updated by the event handler and scheduler
+///
+/// # Returns
+/// * `std::io::Result<()>` - Error if the address cannot be bound
+pub async fn serve(
+    addr: SocketAddr,
+    database: Arc<SqlitePool>,
+    health: Arc<Health>,
+) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(render_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(HttpState { database, health });
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "Serving HTTP endpoints");
+    axum::serve(listener, app).await
+}
+
+async fn render_metrics(State(state): State<HttpState>) -> impl IntoResponse {
+    let metrics = metrics();
+    metrics.observe_pool(&state.database);
+
+    (
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        metrics.render(),
+    )
+}
+
+/// Liveness: the process answers and the database is reachable.
+async fn healthz(State(state): State<HttpState>) -> (StatusCode, String) {
+    match check_database(&state.database).await {
+        Ok(()) => (StatusCode::OK, "ok".to_string()),
+        Err(e) => {
+            warn!(error = ?e, "Health check failed");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("database unreachable: {}", e),
+            )
+        }
+    }
+}
+
+/// Readiness: the gateway is connected and update passes keep finishing.
+async fn readyz(State(state): State<HttpState>) -> (StatusCode, String) {
+    match state.health.readiness(Utc::now().timestamp()) {
+        Ok(()) => (StatusCode::OK, "ready".to_string()),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason),
+    }
+}
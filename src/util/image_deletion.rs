@@ -0,0 +1,207 @@
+// ABOUTME: Tracks archived images that have to be deleted from the image store
+// ABOUTME: Records delete links on upload, queues images whose history rows are gone and retries failed deletions
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tracing::warn;
+
+use crate::util::storage::{ImageStore, StoredImage};
+
+/// Outcome of deleting queued images from the image store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeletionReport {
+    pub deleted: u64,
+    /// Images that stay queued for the next attempt
+    pub failed: u64,
+}
+
+/// Remembers the delete link of a freshly archived image, if the store handed one out.
+pub async fn record_upload(database: &SqlitePool, image: &StoredImage) -> Result<(), sqlx::Error> {
+    let Some(delete_url) = &image.delete_url else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "INSERT OR REPLACE INTO ArchivedImage (link, deleteUrl) VALUES (?, ?)",
+        image.link,
+        delete_url
+    )
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+/// Images handed to [`queue`], split by whether the bot can delete them itself.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueuedImages {
+    /// Links waiting in the deletion queue
+    pub queued: Vec<String>,
+    /// Links archived before delete links were recorded; only the operator can remove these
+    pub without_delete_link: Vec<String>,
+}
+
+/// Queues images for deletion.
+///
+/// Runs inside the transaction that removes the last history rows referencing the images,
+/// so their links are never lost even if the image store cannot be reached afterwards.
+/// Images without a recorded delete link are not queued, as no retry could ever delete
+/// them; they are logged for the operator to remove by hand instead.
+///
+/// # Arguments
+/// * `transaction` - The transaction removing the history rows
+/// * `links` - Links of the images no row references anymore
+/// * `now` - Current Unix timestamp
+///
+/// # Returns
+/// * `Result<QueuedImages, sqlx::Error>` - Which links were queued and which were left to the operator
+pub async fn queue(
+    transaction: &mut Transaction<'_, Sqlite>,
+    links: &[String],
+    now: i64,
+) -> Result<QueuedImages, sqlx::Error> {
+    let mut images = QueuedImages::default();
+
+    for link in links {
+        let delete_url =
+            sqlx::query_scalar!("SELECT deleteUrl FROM ArchivedImage WHERE link = ?", link)
+                .fetch_optional(&mut **transaction)
+                .await?;
+
+        match delete_url {
+            Some(delete_url) => {
+                sqlx::query!(
+                    "INSERT OR IGNORE INTO PendingImageDeletion (link, deleteUrl, queuedAt) VALUES (?, ?, ?)",
+                    link,
+                    delete_url,
+                    now
+                )
+                .execute(&mut **transaction)
+                .await?;
+
+                sqlx::query!("DELETE FROM ArchivedImage WHERE link = ?", link)
+                    .execute(&mut **transaction)
+                    .await?;
+
+                images.queued.push(link.clone());
+            }
+            None => {
+                warn!(link = %link, "Archived image has no delete link and must be removed from the image host by the operator");
+                images.without_delete_link.push(link.clone());
+            }
+        }
+    }
+
+    Ok(images)
+}
+
+/// Deletes the given queued images from the image store.
+///
+/// Deleted images leave the queue, failed ones stay queued for [`retry_pending`].
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `links` - Links previously passed to [`queue`]
+pub async fn delete_queued(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    links: &[String],
+) -> Result<DeletionReport, sqlx::Error> {
+    let mut report = DeletionReport::default();
+
+    for link in links {
+        let Some(delete_url) = sqlx::query_scalar!(
+            "SELECT deleteUrl FROM PendingImageDeletion WHERE link = ?",
+            link
+        )
+        .fetch_optional(database)
+        .await?
+        else {
+            continue;
+        };
+
+        let image = StoredImage {
+            link: link.clone(),
+            delete_url,
+        };
+        if attempt(database, image_store, &image).await? {
+            report.deleted += 1;
+        } else {
+            report.failed += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Retries every queued deletion, such as those that failed during /optout or a cleanup.
+///
+/// Entries without a delete link, queued before such images were left to the operator,
+/// are logged and dropped since no attempt can ever succeed.
+pub async fn retry_pending(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+) -> Result<DeletionReport, sqlx::Error> {
+    let unremovable = sqlx::query_scalar!(
+        r#"DELETE FROM PendingImageDeletion WHERE deleteUrl IS NULL RETURNING link AS "link!: String""#
+    )
+    .fetch_all(database)
+    .await?;
+    for link in unremovable {
+        warn!(link = %link, "Archived image has no delete link and must be removed from the image host by the operator");
+    }
+
+    let pending = sqlx::query!(
+        r#"SELECT link AS "link!: String", deleteUrl AS delete_url FROM PendingImageDeletion ORDER BY queuedAt"#
+    )
+    .fetch_all(database)
+    .await?;
+
+    let mut report = DeletionReport::default();
+    for entry in pending {
+        let image = StoredImage {
+            link: entry.link,
+            delete_url: entry.delete_url,
+        };
+        if attempt(database, image_store, &image).await? {
+            report.deleted += 1;
+        } else {
+            report.failed += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Deletes one queued image, dropping it from the queue or recording the failure.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - Whether the image was deleted
+async fn attempt(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    image: &StoredImage,
+) -> Result<bool, sqlx::Error> {
+    match image_store.delete(image).await {
+        Ok(()) => {
+            sqlx::query!(
+                "DELETE FROM PendingImageDeletion WHERE link = ?",
+                image.link
+            )
+            .execute(database)
+            .await?;
+            Ok(true)
+        }
+        Err(e) => {
+            warn!(link = %image.link, error = %e, "Failed to delete archived image");
+            let error = e.to_string();
+            sqlx::query!(
+                "UPDATE PendingImageDeletion SET attempts = attempts + 1, lastError = ? WHERE link = ?",
+                error,
+                image.link
+            )
+            .execute(database)
+            .await?;
+            Ok(false)
+        }
+    }
+}
@@ -0,0 +1,420 @@
+// ABOUTME: Builds images out of archived profile pictures and server icons
+// ABOUTME: Fetches archived images, normalizes them to square tiles with date labels and encodes GIF timelines and PNG grids
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+
+use chrono::DateTime;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{Delay, Frame, ImageError, ImageFormat, RgbImage};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use tracing::warn;
+
+use crate::util::charts;
+use crate::util::history::PictureRecord;
+use crate::util::storage::ImageStore;
+
+/// Most recent pictures shown in a timeline.
+pub const MAX_TIMELINE_FRAMES: usize = 60;
+
+/// Largest timeline handed to Discord; bigger ones are re-rendered with smaller frames.
+pub const MAX_OUTPUT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Edge lengths tried for timeline frames, largest first.
+const TIMELINE_SIZES: [u32; 3] = [256, 160, 96];
+
+const FRAME_DELAY_MS: u32 = 1000;
+/// The current picture stays a little longer before the animation loops.
+const LAST_FRAME_DELAY_MS: u32 = 3000;
+
+/// Most recent pictures shown in a grid.
+pub const MAX_GRID_TILES: usize = 100;
+
+/// Edge lengths tried for grid tiles, largest first.
+const GRID_SIZES: [u32; 3] = [128, 96, 64];
+const GRID_COLUMNS: usize = 10;
+const GRID_GAP: u32 = 8;
+const GRID_BACKGROUND: [u8; 3] = [43, 45, 49];
+const GRID_TEXT: RGBColor = RGBColor(219, 222, 225);
+
+/// GIF quantization speed from 1 (best) to 30 (fastest).
+const GIF_SPEED: i32 = 10;
+
+/// An archived image with the time it was put in place.
+pub struct DatedImage {
+    pub changed_at: i64,
+    pub bytes: Vec<u8>,
+}
+
+/// Building an image failed.
+#[derive(Debug)]
+pub enum ImagingError {
+    /// None of the archived images could be loaded or decoded.
+    NoImages,
+    /// Even the smallest rendering exceeds the output limit.
+    TooLarge(usize),
+    Encode(ImageError),
+    Draw(String),
+}
+
+impl fmt::Display for ImagingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImagingError::NoImages => write!(f, "No archived image could be loaded"),
+            ImagingError::TooLarge(bytes) => {
+                write!(f, "Rendered image is too large ({} bytes)", bytes)
+            }
+            ImagingError::Encode(err) => write!(f, "Encoding error: {}", err),
+            ImagingError::Draw(err) => write!(f, "Drawing error: {}", err),
+        }
+    }
+}
+
+impl Error for ImagingError {}
+
+impl From<ImageError> for ImagingError {
+    fn from(err: ImageError) -> Self {
+        ImagingError::Encode(err)
+    }
+}
+
+impl<E: Error + Send + Sync> From<DrawingAreaErrorKind<E>> for ImagingError {
+    fn from(err: DrawingAreaErrorKind<E>) -> Self {
+        ImagingError::Draw(err.to_string())
+    }
+}
+
+/// Downloads archived images from the image store, oldest first.
+///
+/// Each link is fetched once, so reverted pictures do not cost another download. Records without
+/// a link or whose download fails are skipped and logged.
+///
+/// # Arguments
+/// * `image_store` - Backend holding the archived images
+/// * `records` - The pictures to fetch, in any order
+pub async fn fetch_archived(
+    image_store: &dyn ImageStore,
+    records: &[PictureRecord],
+) -> Vec<DatedImage> {
+    let mut records: Vec<&PictureRecord> = records.iter().collect();
+    records.sort_by_key(|record| record.changed_at);
+
+    let mut downloads: HashMap<&str, Option<Vec<u8>>> = HashMap::new();
+    let mut images = Vec::with_capacity(records.len());
+
+    for record in records {
+        let Some(link) = record.link.as_deref() else {
+            continue;
+        };
+
+        if !downloads.contains_key(link) {
+            let bytes = match image_store.fetch(link).await {
+                Ok(bytes) => Some(bytes),
+                Err(e) => {
+                    warn!(link = %link, error = %e, "Failed to fetch archived image");
+                    None
+                }
+            };
+            downloads.insert(link, bytes);
+        }
+
+        if let Some(Some(bytes)) = downloads.get(link) {
+            images.push(DatedImage {
+                changed_at: record.changed_at,
+                bytes: bytes.clone(),
+            });
+        }
+    }
+
+    images
+}
+
+/// Renders an animated GIF showing the images one after another with their dates.
+///
+/// Only the last `MAX_TIMELINE_FRAMES` images are used. Images that cannot be decoded are
+/// skipped. Frames shrink until the GIF fits into `MAX_OUTPUT_BYTES`.
+///
+/// # Arguments
+/// * `images` - Archived images, oldest first
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The encoded GIF
+/// * `Err(ImagingError)` - Nothing could be decoded, encoding failed or the result is too large
+pub fn render_timeline(images: &[DatedImage]) -> Result<Vec<u8>, ImagingError> {
+    let images = &images[images.len().saturating_sub(MAX_TIMELINE_FRAMES)..];
+
+    let decoded = decode(images);
+    if decoded.is_empty() {
+        return Err(ImagingError::NoImages);
+    }
+
+    let mut size_of_last = 0;
+    for size in TIMELINE_SIZES {
+        let mut frames = Vec::with_capacity(decoded.len());
+        for (index, (changed_at, image)) in decoded.iter().enumerate() {
+            let tile = labelled_tile(image, size, &format_date(*changed_at))?;
+            let delay = if index + 1 == decoded.len() {
+                LAST_FRAME_DELAY_MS
+            } else {
+                FRAME_DELAY_MS
+            };
+            frames.push(Frame::from_parts(
+                image::DynamicImage::ImageRgb8(tile).into_rgba8(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(delay, 1),
+            ));
+        }
+
+        let gif = encode_gif(frames)?;
+        if gif.len() <= MAX_OUTPUT_BYTES {
+            return Ok(gif);
+        }
+        size_of_last = gif.len();
+    }
+
+    Err(ImagingError::TooLarge(size_of_last))
+}
+
+/// Renders the images as a PNG grid, oldest first, with each date written beneath its tile.
+///
+/// Only the last `MAX_GRID_TILES` images are used. Images that cannot be decoded are skipped.
+/// Tiles shrink until the PNG fits into `MAX_OUTPUT_BYTES`.
+///
+/// # Arguments
+/// * `images` - Archived images, oldest first
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The encoded PNG
+/// * `Err(ImagingError)` - Nothing could be decoded, drawing failed or the result is too large
+pub fn render_grid(images: &[DatedImage]) -> Result<Vec<u8>, ImagingError> {
+    let decoded = decode(&images[images.len().saturating_sub(MAX_GRID_TILES)..]);
+    if decoded.is_empty() {
+        return Err(ImagingError::NoImages);
+    }
+
+    let columns = decoded.len().min(GRID_COLUMNS);
+    let rows = decoded.len().div_ceil(GRID_COLUMNS);
+
+    let mut size_of_last = 0;
+    for size in GRID_SIZES {
+        let label_height = (size / 6).max(14);
+        let cell_width = size + GRID_GAP;
+        let cell_height = size + label_height + GRID_GAP;
+        let width = columns as u32 * cell_width + GRID_GAP;
+        let height = rows as u32 * cell_height + GRID_GAP;
+
+        let mut canvas = RgbImage::from_pixel(width, height, image::Rgb(GRID_BACKGROUND));
+        for (index, (_, image)) in decoded.iter().enumerate() {
+            let x = GRID_GAP + (index % GRID_COLUMNS) as u32 * cell_width;
+            let y = GRID_GAP + (index / GRID_COLUMNS) as u32 * cell_height;
+            let tile = image
+                .resize_to_fill(size, size, FilterType::Triangle)
+                .to_rgb8();
+            image::imageops::replace(&mut canvas, &tile, i64::from(x), i64::from(y));
+        }
+
+        charts::register_font();
+        {
+            let root =
+                BitMapBackend::with_buffer(canvas.as_mut(), (width, height)).into_drawing_area();
+            let font_size = f64::from(label_height) * 0.7;
+            for (index, (changed_at, _)) in decoded.iter().enumerate() {
+                let x = GRID_GAP + (index % GRID_COLUMNS) as u32 * cell_width + size / 2;
+                let y = GRID_GAP + (index / GRID_COLUMNS) as u32 * cell_height + size;
+                root.draw(&Text::new(
+                    format_date(*changed_at),
+                    (x as i32, (y + label_height / 2) as i32),
+                    ("sans-serif", font_size)
+                        .into_font()
+                        .color(&GRID_TEXT)
+                        .pos(Pos::new(HPos::Center, VPos::Center)),
+                ))?;
+            }
+            root.present()?;
+        }
+
+        let mut png = Vec::new();
+        canvas.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        if png.len() <= MAX_OUTPUT_BYTES {
+            return Ok(png);
+        }
+        size_of_last = png.len();
+    }
+
+    Err(ImagingError::TooLarge(size_of_last))
+}
+
+/// Scales and crops an image to a square tile and writes `label` onto a band at its bottom.
+pub fn labelled_tile(
+    image: &image::DynamicImage,
+    size: u32,
+    label: &str,
+) -> Result<RgbImage, ImagingError> {
+    let mut tile = image
+        .resize_to_fill(size, size, FilterType::Triangle)
+        .to_rgb8();
+
+    charts::register_font();
+    {
+        let root = BitMapBackend::with_buffer(tile.as_mut(), (size, size)).into_drawing_area();
+        let band_height = (size / 7).max(14);
+        let font_size = f64::from(band_height) * 0.7;
+
+        root.draw(&Rectangle::new(
+            [(0, (size - band_height) as i32), (size as i32, size as i32)],
+            BLACK.mix(0.6).filled(),
+        ))?;
+        root.draw(&Text::new(
+            label.to_string(),
+            ((size / 2) as i32, (size - band_height / 2) as i32),
+            ("sans-serif", font_size)
+                .into_font()
+                .color(&WHITE)
+                .pos(Pos::new(HPos::Center, VPos::Center)),
+        ))?;
+        root.present()?;
+    }
+
+    Ok(tile)
+}
+
+/// Formats a Unix timestamp as the UTC date shown on tiles.
+pub fn format_date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// Decodes the images, skipping and logging those that are not a supported format.
+fn decode(images: &[DatedImage]) -> Vec<(i64, image::DynamicImage)> {
+    images
+        .iter()
+        .filter_map(|image| match image::load_from_memory(&image.bytes) {
+            Ok(decoded) => Some((image.changed_at, decoded)),
+            Err(e) => {
+                warn!(changed_at = image.changed_at, error = %e, "Skipping undecodable image");
+                None
+            }
+        })
+        .collect()
+}
+
+fn encode_gif(frames: Vec<Frame>) -> Result<Vec<u8>, ImagingError> {
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut gif, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames)?;
+    }
+
+    Ok(gif)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(color: [u8; 3], width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_pixel(width, height, image::Rgb(color));
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_timeline_is_animated_gif() {
+        let images = vec![
+            DatedImage {
+                changed_at: 1_700_000_000,
+                bytes: png([255, 0, 0], 128, 128),
+            },
+            DatedImage {
+                changed_at: 1_700_100_000,
+                bytes: b"not an image".to_vec(),
+            },
+            DatedImage {
+                changed_at: 1_700_200_000,
+                bytes: png([0, 0, 255], 300, 200),
+            },
+        ];
+
+        let gif = render_timeline(&images).unwrap();
+
+        assert_eq!(&gif[..6], b"GIF89a");
+        let decoder = image::codecs::gif::GifDecoder::new(Cursor::new(gif)).unwrap();
+        let frames = image::AnimationDecoder::into_frames(decoder)
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2, "Undecodable images should be skipped");
+        assert_eq!(frames[0].buffer().dimensions(), (256, 256));
+    }
+
+    #[test]
+    fn test_timeline_without_images() {
+        assert!(matches!(render_timeline(&[]), Err(ImagingError::NoImages)));
+    }
+
+    #[test]
+    fn test_grid_wraps_rows() {
+        let images: Vec<DatedImage> = (0..12)
+            .map(|index| DatedImage {
+                changed_at: 1_700_000_000 + index * 86400,
+                bytes: png([0, 255, 0], 64, 64),
+            })
+            .collect();
+
+        let grid = image::load_from_memory(&render_grid(&images).unwrap())
+            .unwrap()
+            .to_rgb8();
+
+        let label_height = 128 / 6;
+        assert_eq!(grid.width(), 10 * (128 + GRID_GAP) + GRID_GAP);
+        assert_eq!(
+            grid.height(),
+            2 * (128 + label_height + GRID_GAP) + GRID_GAP
+        );
+        assert_eq!(grid.get_pixel(GRID_GAP, GRID_GAP).0, [0, 255, 0]);
+        // The second row only holds two tiles
+        let third_tile_x = GRID_GAP + 2 * (128 + GRID_GAP);
+        let second_row_y = GRID_GAP + 128 + label_height + GRID_GAP;
+        assert_eq!(
+            grid.get_pixel(third_tile_x, second_row_y).0,
+            GRID_BACKGROUND
+        );
+    }
+
+    #[test]
+    fn test_grid_without_images() {
+        let images = vec![DatedImage {
+            changed_at: 1_700_000_000,
+            bytes: b"not an image".to_vec(),
+        }];
+
+        assert!(matches!(render_grid(&images), Err(ImagingError::NoImages)));
+    }
+
+    #[test]
+    fn test_label_band_is_drawn() {
+        let image = image::DynamicImage::ImageRgb8(RgbImage::from_pixel(
+            64,
+            64,
+            image::Rgb([255, 255, 255]),
+        ));
+
+        let tile = labelled_tile(&image, 64, "2024-01-01").unwrap();
+
+        assert_eq!(tile.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_ne!(tile.get_pixel(0, 63).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(1_704_067_200), "2024-01-01");
+    }
+}
@@ -0,0 +1,64 @@
+// ABOUTME: Sets up the tracing subscriber that receives all diagnostics of the bot
+// ABOUTME: Supports human-readable text or one JSON object per line for log aggregators
+use std::str::FromStr;
+
+use tracing_subscriber::EnvFilter;
+
+/// Filter used when `RUST_LOG` is not set.
+const DEFAULT_FILTER: &str = "info,serenity=warn,sqlx=warn";
+
+/// Output format of the log lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines, the default.
+    #[default]
+    Text,
+    /// One JSON object per event, including the fields of the enclosing spans.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "text" | "" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("Unknown log format: {}", other)),
+        }
+    }
+}
+
+/// Installs the global tracing subscriber.
+///
+/// The level filter is read from `RUST_LOG` and falls back to [`DEFAULT_FILTER`].
+///
+/// # Arguments
+/// * `format` - Whether to write text or JSON lines
+pub fn init(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!(" JSON ".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -0,0 +1,176 @@
+// ABOUTME: Prometheus metrics describing update passes, image uploads, commands and the database pool
+// ABOUTME: All metrics live in one process-wide registry that the HTTP server renders on /metrics
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::SqlitePool;
+
+/// Buckets in seconds for whole update passes, which take minutes on large installs.
+const PASS_DURATION_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The process-wide metrics.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// Entities looked at during update passes, by `entity_type`.
+    pub entities_checked: IntCounterVec,
+    /// New or reverted images and new usernames, by `entity_type`.
+    pub changes_detected: IntCounterVec,
+    /// Checks that failed, by `entity_type` and `reason` (download, upload, database).
+    pub check_failures: IntCounterVec,
+    /// Duration of an update pass, by `entity_type`.
+    pub pass_duration: HistogramVec,
+    /// Duration of image uploads to ImgBB.
+    pub imgbb_upload_duration: Histogram,
+    /// Slash command invocations, by `command`.
+    pub command_invocations: IntCounterVec,
+    /// Slash commands that returned an error, by `command` and error `kind`.
+    pub command_errors: IntCounterVec,
+    /// Open connections of the database pool, updated on every scrape.
+    pub db_pool_connections: IntGauge,
+    /// Idle connections of the database pool, updated on every scrape.
+    pub db_pool_idle_connections: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("pfp_checker".to_string()), None)
+            .expect("metric prefix is valid");
+
+        let entities_checked = IntCounterVec::new(
+            Opts::new(
+                "entities_checked_total",
+                "Entities checked during update passes",
+            ),
+            &["entity_type"],
+        )
+        .expect("metric definition is valid");
+        let changes_detected = IntCounterVec::new(
+            Opts::new(
+                "changes_detected_total",
+                "Changed pictures, icons and usernames recorded",
+            ),
+            &["entity_type"],
+        )
+        .expect("metric definition is valid");
+        let check_failures = IntCounterVec::new(
+            Opts::new("check_failures_total", "Entity checks that failed"),
+            &["entity_type", "reason"],
+        )
+        .expect("metric definition is valid");
+        let pass_duration = HistogramVec::new(
+            HistogramOpts::new("update_pass_duration_seconds", "Duration of update passes")
+                .buckets(PASS_DURATION_BUCKETS.to_vec()),
+            &["entity_type"],
+        )
+        .expect("metric definition is valid");
+        let imgbb_upload_duration = Histogram::with_opts(HistogramOpts::new(
+            "imgbb_upload_duration_seconds",
+            "Duration of image uploads to ImgBB",
+        ))
+        .expect("metric definition is valid");
+        let command_invocations = IntCounterVec::new(
+            Opts::new("command_invocations_total", "Slash command invocations"),
+            &["command"],
+        )
+        .expect("metric definition is valid");
+        let command_errors = IntCounterVec::new(
+            Opts::new("command_errors_total", "Slash commands that failed"),
+            &["command", "kind"],
+        )
+        .expect("metric definition is valid");
+        let db_pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Open connections of the database pool",
+        )
+        .expect("metric definition is valid");
+        let db_pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections of the database pool",
+        )
+        .expect("metric definition is valid");
+
+        for collector in [
+            Box::new(entities_checked.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(changes_detected.clone()),
+            Box::new(check_failures.clone()),
+            Box::new(pass_duration.clone()),
+            Box::new(imgbb_upload_duration.clone()),
+            Box::new(command_invocations.clone()),
+            Box::new(command_errors.clone()),
+            Box::new(db_pool_connections.clone()),
+            Box::new(db_pool_idle_connections.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Metrics {
+            registry,
+            entities_checked,
+            changes_detected,
+            check_failures,
+            pass_duration,
+            imgbb_upload_duration,
+            command_invocations,
+            command_errors,
+            db_pool_connections,
+            db_pool_idle_connections,
+        }
+    }
+
+    /// Copies the current usage of the database pool into the pool gauges.
+    pub fn observe_pool(&self, pool: &SqlitePool) {
+        self.db_pool_connections.set(i64::from(pool.size()));
+        self.db_pool_idle_connections.set(pool.num_idle() as i64);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding into a Vec cannot fail");
+        String::from_utf8(buffer).expect("Prometheus text format is UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_labels() {
+        let metrics = Metrics::new();
+        metrics
+            .check_failures
+            .with_label_values(&["server icon", "upload"])
+            .inc();
+        metrics
+            .command_invocations
+            .with_label_values(&["ping"])
+            .inc_by(2);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            r#"pfp_checker_check_failures_total{entity_type="server icon",reason="upload"} 1"#
+        ));
+        assert!(rendered.contains(r#"pfp_checker_command_invocations_total{command="ping"} 2"#));
+    }
+
+    #[test]
+    fn test_render_without_samples() {
+        let rendered = Metrics::new().render();
+        assert!(rendered.contains("pfp_checker_db_pool_connections 0"));
+        assert!(rendered.contains("pfp_checker_imgbb_upload_duration_seconds_count 0"));
+    }
+}
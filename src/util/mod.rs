@@ -1,5 +1,26 @@
+pub mod alerts;
+pub mod charts;
+pub mod check_status;
 pub mod chron_update;
 pub mod config;
+pub mod confirmation;
+pub mod discord;
 pub mod external;
+pub mod fingerprint;
+pub mod format;
+pub mod health;
+pub mod history;
+pub mod http;
+pub mod image_deletion;
+pub mod imaging;
+pub mod logging;
+pub mod metrics;
 pub mod objects;
 pub mod pagination;
+pub mod permissions;
+pub mod retention;
+pub mod slash_command;
+pub mod state;
+pub mod stats;
+pub mod storage;
+pub mod update_runs;
@@ -2,4 +2,5 @@ pub struct EmbedEntry {
     pub title: String,
     pub content: String,
     pub inline: bool,
+    pub image: Option<String>,
 }
@@ -1,6 +1,28 @@
+// ABOUTME: Component routing for paginated history views
+// ABOUTME: Versioned button custom_id codec, the PaginatedView trait, list and gallery pages, and the button router
 use std::fmt;
 use std::num::ParseIntError;
 
+use serenity::all::{
+    ButtonStyle, CommandOptionType, ComponentInteraction, Context, CreateActionRow, CreateButton,
+    CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+use crate::util::objects::EmbedEntry;
+
+/// Version tag prefixed to pagination custom_ids.
+///
+/// Buttons on messages posted before the tag was introduced use the legacy
+/// underscore format and are still decoded by [`parse_pagination_button`].
+pub const CUSTOM_ID_VERSION: &str = "pg1";
+
+/// Number of entries per page unless a view overrides it.
+pub const DEFAULT_ENTRIES_PER_PAGE: usize = 10;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct PaginationButton {
     pub command: String,
@@ -20,7 +42,7 @@ impl PaginationButton {
     /// The new page number (0-indexed) to navigate to
     ///
     /// # Examples
-    /// ```ignore
+    /// ```
     /// # use pfp_checker::util::pagination::PaginationButton;
     /// let button = PaginationButton {
     ///     command: "pfphistory".to_string(),
@@ -71,14 +93,36 @@ impl fmt::Display for PaginationParseError {
 
 impl std::error::Error for PaginationParseError {}
 
+/// Encodes a pagination button into a versioned custom_id.
+///
+/// Format: `pg1:{view}:{direction}:{page}:{target_id}`
+///
+/// # Arguments
+/// * `view` - Name of the paginated view, usually the command name
+/// * `direction` - One of `first`, `back`, `next` or `last`
+/// * `current_page` - The page displayed when the button was rendered (0-indexed)
+/// * `target_id` - The user or guild ID the view shows history for
+pub fn encode_pagination_button(
+    view: &str,
+    direction: &str,
+    current_page: usize,
+    target_id: u64,
+) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        CUSTOM_ID_VERSION, view, direction, current_page, target_id
+    )
+}
+
 /// Parses a pagination button custom_id into structured components.
 ///
-/// Supports two formats:
+/// Supports the versioned format produced by [`encode_pagination_button`] and two legacy formats:
 /// - 3-part: `{command}_first_{target_id}` or `{command}_last_{target_id}`
 /// - 4-part: `{command}_{back|next}_{page}_{target_id}`
 ///
 /// The `target_id` can be either a user ID (for pfphistory/usernamehistory)
-/// or a guild ID (for serverpfphistory).
+/// or a guild ID (for serverpfphistory). Whether the command has a paginated view
+/// is checked by the [`PaginationRouter`], not here.
 ///
 /// # Arguments
 /// * `custom_id` - The button's custom_id string
@@ -88,13 +132,51 @@ impl std::error::Error for PaginationParseError {}
 /// * `Err(PaginationParseError)` - Parsing failed
 ///
 /// # Examples
-/// ```ignore
+/// ```
 /// use pfp_checker::util::pagination::parse_pagination_button;
 ///
 /// let result = parse_pagination_button("pfphistory_first_123456");
 /// assert!(result.is_ok());
 /// ```
 pub fn parse_pagination_button(custom_id: &str) -> Result<PaginationButton, PaginationParseError> {
+    match custom_id.split_once(':') {
+        Some((CUSTOM_ID_VERSION, rest)) => parse_versioned_button(rest),
+        Some((version, _)) => Err(PaginationParseError::InvalidFormat(format!(
+            "Unknown custom_id version {}",
+            version
+        ))),
+        None => parse_legacy_button(custom_id),
+    }
+}
+
+/// Parses the part of a `pg1` custom_id following the version tag.
+fn parse_versioned_button(rest: &str) -> Result<PaginationButton, PaginationParseError> {
+    let parts: Vec<&str> = rest.split(':').collect();
+
+    if parts.len() != 4 {
+        return Err(PaginationParseError::InvalidFormat(format!(
+            "Expected 4 parts after the version, got {}",
+            parts.len()
+        )));
+    }
+
+    let current_page = parts[2]
+        .parse::<usize>()
+        .map_err(PaginationParseError::InvalidPage)?;
+    let target_id = parts[3]
+        .parse::<u64>()
+        .map_err(PaginationParseError::InvalidUserId)?;
+
+    Ok(PaginationButton {
+        command: parts[0].to_string(),
+        direction: parts[1].to_string(),
+        target_id,
+        current_page,
+    })
+}
+
+/// Parses the underscore separated custom_ids used before versioning.
+fn parse_legacy_button(custom_id: &str) -> Result<PaginationButton, PaginationParseError> {
     let parts: Vec<&str> = custom_id.split('_').collect();
 
     if parts.len() < 3 {
@@ -107,16 +189,6 @@ pub fn parse_pagination_button(custom_id: &str) -> Result<PaginationButton, Pagi
     let command = parts[0];
     let direction = parts[1];
 
-    // Validate command is supported
-    match command {
-        "pfphistory" | "usernamehistory" | "serverpfphistory" => {}
-        _ => {
-            return Err(PaginationParseError::UnsupportedCommand(
-                command.to_string(),
-            ))
-        }
-    }
-
     // Parse based on direction (determines format)
     let (target_id, current_page) = if direction == "first" || direction == "last" {
         // 3-part format: {command}_{first|last}_{target_id}
@@ -155,6 +227,303 @@ pub fn parse_pagination_button(custom_id: &str) -> Result<PaginationButton, Pagi
     })
 }
 
+/// Layout of a picture history, chosen with the `mode` option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HistoryMode {
+    /// Several entries per page as embed fields with links.
+    #[default]
+    List,
+    /// One picture per page shown as the embed image.
+    Gallery,
+}
+
+impl HistoryMode {
+    /// Builds the optional `mode` command option.
+    pub fn option() -> CreateCommandOption {
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "mode",
+            "Show a list of links or one picture per page.",
+        )
+        .add_string_choice("List", "list")
+        .add_string_choice("Gallery", "gallery")
+    }
+
+    /// Reads the `mode` option, falling back to the list layout.
+    pub fn from_options(options: &[ResolvedOption<'_>]) -> Self {
+        options
+            .iter()
+            .find_map(|option| match (option.name, &option.value) {
+                ("mode", ResolvedValue::String("gallery")) => Some(HistoryMode::Gallery),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Everything a paginated view displays, loaded fresh for every page.
+pub struct PageSource {
+    pub title: String,
+    pub entries: Vec<EmbedEntry>,
+}
+
+/// A history view that can be paged through with First/Back/Next/Last buttons.
+///
+/// Implementations are registered with a [`PaginationRouter`], which decodes button
+/// clicks, reloads the entries and renders the requested page.
+#[async_trait]
+pub trait PaginatedView: Send + Sync {
+    /// Unique name of the view, stored in the button custom_ids.
+    fn name(&self) -> &'static str;
+
+    /// Number of entries shown on one page.
+    fn entries_per_page(&self) -> usize {
+        DEFAULT_ENTRIES_PER_PAGE
+    }
+
+    /// Loads the title and all entries of the view for a user or guild.
+    async fn load(
+        &self,
+        ctx: &Context,
+        database: &SqlitePool,
+        target_id: u64,
+    ) -> Result<PageSource, BotError>;
+
+    /// Renders one page of the view with its navigation buttons.
+    fn render_page(
+        &self,
+        source: &PageSource,
+        target_id: u64,
+        page: usize,
+    ) -> CreateInteractionResponseMessage {
+        render_list_page(
+            self.name(),
+            source,
+            target_id,
+            page,
+            self.entries_per_page(),
+        )
+    }
+}
+
+/// Renders a page as one embed field per entry followed by the navigation buttons.
+///
+/// # Arguments
+/// * `view` - Name of the view, used for the button custom_ids
+/// * `source` - Title and entries of the view
+/// * `target_id` - The user or guild ID the view shows history for
+/// * `page` - The page to render (0-indexed)
+/// * `entries_per_page` - Number of entries per page
+pub fn render_list_page(
+    view: &str,
+    source: &PageSource,
+    target_id: u64,
+    page: usize,
+    entries_per_page: usize,
+) -> CreateInteractionResponseMessage {
+    let total_pages = (source.entries.len() as f32 / entries_per_page as f32).ceil() as usize;
+    let start = (page * entries_per_page).min(source.entries.len());
+    let end = (start + entries_per_page).min(source.entries.len());
+
+    let embed = CreateEmbed::new()
+        .title(&source.title)
+        .fields(
+            source.entries[start..end]
+                .iter()
+                .map(|entry| (entry.title.clone(), entry.content.clone(), entry.inline)),
+        )
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {}",
+            page + 1,
+            total_pages
+        )));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(vec![navigation_buttons(
+            view,
+            target_id,
+            page,
+            end == source.entries.len(),
+        )])
+}
+
+/// Renders a page showing a single entry, with its image as the embed image.
+///
+/// The entry title and content form the description, so timestamps and links in them render.
+/// Views using this should show one entry per page.
+///
+/// # Arguments
+/// * `view` - Name of the view, used for the button custom_ids
+/// * `source` - Title and entries of the view
+/// * `target_id` - The user or guild ID the view shows history for
+/// * `page` - The entry to render (0-indexed)
+pub fn render_gallery_page(
+    view: &str,
+    source: &PageSource,
+    target_id: u64,
+    page: usize,
+) -> CreateInteractionResponseMessage {
+    let page = page.min(source.entries.len().saturating_sub(1));
+
+    let mut embed = CreateEmbed::new()
+        .title(&source.title)
+        .footer(CreateEmbedFooter::new(format!(
+            "Picture {} of {}",
+            page + 1,
+            source.entries.len()
+        )));
+    if let Some(entry) = source.entries.get(page) {
+        embed = embed.description(format!("{}\n{}", entry.title, entry.content));
+        if let Some(image) = &entry.image {
+            embed = embed.image(image);
+        }
+    }
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(vec![navigation_buttons(
+            view,
+            target_id,
+            page,
+            page + 1 >= source.entries.len(),
+        )])
+}
+
+/// Builds the First/Back/Next/Last button row of a paginated view.
+///
+/// # Arguments
+/// * `view` - Name of the view, used for the button custom_ids
+/// * `target_id` - The user or guild ID the view shows history for
+/// * `page` - The page currently displayed (0-indexed)
+/// * `is_last_page` - Whether the page shows the final entry
+pub fn navigation_buttons(
+    view: &str,
+    target_id: u64,
+    page: usize,
+    is_last_page: bool,
+) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(encode_pagination_button(view, "first", page, target_id))
+            .label("First")
+            .style(ButtonStyle::Primary)
+            .disabled(page == 0),
+        CreateButton::new(encode_pagination_button(view, "back", page, target_id))
+            .label("Back")
+            .style(ButtonStyle::Primary)
+            .disabled(page == 0),
+        CreateButton::new(encode_pagination_button(view, "next", page, target_id))
+            .label("Next")
+            .style(ButtonStyle::Primary)
+            .disabled(is_last_page),
+        CreateButton::new(encode_pagination_button(view, "last", page, target_id))
+            .label("Last")
+            .style(ButtonStyle::Primary)
+            .disabled(is_last_page),
+    ])
+}
+
+/// Builds a button that opens the first page of a view as a new message only the clicker sees.
+///
+/// Used to link to a history from messages that are not themselves paginated, such as alerts.
+///
+/// # Arguments
+/// * `view` - Name of the view to open
+/// * `target_id` - The user or guild ID the view shows history for
+/// * `label` - Text on the button
+pub fn open_view_button(view: &str, target_id: u64, label: impl Into<String>) -> CreateButton {
+    CreateButton::new(encode_pagination_button(view, "open", 0, target_id))
+        .label(label)
+        .style(ButtonStyle::Secondary)
+}
+
+/// Builds the command response showing the first page of a view.
+pub fn first_page_response(
+    view: &dyn PaginatedView,
+    source: &PageSource,
+    target_id: u64,
+) -> CreateInteractionResponse {
+    CreateInteractionResponse::Message(view.render_page(source, target_id, 0))
+}
+
+/// Dispatches pagination button clicks to the registered views.
+#[derive(Default)]
+pub struct PaginationRouter {
+    views: Vec<Box<dyn PaginatedView>>,
+}
+
+impl PaginationRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a paginated view.
+    pub fn with_view(mut self, view: impl PaginatedView + 'static) -> Self {
+        self.views.push(Box::new(view));
+        self
+    }
+
+    /// Looks up the view registered under a name.
+    ///
+    /// # Returns
+    /// * `Err(PaginationParseError::UnsupportedCommand)` - No view has that name
+    pub fn view_for(&self, name: &str) -> Result<&dyn PaginatedView, PaginationParseError> {
+        self.views
+            .iter()
+            .find(|view| view.name() == name)
+            .map(|view| view.as_ref())
+            .ok_or_else(|| PaginationParseError::UnsupportedCommand(name.to_string()))
+    }
+
+    /// Decodes a custom_id into its view and button data.
+    ///
+    /// # Returns
+    /// * `Result<(&dyn PaginatedView, PaginationButton), PaginationParseError>` - The view to render and the clicked button
+    pub fn route(
+        &self,
+        custom_id: &str,
+    ) -> Result<(&dyn PaginatedView, PaginationButton), PaginationParseError> {
+        let button = parse_pagination_button(custom_id)?;
+        let view = self.view_for(&button.command)?;
+        Ok((view, button))
+    }
+
+    /// Handles a component interaction as a pagination button of a registered view.
+    ///
+    /// # Arguments
+    /// * `ctx` - The Serenity context
+    /// * `component` - The button interaction
+    /// * `database` - SQLite connection pool
+    ///
+    /// # Returns
+    /// * `Result<(), BotError>` - Ok if the page was shown, `BotError::InvalidInput` if the button is not a known pagination button
+    pub async fn dispatch(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+        database: &SqlitePool,
+    ) -> Result<(), BotError> {
+        let (view, button) = self.route(&component.data.custom_id).map_err(|why| {
+            BotError::InvalidInput(format!("This button is no longer supported ({}).", why))
+        })?;
+
+        let source = view.load(ctx, database, button.target_id).await?;
+        let new_page = button.resolve_new_page(source.entries.len(), view.entries_per_page());
+        let page = view.render_page(&source, button.target_id, new_page);
+
+        // Opening a view leaves the message with the button untouched
+        let response = if button.direction == "open" {
+            CreateInteractionResponse::Message(page.ephemeral(true))
+        } else {
+            CreateInteractionResponse::UpdateMessage(page)
+        };
+
+        component.create_response(&ctx.http, response).await?;
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -266,11 +635,88 @@ mod tests {
         }
     }
 
+    struct DummyView;
+
+    #[async_trait]
+    impl PaginatedView for DummyView {
+        fn name(&self) -> &'static str {
+            "pfphistory"
+        }
+
+        async fn load(
+            &self,
+            _ctx: &Context,
+            _database: &SqlitePool,
+            _target_id: u64,
+        ) -> Result<PageSource, BotError> {
+            Ok(PageSource {
+                title: String::new(),
+                entries: Vec::new(),
+            })
+        }
+    }
+
+    fn gallery_source() -> PageSource {
+        PageSource {
+            title: "History".to_string(),
+            entries: (0..3)
+                .map(|index| EmbedEntry {
+                    title: format!("Picture {}", index),
+                    content: format!("Checksum: {}", index),
+                    inline: false,
+                    image: Some(format!("https://i.ibb.co/{}.png", index)),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_gallery_page_shows_one_image() {
+        let message =
+            serde_json::to_value(render_gallery_page("pfpgallery", &gallery_source(), 42, 1))
+                .unwrap();
+
+        let embed = &message["embeds"][0];
+        assert_eq!(embed["image"]["url"], "https://i.ibb.co/1.png");
+        assert_eq!(embed["description"], "Picture 1\nChecksum: 1");
+        assert_eq!(embed["footer"]["text"], "Picture 2 of 3");
+
+        let buttons = &message["components"][0]["components"];
+        assert_eq!(buttons[2]["custom_id"], "pg1:pfpgallery:next:1:42");
+        assert_eq!(buttons[2]["disabled"], false);
+    }
+
+    #[test]
+    fn test_gallery_last_page_disables_next() {
+        let message =
+            serde_json::to_value(render_gallery_page("pfpgallery", &gallery_source(), 42, 2))
+                .unwrap();
+
+        let buttons = &message["components"][0]["components"];
+        assert_eq!(buttons[0]["disabled"], false);
+        assert_eq!(buttons[2]["disabled"], true);
+        assert_eq!(buttons[3]["disabled"], true);
+    }
+
+    #[test]
+    fn test_open_view_button() {
+        let button =
+            serde_json::to_value(open_view_button("usernamehistory", 42, "History")).unwrap();
+        let parsed = parse_pagination_button(button["custom_id"].as_str().unwrap()).unwrap();
+
+        assert_eq!(parsed.command, "usernamehistory");
+        assert_eq!(parsed.direction, "open");
+        assert_eq!(parsed.target_id, 42);
+        assert_eq!(parsed.resolve_new_page(25, 10), 0);
+    }
+
     #[test]
     fn test_parse_unsupported_command() {
-        let result = parse_pagination_button("unknowncommand_first_123456");
+        // Parsing accepts any command, the router rejects those without a view
+        let router = PaginationRouter::new().with_view(DummyView);
+        let result = router.route("unknowncommand_first_123456");
         assert!(result.is_err());
-        match result.unwrap_err() {
+        match result.err().unwrap() {
             PaginationParseError::UnsupportedCommand(cmd) => {
                 assert_eq!(cmd, "unknowncommand");
             }
@@ -278,6 +724,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_route_registered_view() {
+        let router = PaginationRouter::new().with_view(DummyView);
+        let (view, button) = router.route("pg1:pfphistory:next:2:123456").unwrap();
+        assert_eq!(view.name(), "pfphistory");
+        assert_eq!(button.current_page, 2);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let custom_id = encode_pagination_button("serverpfphistory", "back", 4, u64::MAX);
+        assert_eq!(
+            custom_id,
+            "pg1:serverpfphistory:back:4:18446744073709551615"
+        );
+
+        let button = parse_pagination_button(&custom_id).unwrap();
+        assert_eq!(
+            button,
+            PaginationButton {
+                command: "serverpfphistory".to_string(),
+                direction: "back".to_string(),
+                target_id: u64::MAX,
+                current_page: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_versioned_first_button_keeps_page() {
+        let button = parse_pagination_button("pg1:usernamehistory:first:3:42").unwrap();
+        assert_eq!(button.direction, "first");
+        assert_eq!(button.current_page, 3);
+        assert_eq!(button.resolve_new_page(100, 10), 0);
+    }
+
+    #[test]
+    fn test_parse_unknown_version() {
+        let result = parse_pagination_button("pg9:pfphistory:next:2:123456");
+        assert!(matches!(
+            result,
+            Err(PaginationParseError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_versioned_wrong_part_count() {
+        let result = parse_pagination_button("pg1:pfphistory:next:123456");
+        assert!(matches!(
+            result,
+            Err(PaginationParseError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_versioned_invalid_page() {
+        let result = parse_pagination_button("pg1:pfphistory:next:two:123456");
+        assert!(matches!(result, Err(PaginationParseError::InvalidPage(_))));
+    }
+
     #[test]
     fn test_parse_first_button_with_too_many_parts() {
         let result = parse_pagination_button("pfphistory_first_123456_extra");
@@ -0,0 +1,239 @@
+// ABOUTME: Per-server role requirements for slash commands
+// ABOUTME: Stores required roles in the CommandPermission table and checks invoking members against them
+use serenity::all::{CommandInteraction, ComponentInteraction, GuildId, Member, RoleId};
+use sqlx::SqlitePool;
+
+use crate::error::BotError;
+
+/// Commands whose required roles can be configured through `/config permissions`.
+///
+/// `/config` itself is deliberately not listed: it is always restricted to members
+/// with the 'Manage Server' permission so a misconfiguration cannot lock admins out.
+pub const CONFIGURABLE_COMMANDS: &[&str] = &[
+    "ping",
+    "botstatus",
+    "monitor",
+    "removemonitor",
+    "restoremonitor",
+    "pfphistory",
+    "usernamehistory",
+    "stats",
+    "pfptimeline",
+    "pfpgrid",
+    "whoused",
+    "monitorserver",
+    "removemonitorserver",
+    "serverpfphistory",
+    "serverstats",
+    "trackingerrors",
+];
+
+/// Checks whether the invoking member may run the command in this server.
+///
+/// A command without any configured roles is open to everyone Discord lets through
+/// (see the default member permissions set in each `register()`). Once at least one
+/// role is configured, the member needs one of them. Administrators always pass.
+/// Commands that can carry a role requirement only work inside a server, as Discord
+/// applies no member permissions in DMs.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `interaction` - The command interaction to check
+///
+/// # Returns
+/// * `Ok(bool)` - Whether the member is allowed to run the command
+/// * `Err(BotError::GuildOnly)` - A command with role requirements was used outside of a server
+pub async fn is_permitted(
+    database: &SqlitePool,
+    interaction: &CommandInteraction,
+) -> Result<bool, BotError> {
+    member_may_run(
+        database,
+        interaction.guild_id,
+        interaction.member.as_deref(),
+        &interaction.data.name,
+    )
+    .await
+}
+
+/// Checks whether the member clicking a button may run the command behind it.
+///
+/// Buttons that act like a command, such as undoing a removal, are subject to the same
+/// role requirements as the command itself; see [`is_permitted`].
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `component` - The button interaction to check
+/// * `command_name` - The command whose role requirements apply
+pub async fn is_component_permitted(
+    database: &SqlitePool,
+    component: &ComponentInteraction,
+    command_name: &str,
+) -> Result<bool, BotError> {
+    member_may_run(
+        database,
+        component.guild_id,
+        component.member.as_ref(),
+        command_name,
+    )
+    .await
+}
+
+async fn member_may_run(
+    database: &SqlitePool,
+    guild_id: Option<GuildId>,
+    member: Option<&Member>,
+    command_name: &str,
+) -> Result<bool, BotError> {
+    let configurable = CONFIGURABLE_COMMANDS.contains(&command_name);
+    let (Some(guild_id), Some(member)) = (guild_id, member) else {
+        if configurable {
+            return Err(BotError::GuildOnly);
+        }
+        return Ok(true);
+    };
+
+    if member
+        .permissions
+        .is_some_and(|permissions| permissions.administrator())
+    {
+        return Ok(true);
+    }
+
+    let required_roles = fetch_required_roles(database, i64::from(guild_id), command_name).await?;
+
+    if required_roles.is_empty() {
+        return Ok(true);
+    }
+
+    Ok(member
+        .roles
+        .iter()
+        .any(|role| required_roles.contains(role)))
+}
+
+/// Requires the invoking member to have the 'Manage Server' permission.
+///
+/// # Returns
+/// * `Err(BotError::Permission)` - The member lacks the permission
+pub fn require_manage_guild(interaction: &CommandInteraction) -> Result<(), BotError> {
+    let lacks_permission = interaction
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| !permissions.manage_guild());
+
+    if lacks_permission {
+        return Err(BotError::Permission(
+            "You need 'Manage Server' permission to use this command.".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fetches the roles required to run a command in a server.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `server_id` - The server's Discord ID
+/// * `command_name` - Name of the slash command without the leading slash
+///
+/// # Returns
+/// * `Result<Vec<RoleId>, sqlx::Error>` - The configured roles, empty if unrestricted
+pub async fn fetch_required_roles(
+    database: &SqlitePool,
+    server_id: i64,
+    command_name: &str,
+) -> Result<Vec<RoleId>, sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT roleId FROM CommandPermission WHERE serverId = ? AND commandName = ?",
+        server_id,
+        command_name
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| entry.roleId)
+        .filter_map(|role_id| u64::try_from(role_id).ok())
+        .map(RoleId::new)
+        .collect())
+}
+
+/// Fetches every role requirement configured for a server, ordered by command name.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `server_id` - The server's Discord ID
+///
+/// # Returns
+/// * `Result<Vec<(String, RoleId)>, sqlx::Error>` - Pairs of command name and required role
+pub async fn fetch_all_required_roles(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Vec<(String, RoleId)>, sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT commandName, roleId FROM CommandPermission WHERE serverId = ? ORDER BY commandName",
+        server_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let role_id = u64::try_from(entry.roleId?).ok()?;
+            Some((entry.commandName?, RoleId::new(role_id)))
+        })
+        .collect())
+}
+
+/// Requires a role for a command in a server.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - `false` if the role was already required
+pub async fn add_required_role(
+    database: &SqlitePool,
+    server_id: i64,
+    command_name: &str,
+    role_id: RoleId,
+) -> Result<bool, sqlx::Error> {
+    let role_id = i64::from(role_id);
+
+    let result = sqlx::query!(
+        "INSERT OR IGNORE INTO CommandPermission (serverId, commandName, roleId) VALUES (?, ?, ?)",
+        server_id,
+        command_name,
+        role_id
+    )
+    .execute(database)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes a role requirement for a command in a server.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - `false` if the role was not required
+pub async fn remove_required_role(
+    database: &SqlitePool,
+    server_id: i64,
+    command_name: &str,
+    role_id: RoleId,
+) -> Result<bool, sqlx::Error> {
+    let role_id = i64::from(role_id);
+
+    let result = sqlx::query!(
+        "DELETE FROM CommandPermission WHERE serverId = ? AND commandName = ? AND roleId = ?",
+        server_id,
+        command_name,
+        role_id
+    )
+    .execute(database)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
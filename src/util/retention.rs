@@ -0,0 +1,655 @@
+// ABOUTME: Retention policies that limit how long profile picture, username and server icon history is kept
+// ABOUTME: Selects expired rows, deletes them, queues their unreferenced archived images for deletion and reports what was pruned
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::util::image_deletion;
+use crate::util::storage::ImageStore;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Limits applied to the history of a single user or server.
+///
+/// `None` means the respective limit is disabled. The most recent entry is always
+/// kept because it represents the current picture or username and is needed for
+/// change detection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<i64>,
+    pub max_entries: Option<i64>,
+}
+
+impl RetentionPolicy {
+    /// Whether this policy never removes anything.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age_days.is_none() && self.max_entries.is_none()
+    }
+
+    /// Combines two policies into one that keeps everything either of them keeps.
+    ///
+    /// Used for users tracked in several servers, so that one server's policy cannot
+    /// remove history another server still wants to see.
+    pub fn most_lenient(&self, other: &RetentionPolicy) -> RetentionPolicy {
+        fn lenient(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+            Some(a?.max(b?))
+        }
+
+        RetentionPolicy {
+            max_age_days: lenient(self.max_age_days, other.max_age_days),
+            max_entries: lenient(self.max_entries, other.max_entries),
+        }
+    }
+
+    /// Selects the entries that fall outside of this policy.
+    ///
+    /// # Arguments
+    /// * `changed_at` - Timestamps of an entity's history entries
+    /// * `now` - The current Unix timestamp
+    ///
+    /// # Returns
+    /// Indices into `changed_at` of the entries to remove
+    ///
+    /// # Examples
+    /// ```
+    /// # use pfp_checker::util::retention::RetentionPolicy;
+    /// let policy = RetentionPolicy { max_age_days: None, max_entries: Some(2) };
+    /// assert_eq!(policy.expired_entries(&[30, 10, 20], 40), vec![1]);
+    /// ```
+    pub fn expired_entries(&self, changed_at: &[i64], now: i64) -> Vec<usize> {
+        let mut newest_first: Vec<usize> = (0..changed_at.len()).collect();
+        newest_first.sort_by(|a, b| changed_at[*b].cmp(&changed_at[*a]));
+
+        let max_entries = self
+            .max_entries
+            .map(|max| usize::try_from(max.max(1)).unwrap_or(usize::MAX))
+            .unwrap_or(usize::MAX);
+        let cutoff = self
+            .max_age_days
+            .map(|days| now - days * SECONDS_PER_DAY)
+            .unwrap_or(i64::MIN);
+
+        let mut expired: Vec<usize> = newest_first
+            .into_iter()
+            .enumerate()
+            // Never remove the current entry
+            .skip(1)
+            .filter(|(rank, index)| *rank >= max_entries || changed_at[*index] < cutoff)
+            .map(|(_, index)| index)
+            .collect();
+
+        expired.sort_unstable();
+        expired
+    }
+}
+
+/// Which part of the database a prune run covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PruneScope {
+    /// Every tracked user and server
+    All,
+    /// A single server's icon history and the users tracked in it
+    Server(i64),
+}
+
+/// Summary of what a prune run removed, or would remove in a dry run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub profile_pictures: u64,
+    pub usernames: u64,
+    pub server_pictures: u64,
+    /// Archived images no longer referenced by any row
+    pub images: u64,
+    /// Archived images the image store failed to delete, kept queued for another attempt
+    pub failed_image_deletions: u64,
+}
+
+impl PruneReport {
+    pub fn total_rows(&self) -> u64 {
+        self.profile_pictures + self.usernames + self.server_pictures
+    }
+}
+
+/// Fetches the retention policy configured for a server, if any.
+pub async fn fetch_server_policy(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<Option<RetentionPolicy>, sqlx::Error> {
+    let entry = sqlx::query!(
+        "SELECT maxAgeDays, maxEntries FROM RetentionPolicy WHERE serverId = ?",
+        server_id
+    )
+    .fetch_optional(database)
+    .await?;
+
+    Ok(entry.map(|entry| RetentionPolicy {
+        max_age_days: entry.maxAgeDays,
+        max_entries: entry.maxEntries,
+    }))
+}
+
+/// Stores the retention policy of a server, replacing any previous one.
+pub async fn set_server_policy(
+    database: &SqlitePool,
+    server_id: i64,
+    policy: RetentionPolicy,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO RetentionPolicy (serverId, maxAgeDays, maxEntries) VALUES (?, ?, ?)",
+        server_id,
+        policy.max_age_days,
+        policy.max_entries
+    )
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the retention policy of a server so the global policy applies again.
+///
+/// # Returns
+/// * `Result<bool, sqlx::Error>` - `false` if the server had no policy
+pub async fn clear_server_policy(
+    database: &SqlitePool,
+    server_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM RetentionPolicy WHERE serverId = ?", server_id)
+        .execute(database)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// An expired ProfilePicture or ServerPicture row; `entity_id` is the user or server ID.
+struct ExpiredPicture {
+    entity_id: i64,
+    checksum: Option<String>,
+    changed_at: Option<i64>,
+    link: Option<String>,
+}
+
+/// An expired UsernameChange row.
+struct ExpiredUsername {
+    user_id: i64,
+    changed_at: Option<i64>,
+    username: Option<String>,
+}
+
+/// Rows a prune run removes.
+#[derive(Default)]
+struct PrunePlan {
+    server_pictures: Vec<ExpiredPicture>,
+    profile_pictures: Vec<ExpiredPicture>,
+    usernames: Vec<ExpiredUsername>,
+}
+
+impl PrunePlan {
+    /// Checksum and link of every picture row in the plan.
+    fn pictures(&self) -> Vec<(String, Option<String>)> {
+        self.server_pictures
+            .iter()
+            .chain(&self.profile_pictures)
+            .filter_map(|picture| Some((picture.checksum.clone()?, picture.link.clone())))
+            .collect()
+    }
+}
+
+/// Deletes history that falls outside of the applicable retention policies.
+///
+/// Server icons follow their server's policy, falling back to `global`. A user's
+/// history follows the most lenient policy of all servers they are tracked in; users
+/// not linked to any server, or linked to a server without its own policy, fall
+/// back to `global`. Archived images are queued for deletion in the same transaction
+/// once no row references their checksum anymore, so images the store fails to delete
+/// are retried by [`image_deletion::retry_pending`].
+///
+/// With `dry_run` set, the expired rows are only selected, so a preview never
+/// blocks the update passes with a write lock.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `global` - Policy for entities without a server specific policy
+/// * `scope` - Which users and servers to prune
+/// * `dry_run` - Only report what would be removed
+///
+/// # Returns
+/// * `Result<PruneReport, sqlx::Error>` - Counts of removed rows and images
+pub async fn prune(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    global: RetentionPolicy,
+    scope: PruneScope,
+    dry_run: bool,
+) -> Result<PruneReport, sqlx::Error> {
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+
+    let plan = plan_prune(database, global, scope, timestamp).await?;
+
+    if dry_run {
+        let mut connection = database.acquire().await?;
+        let (images, _) = unreferenced_images(&mut connection, &plan.pictures(), true).await?;
+
+        return Ok(PruneReport {
+            profile_pictures: plan.profile_pictures.len() as u64,
+            usernames: plan.usernames.len() as u64,
+            server_pictures: plan.server_pictures.len() as u64,
+            images,
+            failed_image_deletions: 0,
+        });
+    }
+
+    let mut transaction = database.begin().await?;
+    let mut report = PruneReport::default();
+
+    for entry in &plan.server_pictures {
+        report.server_pictures += sqlx::query!(
+            "DELETE FROM ServerPicture WHERE checksum = ? AND serverId = ? AND changedAt = ?",
+            entry.checksum,
+            entry.entity_id,
+            entry.changed_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for entry in &plan.profile_pictures {
+        report.profile_pictures += sqlx::query!(
+            "DELETE FROM ProfilePicture WHERE checksum = ? AND userId = ? AND changedAt = ?",
+            entry.checksum,
+            entry.entity_id,
+            entry.changed_at
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    for entry in &plan.usernames {
+        report.usernames += sqlx::query!(
+            "DELETE FROM UsernameChange WHERE userId = ? AND changedAt = ? AND username = ?",
+            entry.user_id,
+            entry.changed_at,
+            entry.username
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    }
+
+    let (images, unreferenced_links) =
+        unreferenced_images(&mut transaction, &plan.pictures(), false).await?;
+    report.images = images;
+
+    let queued = image_deletion::queue(&mut transaction, &unreferenced_links, timestamp).await?;
+    transaction.commit().await?;
+
+    report.failed_image_deletions =
+        image_deletion::delete_queued(database, image_store, &queued.queued)
+            .await?
+            .failed;
+
+    Ok(report)
+}
+
+/// Selects the rows that fall outside of the applicable retention policies.
+async fn plan_prune(
+    database: &SqlitePool,
+    global: RetentionPolicy,
+    scope: PruneScope,
+    timestamp: i64,
+) -> Result<PrunePlan, sqlx::Error> {
+    let server_policies: HashMap<i64, RetentionPolicy> =
+        sqlx::query!("SELECT serverId, maxAgeDays, maxEntries FROM RetentionPolicy")
+            .fetch_all(database)
+            .await?
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.serverId,
+                    RetentionPolicy {
+                        max_age_days: entry.maxAgeDays,
+                        max_entries: entry.maxEntries,
+                    },
+                )
+            })
+            .collect();
+
+    let mut plan = PrunePlan::default();
+
+    // Server icons
+    let server_ids = match scope {
+        PruneScope::All => {
+            sqlx::query_scalar!("SELECT serverId FROM Server")
+                .fetch_all(database)
+                .await?
+        }
+        PruneScope::Server(server_id) => vec![server_id],
+    };
+
+    for server_id in server_ids {
+        let policy = server_policies.get(&server_id).copied().unwrap_or(global);
+        if policy.is_unlimited() {
+            continue;
+        }
+
+        let entries = sqlx::query!(
+            "SELECT checksum, changedAt, link FROM ServerPicture WHERE serverId = ?",
+            server_id
+        )
+        .fetch_all(database)
+        .await?;
+
+        let changed_at: Vec<i64> = entries.iter().map(|e| e.changedAt.unwrap_or(0)).collect();
+
+        for index in policy.expired_entries(&changed_at, timestamp) {
+            let entry = &entries[index];
+            plan.server_pictures.push(ExpiredPicture {
+                entity_id: server_id,
+                checksum: entry.checksum.clone(),
+                changed_at: entry.changedAt,
+                link: entry.link.clone(),
+            });
+        }
+    }
+
+    // User history
+    let user_ids = match scope {
+        PruneScope::All => {
+            sqlx::query_scalar!("SELECT discordId FROM User")
+                .fetch_all(database)
+                .await?
+        }
+        PruneScope::Server(server_id) => sqlx::query_scalar!(
+            "SELECT userId FROM TrackedUserServer WHERE serverId = ?",
+            server_id
+        )
+        .fetch_all(database)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<i64>>(),
+    };
+
+    for user_id in user_ids {
+        let policy = user_policy(database, user_id, &server_policies, global).await?;
+        if policy.is_unlimited() {
+            continue;
+        }
+
+        let pictures = sqlx::query!(
+            "SELECT checksum, changedAt, link FROM ProfilePicture WHERE userId = ?",
+            user_id
+        )
+        .fetch_all(database)
+        .await?;
+
+        let changed_at: Vec<i64> = pictures.iter().map(|e| e.changedAt.unwrap_or(0)).collect();
+
+        for index in policy.expired_entries(&changed_at, timestamp) {
+            let entry = &pictures[index];
+            plan.profile_pictures.push(ExpiredPicture {
+                entity_id: user_id,
+                checksum: entry.checksum.clone(),
+                changed_at: entry.changedAt,
+                link: entry.link.clone(),
+            });
+        }
+
+        let usernames = sqlx::query!(
+            "SELECT username, changedAt FROM UsernameChange WHERE userId = ?",
+            user_id
+        )
+        .fetch_all(database)
+        .await?;
+
+        let changed_at: Vec<i64> = usernames.iter().map(|e| e.changedAt.unwrap_or(0)).collect();
+
+        for index in policy.expired_entries(&changed_at, timestamp) {
+            let entry = &usernames[index];
+            plan.usernames.push(ExpiredUsername {
+                user_id,
+                changed_at: entry.changedAt,
+                username: entry.username.clone(),
+            });
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Deletes users whose /removemonitor grace period has ended.
+///
+/// Their profile pictures, usernames and tracking servers are removed through the
+/// foreign key cascade; archived images no longer referenced by any row are
+/// queued for deletion from the image store.
+///
+/// # Arguments
+/// * `database` - SQLite connection pool
+/// * `image_store` - Backend holding the archived images
+/// * `grace_days` - Days a removed user is kept before being purged
+///
+/// # Returns
+/// * `Result<PruneReport, sqlx::Error>` - Counts of removed rows and images
+pub async fn purge_removed_users(
+    database: &SqlitePool,
+    image_store: &dyn ImageStore,
+    grace_days: i64,
+) -> Result<PruneReport, sqlx::Error> {
+    let now = SystemTime::now();
+    let dt: DateTime<Utc> = now.into();
+    let timestamp = dt.timestamp();
+    let cutoff = timestamp - grace_days * SECONDS_PER_DAY;
+
+    let mut transaction = database.begin().await?;
+    let mut report = PruneReport::default();
+
+    let pictures = sqlx::query!(
+        "SELECT checksum, link FROM ProfilePicture WHERE userId IN (SELECT discordId FROM User WHERE removedAt < ?)",
+        cutoff
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    report.profile_pictures = pictures.len() as u64;
+
+    report.usernames = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM UsernameChange WHERE userId IN (SELECT discordId FROM User WHERE removedAt < ?)",
+        cutoff
+    )
+    .fetch_one(&mut *transaction)
+    .await? as u64;
+
+    sqlx::query!("DELETE FROM User WHERE removedAt < ?", cutoff)
+        .execute(&mut *transaction)
+        .await?;
+
+    let deleted_images: Vec<(String, Option<String>)> = pictures
+        .into_iter()
+        .filter_map(|picture| Some((picture.checksum?, picture.link)))
+        .collect();
+
+    let (images, unreferenced_links) =
+        unreferenced_images(&mut transaction, &deleted_images, false).await?;
+    report.images = images;
+
+    let queued = image_deletion::queue(&mut transaction, &unreferenced_links, timestamp).await?;
+    transaction.commit().await?;
+
+    report.failed_image_deletions =
+        image_deletion::delete_queued(database, image_store, &queued.queued)
+            .await?
+            .failed;
+
+    Ok(report)
+}
+
+/// Finds the images among the removed picture rows that no remaining row references.
+///
+/// # Arguments
+/// * `connection` - Connection or transaction to count the references with
+/// * `removed` - Checksum and link of every removed picture row
+/// * `rows_still_present` - The removed rows are only planned and still counted as references
+///
+/// # Returns
+/// * `Result<(u64, Vec<String>), sqlx::Error>` - Number of unreferenced checksums and their links
+async fn unreferenced_images(
+    connection: &mut SqliteConnection,
+    removed: &[(String, Option<String>)],
+    rows_still_present: bool,
+) -> Result<(u64, Vec<String>), sqlx::Error> {
+    // Removed rows and distinct links per checksum
+    let mut by_checksum: BTreeMap<&str, (i64, Vec<&str>)> = BTreeMap::new();
+    for (checksum, link) in removed {
+        let (rows, links) = by_checksum.entry(checksum).or_default();
+        *rows += 1;
+        if let Some(link) = link {
+            if !links.contains(&link.as_str()) {
+                links.push(link);
+            }
+        }
+    }
+
+    let mut count = 0;
+    let mut unreferenced_links = Vec::new();
+
+    for (checksum, (rows, links)) in by_checksum {
+        let references = sqlx::query_scalar!(
+            "SELECT (SELECT COUNT(*) FROM ProfilePicture WHERE checksum = ?1) + (SELECT COUNT(*) FROM ServerPicture WHERE checksum = ?1)",
+            checksum
+        )
+        .fetch_one(&mut *connection)
+        .await?;
+
+        let remaining = if rows_still_present {
+            references - rows
+        } else {
+            references
+        };
+
+        if remaining <= 0 {
+            count += 1;
+            unreferenced_links.extend(links.into_iter().map(String::from));
+        }
+    }
+
+    Ok((count, unreferenced_links))
+}
+
+/// Resolves the policy for a user from the servers they are tracked in.
+async fn user_policy(
+    database: &SqlitePool,
+    user_id: i64,
+    server_policies: &HashMap<i64, RetentionPolicy>,
+    global: RetentionPolicy,
+) -> Result<RetentionPolicy, sqlx::Error> {
+    let server_ids = sqlx::query_scalar!(
+        "SELECT serverId FROM TrackedUserServer WHERE userId = ?",
+        user_id
+    )
+    .fetch_all(database)
+    .await?;
+
+    let mut policies = server_ids
+        .into_iter()
+        .flatten()
+        .map(|server_id| server_policies.get(&server_id).copied().unwrap_or(global));
+
+    let Some(first) = policies.next() else {
+        return Ok(global);
+    };
+
+    Ok(policies.fold(first, |policy, other| policy.most_lenient(&other)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 100 * SECONDS_PER_DAY;
+
+    fn days_ago(days: i64) -> i64 {
+        NOW - days * SECONDS_PER_DAY
+    }
+
+    #[test]
+    fn test_unlimited_policy_keeps_everything() {
+        let policy = RetentionPolicy::default();
+        assert!(policy.is_unlimited());
+        assert!(policy
+            .expired_entries(&[days_ago(300), days_ago(200), days_ago(1)], NOW)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_max_entries_keeps_newest() {
+        let policy = RetentionPolicy {
+            max_age_days: None,
+            max_entries: Some(2),
+        };
+        // Unordered input, indices 1 and 2 are the oldest
+        let changed_at = [days_ago(5), days_ago(20), days_ago(10), days_ago(1)];
+        assert_eq!(policy.expired_entries(&changed_at, NOW), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_max_age_removes_old_entries() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            max_entries: None,
+        };
+        let changed_at = [days_ago(1), days_ago(29), days_ago(31), days_ago(90)];
+        assert_eq!(policy.expired_entries(&changed_at, NOW), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_current_entry_is_always_kept() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            max_entries: Some(0),
+        };
+        let changed_at = [days_ago(90), days_ago(60)];
+        // Index 1 is the newest and survives although it is older than 30 days
+        assert_eq!(policy.expired_entries(&changed_at, NOW), vec![0]);
+    }
+
+    #[test]
+    fn test_combined_limits() {
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            max_entries: Some(3),
+        };
+        let changed_at = [
+            days_ago(1),
+            days_ago(2),
+            days_ago(3),
+            days_ago(4),
+            days_ago(40),
+        ];
+        assert_eq!(policy.expired_entries(&changed_at, NOW), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_most_lenient_prefers_disabled_limits() {
+        let strict = RetentionPolicy {
+            max_age_days: Some(30),
+            max_entries: Some(5),
+        };
+        let lenient = RetentionPolicy {
+            max_age_days: None,
+            max_entries: Some(10),
+        };
+        assert_eq!(
+            strict.most_lenient(&lenient),
+            RetentionPolicy {
+                max_age_days: None,
+                max_entries: Some(10),
+            }
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// ABOUTME: SlashCommand trait and registry so each command is declared in one place
+// ABOUTME: Dispatches command interactions by name, checks role requirements, and reports failures
+use std::time::Instant;
+
+use serenity::all::{CommandInteraction, Context, CreateCommand};
+use serenity::async_trait;
+use tracing::{info, info_span, Instrument};
+
+use crate::error::{report_command_error, BotError};
+use crate::util::metrics::metrics;
+use crate::util::permissions;
+use crate::util::state::BotState;
+
+/// A slash command that can be registered with Discord and run.
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    /// Name of the command without the leading slash, must match [`SlashCommand::register`].
+    fn name(&self) -> &'static str;
+
+    /// Builds the command definition sent to Discord.
+    fn register(&self) -> CreateCommand;
+
+    /// Runs the command for an interaction.
+    async fn run(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError>;
+}
+
+/// All slash commands of the bot, looked up by name.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn SlashCommand>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command.
+    pub fn with_command(mut self, command: impl SlashCommand + 'static) -> Self {
+        self.commands.push(Box::new(command));
+        self
+    }
+
+    /// Looks up a command by name.
+    pub fn get(&self, name: &str) -> Option<&dyn SlashCommand> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .map(|command| command.as_ref())
+    }
+
+    /// Definitions of every registered command, for `Command::set_global_commands`.
+    pub fn definitions(&self) -> Vec<CreateCommand> {
+        self.commands
+            .iter()
+            .map(|command| command.register())
+            .collect()
+    }
+
+    /// Runs the command an interaction refers to.
+    ///
+    /// Members lacking a role required by `/config permissions` are refused. Errors of
+    /// unknown or failing commands are logged and answered with an ephemeral reply
+    /// instead of leaving the interaction unanswered. Each invocation runs in a
+    /// `command` span carrying the command name, user and server.
+    ///
+    /// # Arguments
+    /// * `ctx` - The Serenity context
+    /// * `interaction` - The command interaction
+    /// * `state` - Shared services handed to the command
+    pub async fn dispatch(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) {
+        let span = info_span!(
+            "command",
+            name = %interaction.data.name,
+            user_id = %interaction.user.id,
+            guild_id = ?interaction.guild_id.map(|id| id.get()),
+        );
+
+        async {
+            let started = Instant::now();
+            let name = interaction.data.name.as_str();
+            let result = self.execute(ctx, interaction, state).await;
+
+            metrics()
+                .command_invocations
+                .with_label_values(&[name])
+                .inc();
+            if let Err(why) = &result {
+                metrics()
+                    .command_errors
+                    .with_label_values(&[name, why.kind()])
+                    .inc();
+            }
+
+            info!(
+                outcome = if result.is_ok() { "ok" } else { "error" },
+                duration_ms = started.elapsed().as_millis() as u64,
+                "Command finished"
+            );
+
+            if let Err(why) = result {
+                report_command_error(ctx, interaction, &why).await;
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &BotState,
+    ) -> Result<(), BotError> {
+        let name = interaction.data.name.as_str();
+        let command = self
+            .get(name)
+            .ok_or_else(|| BotError::UnknownCommand(name.to_string()))?;
+
+        if !permissions::is_permitted(&state.database, interaction).await? {
+            return Err(BotError::Permission(format!(
+                "You don't have a role required to use /{} in this server.",
+                name
+            )));
+        }
+
+        command.run(ctx, interaction, state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyCommand(&'static str);
+
+    #[async_trait]
+    impl SlashCommand for DummyCommand {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn register(&self) -> CreateCommand {
+            CreateCommand::new(self.0).description("Dummy command.")
+        }
+
+        async fn run(
+            &self,
+            _ctx: &Context,
+            _interaction: &CommandInteraction,
+            _state: &BotState,
+        ) -> Result<(), BotError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_get_registered_command() {
+        let registry = CommandRegistry::new()
+            .with_command(DummyCommand("ping"))
+            .with_command(DummyCommand("stats"));
+
+        assert_eq!(
+            registry.get("stats").map(|command| command.name()),
+            Some("stats")
+        );
+        assert!(registry.get("unknown").is_none());
+    }
+
+    #[test]
+    fn test_definitions_cover_all_commands() {
+        let registry = CommandRegistry::new()
+            .with_command(DummyCommand("ping"))
+            .with_command(DummyCommand("stats"));
+
+        assert_eq!(registry.definitions().len(), 2);
+    }
+}
@@ -0,0 +1,20 @@
+// ABOUTME: Shared services and settings handed to commands and component handlers
+// ABOUTME: Bundles the database pool, image store, CDN client, health state and configured policies in one cheaply cloneable struct
+use std::sync::Arc;
+
+use sqlx::SqlitePool;
+
+use crate::util::discord::CdnClient;
+use crate::util::health::Health;
+use crate::util::retention::RetentionPolicy;
+use crate::util::storage::ImageStore;
+
+#[derive(Clone)]
+pub struct BotState {
+    pub database: Arc<SqlitePool>,
+    pub image_store: Arc<dyn ImageStore>,
+    pub cdn: Arc<CdnClient>,
+    pub retention: RetentionPolicy,
+    pub removal_grace_days: i64,
+    pub health: Arc<Health>,
+}
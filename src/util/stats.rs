@@ -0,0 +1,476 @@
+// ABOUTME: Statistics over the full picture history of a monitored user or server
+// ABOUTME: Computes interval figures, monthly counts, longest-kept and reused images, and time-of-day patterns
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+use crate::util::history::PictureRecord;
+
+/// Months shown in the per-month histogram of the embed.
+const HISTOGRAM_MONTHS: usize = 12;
+
+/// Reused images listed in the embed.
+const MAX_REUSED: usize = 3;
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Figures over the seconds between consecutive records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntervalStats {
+    pub median: i64,
+    pub average: i64,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// The image that stayed in place the longest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeptImage {
+    pub checksum: String,
+    pub link: Option<String>,
+    /// Unix timestamp of when the image was put in place.
+    pub since: i64,
+    /// Seconds until it was replaced, or until now for the current image.
+    pub kept_secs: i64,
+}
+
+/// An image that was put in place more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReusedImage {
+    pub checksum: String,
+    pub link: Option<String>,
+    pub times: usize,
+}
+
+/// Statistics over all recorded pictures of one user or server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryStats {
+    /// Number of records, including the first image seen.
+    pub records: usize,
+    /// `None` with fewer than two records.
+    pub intervals: Option<IntervalStats>,
+    /// Seconds between consecutive records, shortest first.
+    pub interval_secs: Vec<i64>,
+    /// Records per calendar month (UTC) from the first to the last record, as `("YYYY-MM", count)`.
+    pub per_month: Vec<(String, usize)>,
+    pub longest_kept: KeptImage,
+    /// Images seen more than once, most reused first.
+    pub most_reused: Vec<ReusedImage>,
+    /// Records per hour of the day (UTC).
+    pub by_hour: [usize; 24],
+    /// Records per day of the week (UTC), starting on Monday.
+    pub by_weekday: [usize; 7],
+    /// Records per day of the week and hour of the day (UTC).
+    pub by_weekday_hour: [[usize; 24]; 7],
+}
+
+impl HistoryStats {
+    /// Computes the statistics of a picture history.
+    ///
+    /// # Arguments
+    /// * `records` - The recorded pictures, in any order
+    /// * `now` - Unix timestamp up to which the current picture counts as kept
+    ///
+    /// # Returns
+    /// * `Option<HistoryStats>` - `None` if there are no records
+    pub fn compute(records: &[PictureRecord], now: i64) -> Option<Self> {
+        let mut records: Vec<&PictureRecord> = records.iter().collect();
+        records.sort_by_key(|record| record.changed_at);
+
+        let first = records.first()?;
+        let last = records.last()?;
+
+        let mut intervals: Vec<i64> = records
+            .windows(2)
+            .map(|pair| pair[1].changed_at - pair[0].changed_at)
+            .collect();
+        intervals.sort_unstable();
+
+        let longest_kept = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let until = records.get(index + 1).map_or(now, |next| next.changed_at);
+                KeptImage {
+                    checksum: record.checksum.clone(),
+                    link: record.link.clone(),
+                    since: record.changed_at,
+                    kept_secs: (until - record.changed_at).max(0),
+                }
+            })
+            .max_by_key(|kept| kept.kept_secs)?;
+
+        let mut by_hour = [0; 24];
+        let mut by_weekday = [0; 7];
+        let mut by_weekday_hour = [[0; 24]; 7];
+        let mut months: HashMap<i32, usize> = HashMap::new();
+        let mut uses: HashMap<&str, ReusedImage> = HashMap::new();
+
+        for record in &records {
+            if let Some(dt) = DateTime::<Utc>::from_timestamp(record.changed_at, 0) {
+                let (weekday, hour) = (
+                    dt.weekday().num_days_from_monday() as usize,
+                    dt.hour() as usize,
+                );
+                by_hour[hour] += 1;
+                by_weekday[weekday] += 1;
+                by_weekday_hour[weekday][hour] += 1;
+                *months.entry(month_index(&dt)).or_default() += 1;
+            }
+
+            // Records are sorted, so the first use is inserted first
+            let image = uses
+                .entry(record.checksum.as_str())
+                .or_insert_with(|| ReusedImage {
+                    checksum: record.checksum.clone(),
+                    link: None,
+                    times: 0,
+                });
+            image.times += 1;
+            if image.link.is_none() {
+                image.link = record.link.clone();
+            }
+        }
+
+        let per_month = match (
+            DateTime::<Utc>::from_timestamp(first.changed_at, 0),
+            DateTime::<Utc>::from_timestamp(last.changed_at, 0),
+        ) {
+            (Some(first), Some(last)) => (month_index(&first)..=month_index(&last))
+                .map(|month| {
+                    (
+                        format!("{}-{:02}", month.div_euclid(12), month.rem_euclid(12) + 1),
+                        months.get(&month).copied().unwrap_or(0),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut most_reused: Vec<ReusedImage> =
+            uses.into_values().filter(|image| image.times > 1).collect();
+        most_reused.sort_by(|a, b| b.times.cmp(&a.times).then(a.checksum.cmp(&b.checksum)));
+
+        Some(HistoryStats {
+            records: records.len(),
+            intervals: interval_stats(&intervals),
+            interval_secs: intervals,
+            per_month,
+            longest_kept,
+            most_reused,
+            by_hour,
+            by_weekday,
+            by_weekday_hour,
+        })
+    }
+
+    /// Embed fields describing the statistics, as `(name, value, inline)`.
+    pub fn embed_fields(&self) -> Vec<(&'static str, String, bool)> {
+        // The first record is where monitoring started, every later one is a change
+        let mut fields = vec![(
+            "Changes since beginning of Monitoring",
+            self.interval_secs.len().to_string(),
+            false,
+        )];
+
+        match self.intervals {
+            Some(intervals) => {
+                fields.push(("Median interval", format_interval(intervals.median), true));
+                fields.push(("Average interval", format_interval(intervals.average), true));
+                fields.push((
+                    "Shortest / longest",
+                    format!(
+                        "{} / {}",
+                        format_interval(intervals.min),
+                        format_interval(intervals.max)
+                    ),
+                    true,
+                ));
+            }
+            None => fields.push((
+                "Intervals",
+                "Not enough changes recorded yet.".to_string(),
+                false,
+            )),
+        }
+
+        fields.push((
+            "Longest kept",
+            format!(
+                "{} for {}, since <t:{}:D>",
+                image_label(self.longest_kept.link.as_deref()),
+                format_interval(self.longest_kept.kept_secs),
+                self.longest_kept.since
+            ),
+            false,
+        ));
+
+        let reused = if self.most_reused.is_empty() {
+            "No image was used twice.".to_string()
+        } else {
+            self.most_reused
+                .iter()
+                .take(MAX_REUSED)
+                .map(|image| format!("{} × {}", image_label(image.link.as_deref()), image.times))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        fields.push(("Most reused", reused, false));
+
+        if let Some((hour, count)) = busiest(&self.by_hour) {
+            fields.push((
+                "Busiest hour (UTC)",
+                format!(
+                    "{:02}:00–{:02}:00 ({})",
+                    hour,
+                    (hour + 1) % 24,
+                    plural(count)
+                ),
+                true,
+            ));
+        }
+        if let Some((day, count)) = busiest(&self.by_weekday) {
+            fields.push((
+                "Busiest day (UTC)",
+                format!("{} ({})", WEEKDAYS[day], plural(count)),
+                true,
+            ));
+        }
+
+        fields.push(("Changes per month", self.month_histogram(), false));
+
+        fields
+    }
+
+    /// The last months of `per_month` as a text bar chart in a code block.
+    fn month_histogram(&self) -> String {
+        let months = &self.per_month[self.per_month.len().saturating_sub(HISTOGRAM_MONTHS)..];
+        let max = months.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let width = max.to_string().len();
+
+        let lines: Vec<String> = months
+            .iter()
+            .map(|(month, count)| {
+                // Scale bars to at most 20 blocks
+                let bar = if max == 0 {
+                    0
+                } else {
+                    (count * 20).div_ceil(max)
+                };
+                format!("{} {:>width$} {}", month, count, "█".repeat(bar))
+            })
+            .collect();
+
+        format!("```\n{}\n```", lines.join("\n"))
+    }
+}
+
+/// Median, average, minimum and maximum of sorted intervals.
+fn interval_stats(sorted: &[i64]) -> Option<IntervalStats> {
+    let (&min, &max) = (sorted.first()?, sorted.last()?);
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2
+    } else {
+        sorted[middle]
+    };
+
+    Some(IntervalStats {
+        median,
+        average: sorted.iter().sum::<i64>() / sorted.len() as i64,
+        min,
+        max,
+    })
+}
+
+/// Months since year 0, so consecutive months are consecutive numbers.
+fn month_index(dt: &DateTime<Utc>) -> i32 {
+    dt.year() * 12 + dt.month0() as i32
+}
+
+/// Index and count of the most frequent slot, `None` if all are empty.
+fn busiest(counts: &[usize]) -> Option<(usize, usize)> {
+    counts
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+}
+
+fn plural(count: usize) -> String {
+    if count == 1 {
+        "1 change".to_string()
+    } else {
+        format!("{} changes", count)
+    }
+}
+
+fn image_label(link: Option<&str>) -> String {
+    match link {
+        Some(link) => format!("[Picture]({})", link),
+        None => "Picture (not archived)".to_string(),
+    }
+}
+
+/// Formats seconds as `3d 4h`, `5h 6m` or `7m`.
+pub fn format_interval(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+
+    if seconds >= 86400 {
+        format!("{}d {}h", seconds / 86400, seconds % 86400 / 3600)
+    } else if seconds >= 3600 {
+        format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+    } else {
+        format!("{}m", seconds / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86400;
+    /// Monday, 2024-01-01 00:00:00 UTC
+    const MONDAY: i64 = 1704067200;
+
+    fn record(checksum: &str, changed_at: i64) -> PictureRecord {
+        PictureRecord {
+            checksum: checksum.to_string(),
+            changed_at,
+            link: Some(format!("https://i.ibb.co/{}.png", checksum)),
+        }
+    }
+
+    #[test]
+    fn test_no_records() {
+        assert_eq!(HistoryStats::compute(&[], MONDAY), None);
+    }
+
+    #[test]
+    fn test_intervals_ignore_record_order() {
+        let records = [
+            record("c", MONDAY + 10 * DAY),
+            record("a", MONDAY),
+            record("b", MONDAY + DAY),
+            record("d", MONDAY + 13 * DAY),
+        ];
+
+        let stats = HistoryStats::compute(&records, MONDAY + 20 * DAY).unwrap();
+
+        assert_eq!(stats.records, 4);
+        assert_eq!(
+            stats.embed_fields()[0].1,
+            "3",
+            "The first record is no change"
+        );
+        assert_eq!(
+            stats.intervals,
+            Some(IntervalStats {
+                median: 3 * DAY,
+                average: 13 * DAY / 3,
+                min: DAY,
+                max: 9 * DAY,
+            })
+        );
+    }
+
+    #[test]
+    fn test_even_median() {
+        let records = [
+            record("a", MONDAY),
+            record("b", MONDAY + DAY),
+            record("c", MONDAY + 4 * DAY),
+        ];
+
+        let stats = HistoryStats::compute(&records, MONDAY).unwrap();
+
+        assert_eq!(stats.intervals.unwrap().median, 2 * DAY);
+    }
+
+    #[test]
+    fn test_single_record_is_kept_until_now() {
+        let stats = HistoryStats::compute(&[record("a", MONDAY)], MONDAY + 5 * DAY).unwrap();
+
+        assert_eq!(stats.intervals, None);
+        assert_eq!(stats.longest_kept.checksum, "a");
+        assert_eq!(stats.longest_kept.kept_secs, 5 * DAY);
+    }
+
+    #[test]
+    fn test_longest_kept_and_reused() {
+        let records = [
+            record("a", MONDAY),
+            record("b", MONDAY + DAY),
+            record("a", MONDAY + 20 * DAY),
+            record("b", MONDAY + 21 * DAY),
+            record("a", MONDAY + 22 * DAY),
+        ];
+
+        let stats = HistoryStats::compute(&records, MONDAY + 23 * DAY).unwrap();
+
+        assert_eq!(stats.longest_kept.checksum, "b");
+        assert_eq!(stats.longest_kept.since, MONDAY + DAY);
+        assert_eq!(stats.longest_kept.kept_secs, 19 * DAY);
+
+        let reused: Vec<(&str, usize)> = stats
+            .most_reused
+            .iter()
+            .map(|image| (image.checksum.as_str(), image.times))
+            .collect();
+        assert_eq!(reused, vec![("a", 3), ("b", 2)]);
+    }
+
+    #[test]
+    fn test_months_include_gaps() {
+        let records = [
+            record("a", MONDAY),
+            record("b", MONDAY + DAY),
+            record("c", MONDAY + 70 * DAY),
+        ];
+
+        let stats = HistoryStats::compute(&records, MONDAY).unwrap();
+
+        assert_eq!(
+            stats.per_month,
+            vec![
+                ("2024-01".to_string(), 2),
+                ("2024-02".to_string(), 0),
+                ("2024-03".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_patterns() {
+        let records = [
+            record("a", MONDAY + 14 * 3600),
+            record("b", MONDAY + 7 * DAY + 14 * 3600),
+            record("c", MONDAY + 5 * DAY + 9 * 3600),
+        ];
+
+        let stats = HistoryStats::compute(&records, MONDAY).unwrap();
+
+        assert_eq!(stats.by_hour[14], 2);
+        assert_eq!(stats.by_hour[9], 1);
+        assert_eq!(stats.by_weekday, [2, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(stats.by_weekday_hour[0][14], 2);
+        assert_eq!(stats.by_weekday_hour[5][9], 1);
+        assert_eq!(busiest(&stats.by_weekday), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_format_interval() {
+        assert_eq!(format_interval(59), "0m");
+        assert_eq!(format_interval(2 * 3600 + 5 * 60), "2h 5m");
+        assert_eq!(format_interval(3 * DAY + 4 * 3600), "3d 4h");
+    }
+}
@@ -0,0 +1,142 @@
+// ABOUTME: Abstraction over the backend that archives profile pictures and server icons
+// ABOUTME: Defines the ImageStore trait and its ImgBB implementation
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use serenity::async_trait;
+
+use crate::util::external::imgbb::{self, ImgBBError};
+
+// Custom error type for image store operations
+#[derive(Debug)]
+pub enum StorageError {
+    ImgBB(ImgBBError),
+    Request(reqwest::Error),
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::ImgBB(err) => write!(f, "ImgBB error: {}", err),
+            StorageError::Request(err) => write!(f, "Request error: {}", err),
+            StorageError::Unsupported(operation) => {
+                write!(
+                    f,
+                    "Operation not supported by this image store: {}",
+                    operation
+                )
+            }
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+impl From<ImgBBError> for StorageError {
+    fn from(err: ImgBBError) -> Self {
+        StorageError::ImgBB(err)
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(err: reqwest::Error) -> Self {
+        StorageError::Request(err)
+    }
+}
+
+/// An archived image as handed out by an image store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredImage {
+    /// Link under which the image is shown
+    pub link: String,
+    /// Link that deletes the image, for backends that hand one out on upload
+    pub delete_url: Option<String>,
+}
+
+/// A backend that archives images and hands out links to them.
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    /// Archives an image and returns the links under which it is stored.
+    async fn upload(
+        &self,
+        image_data: Vec<u8>,
+        filename: String,
+    ) -> Result<StoredImage, StorageError>;
+
+    /// Downloads the bytes of a previously archived image.
+    async fn fetch(&self, link: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Removes a previously archived image from the backend.
+    async fn delete(&self, image: &StoredImage) -> Result<(), StorageError>;
+}
+
+/// How long an upload to ImgBB may take before it counts as failed.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stores images on ImgBB using the configured API key.
+pub struct ImgBBStore {
+    api_key: String,
+    upload_url: String,
+    timeout: Duration,
+}
+
+impl ImgBBStore {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        ImgBBStore {
+            api_key: api_key.into(),
+            upload_url: imgbb::DEFAULT_UPLOAD_URL.to_string(),
+            timeout: UPLOAD_TIMEOUT,
+        }
+    }
+
+    /// Uploads to another endpoint speaking the ImgBB API, such as a local stand-in.
+    pub fn with_upload_url(mut self, upload_url: impl Into<String>) -> Self {
+        self.upload_url = upload_url.into();
+        self
+    }
+
+    /// Overrides how long an upload may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl ImageStore for ImgBBStore {
+    async fn upload(
+        &self,
+        image_data: Vec<u8>,
+        filename: String,
+    ) -> Result<StoredImage, StorageError> {
+        let data = imgbb::upload_image(
+            image_data,
+            filename,
+            &self.api_key,
+            &self.upload_url,
+            self.timeout,
+        )
+        .await?;
+
+        Ok(StoredImage {
+            link: data.url,
+            delete_url: data.delete_url,
+        })
+    }
+
+    async fn fetch(&self, link: &str) -> Result<Vec<u8>, StorageError> {
+        let response = reqwest::get(link).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn delete(&self, image: &StoredImage) -> Result<(), StorageError> {
+        // Images archived before delete links were recorded can only be removed by hand
+        let Some(delete_url) = &image.delete_url else {
+            return Err(StorageError::Unsupported("delete without a delete link"));
+        };
+
+        Ok(imgbb::delete_image(delete_url, self.timeout).await?)
+    }
+}
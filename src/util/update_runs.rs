@@ -0,0 +1,183 @@
+// ABOUTME: Records every update pass in the UpdateRun table and summarizes recent passes
+// ABOUTME: Feeds /botstatus with last and next runs, durations, failure rates, tracked entities and storage usage
+use sqlx::SqlitePool;
+
+/// Days of update pass history kept by the daily cleanup.
+pub const UPDATE_RUN_RETENTION_DAYS: i64 = 30;
+
+/// What started an update pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTrigger {
+    /// The periodic scheduler.
+    Schedule,
+    /// A command such as /monitor that checks new entities right away.
+    Command,
+}
+
+impl RunTrigger {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunTrigger::Schedule => "schedule",
+            RunTrigger::Command => "command",
+        }
+    }
+}
+
+/// Counts collected while an update pass runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunCounts {
+    pub checked: i64,
+    pub changes: i64,
+    pub failures: i64,
+}
+
+/// The most recent pass of one entity type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatestRun {
+    pub entity_type: String,
+    pub started_at: i64,
+    /// `None` while the pass is still running or if it never finished.
+    pub finished_at: Option<i64>,
+}
+
+/// Aggregated finished passes of one entity type within a time window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunStats {
+    pub entity_type: String,
+    pub runs: i64,
+    pub average_duration_secs: f64,
+    pub checked: i64,
+    pub failures: i64,
+}
+
+impl RunStats {
+    /// Share of checks that failed, between 0 and 1.
+    pub fn failure_rate(&self) -> f64 {
+        if self.checked == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.checked as f64
+        }
+    }
+}
+
+/// Records the start of an update pass.
+///
+/// # Returns
+/// * `Result<i64, sqlx::Error>` - ID of the new run, to pass to [`finish_run`]
+pub async fn start_run(
+    database: &SqlitePool,
+    entity_type: &str,
+    trigger: RunTrigger,
+    started_at: i64,
+) -> Result<i64, sqlx::Error> {
+    let trigger = trigger.as_str();
+
+    let result = sqlx::query!(
+        "INSERT INTO UpdateRun (entityType, triggeredBy, startedAt) VALUES (?, ?, ?)",
+        entity_type,
+        trigger,
+        started_at
+    )
+    .execute(database)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Records the end of an update pass together with its counts.
+pub async fn finish_run(
+    database: &SqlitePool,
+    run_id: i64,
+    finished_at: i64,
+    counts: RunCounts,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE UpdateRun SET finishedAt = ?, checked = ?, changes = ?, failures = ? WHERE id = ?",
+        finished_at,
+        counts.checked,
+        counts.changes,
+        counts.failures,
+        run_id
+    )
+    .execute(database)
+    .await?;
+
+    Ok(())
+}
+
+/// The latest pass of every entity type, ordered by entity type.
+pub async fn latest_runs(database: &SqlitePool) -> Result<Vec<LatestRun>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT entityType AS "entity_type!: String", startedAt AS "started_at!: i64", finishedAt AS "finished_at: i64"
+        FROM UpdateRun
+        WHERE id IN (SELECT MAX(id) FROM UpdateRun GROUP BY entityType)
+        ORDER BY entityType"#
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| LatestRun {
+            entity_type: row.entity_type,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        })
+        .collect())
+}
+
+/// Start time of the latest pass started by the scheduler.
+pub async fn last_scheduled_start(database: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT MAX(startedAt) AS "started_at: i64" FROM UpdateRun WHERE triggeredBy = 'schedule'"#
+    )
+    .fetch_one(database)
+    .await
+}
+
+/// Statistics of the passes that finished since a point in time, ordered by entity type.
+pub async fn run_stats_since(
+    database: &SqlitePool,
+    since: i64,
+) -> Result<Vec<RunStats>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT entityType AS "entity_type!: String",
+            COUNT(*) AS "runs!: i64",
+            AVG(finishedAt - startedAt) AS "average_duration: f64",
+            SUM(checked) AS "checked: i64",
+            SUM(failures) AS "failures: i64"
+        FROM UpdateRun
+        WHERE startedAt >= ? AND finishedAt IS NOT NULL
+        GROUP BY entityType
+        ORDER BY entityType"#,
+        since
+    )
+    .fetch_all(database)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| RunStats {
+            entity_type: row.entity_type,
+            runs: row.runs,
+            average_duration_secs: row.average_duration.unwrap_or(0.0),
+            checked: row.checked.unwrap_or(0),
+            failures: row.failures.unwrap_or(0),
+        })
+        .collect())
+}
+
+/// Deletes passes older than [`UPDATE_RUN_RETENTION_DAYS`].
+///
+/// # Returns
+/// * `Result<u64, sqlx::Error>` - Number of deleted runs
+pub async fn prune_runs(database: &SqlitePool, now: i64) -> Result<u64, sqlx::Error> {
+    let cutoff = now - UPDATE_RUN_RETENTION_DAYS * 24 * 60 * 60;
+
+    let result = sqlx::query!("DELETE FROM UpdateRun WHERE startedAt < ?", cutoff)
+        .execute(database)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,138 @@
+// ABOUTME: Integration tests for the per-entity check status of monitored users and servers
+// ABOUTME: Tests that failures are counted and backed off, successes reset them, and failing users are listed
+mod common;
+
+use common::create_test_db;
+use pfp_checker::util::check_status::{self, SERVER_STATUS, USER_STATUS};
+use sqlx::SqlitePool;
+
+/// Helper function to insert a monitored user added in a server
+async fn insert_tracked_user(pool: &SqlitePool, user_id: i64, server_id: i64) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        "INSERT INTO TrackedUserServer (userId, serverId) VALUES (?, ?)",
+        user_id,
+        server_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_new_entities_have_no_failures() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_tracked_user(&pool, 1, 100).await;
+    sqlx::query!("INSERT INTO Server (serverId, trackedSince) VALUES (100, 0)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let user = check_status::user_status(&pool, 1).await.unwrap().unwrap();
+    let server = check_status::server_status(&pool, 100)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(user.consecutive_failures, 0);
+    assert_eq!(server.consecutive_failures, 0);
+    assert_eq!(server.next_check_at, None);
+}
+
+#[tokio::test]
+async fn test_failures_are_backed_off() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_tracked_user(&pool, 1, 100).await;
+
+    let failures = check_status::record_failure(&pool, USER_STATUS, 1, 1000, "Unknown User")
+        .await
+        .unwrap();
+    assert_eq!(failures, 1);
+    let status = check_status::user_status(&pool, 1).await.unwrap().unwrap();
+    assert_eq!(
+        status.next_check_at, None,
+        "A single failure should be retried on the next pass"
+    );
+
+    let failures = check_status::record_failure(&pool, USER_STATUS, 1, 2000, "Unknown User")
+        .await
+        .unwrap();
+    assert_eq!(failures, 2);
+    let status = check_status::user_status(&pool, 1).await.unwrap().unwrap();
+    assert_eq!(
+        status.next_check_at,
+        Some(2000 + check_status::backoff_secs(2))
+    );
+    assert_eq!(status.last_error.as_deref(), Some("Unknown User"));
+}
+
+#[tokio::test]
+async fn test_success_resets_status() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    sqlx::query!("INSERT INTO Server (serverId, trackedSince) VALUES (100, 0)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    check_status::record_failure(&pool, SERVER_STATUS, 100, 1000, "Missing Access")
+        .await
+        .unwrap();
+    check_status::record_failure(&pool, SERVER_STATUS, 100, 2000, "Missing Access")
+        .await
+        .unwrap();
+
+    check_status::record_success(&pool, SERVER_STATUS, 100, 4000)
+        .await
+        .unwrap();
+
+    let status = check_status::server_status(&pool, 100)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status.consecutive_failures, 0);
+    assert_eq!(status.last_success_at, Some(4000));
+    assert_eq!(status.last_error, None);
+    assert_eq!(status.next_check_at, None);
+}
+
+#[tokio::test]
+async fn test_failing_users_in_server() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_tracked_user(&pool, 1, 100).await;
+    insert_tracked_user(&pool, 2, 100).await;
+    insert_tracked_user(&pool, 3, 200).await;
+
+    for now in [1000, 2000, 3000] {
+        for user_id in [1, 3] {
+            check_status::record_failure(&pool, USER_STATUS, user_id, now, "Unknown User")
+                .await
+                .unwrap();
+        }
+    }
+    check_status::record_failure(&pool, USER_STATUS, 2, 1000, "Unknown User")
+        .await
+        .unwrap();
+
+    let failing = check_status::failing_users_in_server(&pool, 100)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        failing.len(),
+        1,
+        "Only users of this server failing 3 times should be listed"
+    );
+    assert_eq!(failing[0].id, 1);
+    assert_eq!(failing[0].consecutive_failures, 3);
+    assert_eq!(failing[0].last_error.as_deref(), Some("Unknown User"));
+}
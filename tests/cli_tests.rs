@@ -0,0 +1,191 @@
+// ABOUTME: Integration tests for the maintenance subcommands of the pfp-checker binary
+// ABOUTME: Runs the built binary against temporary databases and inspects the result with SQL
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use sqlx::SqlitePool;
+use tempfile::TempDir;
+
+/// Runs the binary with `DATABASE_URL` pointing at the given file
+fn pfp_checker(database: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pfp-checker"))
+        .args(args)
+        .env("DATABASE_URL", database)
+        .env("RUST_LOG", "warn")
+        .output()
+        .expect("failed to run pfp-checker")
+}
+
+/// Helper function to create a path for a database that does not exist yet
+fn database_path(temp_dir: &TempDir, name: &str) -> PathBuf {
+    temp_dir.path().join(name)
+}
+
+/// Helper function to open a database created by the binary
+async fn open(database: &Path) -> SqlitePool {
+    SqlitePool::connect(&format!("sqlite:{}", database.display()))
+        .await
+        .unwrap()
+}
+
+#[test]
+fn test_migrate_creates_database() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+
+    let output = pfp_checker(&database, &["migrate"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(database.exists());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("up to date"));
+}
+
+#[tokio::test]
+async fn test_add_and_remove_user() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+
+    let output = pfp_checker(&database, &["add-user", "1234", "--server", "99"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = pfp_checker(&database, &["remove-user", "1234"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let pool = open(&database).await;
+    let user = sqlx::query!(
+        "SELECT trackedSince, removedAt FROM User WHERE discordId = ?",
+        1234_i64
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(user.trackedSince.is_some());
+    assert!(
+        user.removedAt.is_some(),
+        "Removed user should be soft-deleted"
+    );
+
+    let servers = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM TrackedUserServer WHERE userId = 1234 AND serverId = 99"
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(servers, 1);
+}
+
+#[tokio::test]
+async fn test_add_user_refuses_opted_out_user() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+
+    assert!(pfp_checker(&database, &["migrate"]).status.success());
+    let pool = open(&database).await;
+    sqlx::query!("INSERT INTO OptOut (userId, optedOutAt) VALUES (55, 0)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let output = pfp_checker(&database, &["add-user", "55"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("opted out"));
+    let users = sqlx::query_scalar!("SELECT COUNT(*) FROM User")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(users, 0);
+}
+
+#[test]
+fn test_remove_unknown_user_fails() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+
+    let output = pfp_checker(&database, &["remove-user", "1"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not being monitored"));
+}
+
+#[tokio::test]
+async fn test_export_import_round_trip() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let source = database_path(&temp_dir, "source.db");
+    let target = database_path(&temp_dir, "target.db");
+    let export = temp_dir.path().join("export.json");
+
+    assert!(pfp_checker(&source, &["add-user", "1", "--server", "10"])
+        .status
+        .success());
+    let pool = open(&source).await;
+    sqlx::query!(
+        "INSERT INTO ProfilePicture (checksum, userId, changedAt, link) VALUES ('abc', 1, 100, 'https://i.ibb.co/a.png')"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO UsernameChange (userId, changedAt, username) VALUES (1, 100, 'alice')"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let output = pfp_checker(&source, &["export", export.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Importing twice must not duplicate anything
+    for _ in 0..2 {
+        let output = pfp_checker(&target, &["import", export.to_str().unwrap()]);
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let pool = open(&target).await;
+    let pictures = sqlx::query!("SELECT checksum, link FROM ProfilePicture WHERE userId = 1")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(pictures.len(), 1);
+    assert_eq!(pictures[0].link.as_deref(), Some("https://i.ibb.co/a.png"));
+
+    let usernames = sqlx::query_scalar!("SELECT COUNT(*) FROM UsernameChange WHERE userId = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(usernames, 1);
+
+    let tracked_in = sqlx::query_scalar!("SELECT serverId FROM TrackedUserServer WHERE userId = 1")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(tracked_in, vec![Some(10)]);
+}
+
+#[test]
+fn test_backup_never_overwrites() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+    let backup = temp_dir.path().join("backup.db");
+
+    assert!(pfp_checker(&database, &["add-user", "1"]).status.success());
+
+    let output = pfp_checker(&database, &["backup", backup.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(backup.exists());
+
+    let output = pfp_checker(&database, &["backup", backup.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+}
+
+#[test]
+fn test_vacuum() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = database_path(&temp_dir, "bot.db");
+
+    let output = pfp_checker(&database, &["vacuum"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Vacuumed database"));
+}
@@ -0,0 +1,221 @@
+// ABOUTME: Integration tests for per-server command role requirements
+// ABOUTME: Tests storing role requirements and checking invoking members against them
+mod common;
+
+use common::create_test_db;
+use pfp_checker::error::BotError;
+use pfp_checker::util::permissions::{
+    add_required_role, fetch_required_roles, is_permitted, remove_required_role,
+};
+use serde_json::json;
+use serenity::all::{CommandInteraction, RoleId};
+
+const SERVER_ID: u64 = 123456789;
+
+/// Builds a /monitor interaction invoked by a server member
+///
+/// # Arguments
+/// * `roles` - Roles of the invoking member
+/// * `permissions` - The member's resolved permissions as a Discord bitfield string
+fn monitor_interaction(roles: &[u64], permissions: &str) -> CommandInteraction {
+    let roles: Vec<String> = roles.iter().map(u64::to_string).collect();
+
+    serde_json::from_value(json!({
+        "id": "1",
+        "application_id": "2",
+        "type": 2,
+        "data": { "id": "3", "name": "monitor", "type": 1 },
+        "guild_id": SERVER_ID.to_string(),
+        "channel_id": "4",
+        "member": {
+            "user": {
+                "id": "5",
+                "username": "member",
+                "discriminator": "0",
+                "global_name": null,
+                "avatar": null
+            },
+            "roles": roles,
+            "joined_at": "2024-01-01T00:00:00+00:00",
+            "deaf": false,
+            "mute": false,
+            "flags": 0,
+            "permissions": permissions
+        },
+        "token": "token",
+        "version": 1,
+        "app_permissions": "0",
+        "locale": "en-US",
+        "entitlements": [],
+        "attachment_size_limit": 8388608
+    }))
+    .expect("Valid interaction payload")
+}
+
+/// Builds a /monitor interaction invoked from a DM, without a server or member
+fn dm_monitor_interaction() -> CommandInteraction {
+    serde_json::from_value(json!({
+        "id": "1",
+        "application_id": "2",
+        "type": 2,
+        "data": { "id": "3", "name": "monitor", "type": 1 },
+        "channel_id": "4",
+        "user": {
+            "id": "5",
+            "username": "member",
+            "discriminator": "0",
+            "global_name": null,
+            "avatar": null
+        },
+        "token": "token",
+        "version": 1,
+        "app_permissions": "0",
+        "locale": "en-US",
+        "entitlements": [],
+        "attachment_size_limit": 8388608
+    }))
+    .expect("Valid interaction payload")
+}
+
+#[tokio::test]
+async fn test_command_permission_table_creation() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    // Verify CommandPermission table exists
+    let result = sqlx::query!(
+        "SELECT name FROM sqlite_master WHERE type='table' AND name='CommandPermission'"
+    )
+    .fetch_one(&pool)
+    .await;
+
+    assert!(result.is_ok(), "CommandPermission table should exist");
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_duplicate_role_requirement_is_ignored() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let role = RoleId::new(555);
+
+    assert!(add_required_role(&pool, SERVER_ID as i64, "monitor", role)
+        .await
+        .unwrap());
+    assert!(
+        !add_required_role(&pool, SERVER_ID as i64, "monitor", role)
+            .await
+            .unwrap(),
+        "The same role should only be stored once per command"
+    );
+    assert_eq!(
+        fetch_required_roles(&pool, SERVER_ID as i64, "monitor")
+            .await
+            .unwrap(),
+        vec![role]
+    );
+
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_role_requirements_are_scoped_per_server_and_command() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    let server_id = SERVER_ID as i64;
+    let other_server_id: i64 = 987654321;
+
+    for (server, command, role) in [
+        (server_id, "monitor", 1),
+        (server_id, "monitor", 2),
+        (server_id, "removemonitor", 3),
+        (other_server_id, "monitor", 4),
+    ] {
+        add_required_role(&pool, server, command, RoleId::new(role))
+            .await
+            .unwrap();
+    }
+
+    let mut roles = fetch_required_roles(&pool, server_id, "monitor")
+        .await
+        .unwrap();
+    roles.sort();
+    assert_eq!(
+        roles,
+        vec![RoleId::new(1), RoleId::new(2)],
+        "Only this server's monitor roles"
+    );
+
+    // Removing one requirement leaves the others untouched
+    assert!(
+        remove_required_role(&pool, server_id, "monitor", RoleId::new(1))
+            .await
+            .unwrap()
+    );
+    assert!(
+        !remove_required_role(&pool, server_id, "monitor", RoleId::new(1))
+            .await
+            .unwrap(),
+        "A role that is not required cannot be removed"
+    );
+
+    let remaining = sqlx::query_scalar!("SELECT COUNT(*) FROM CommandPermission")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 3);
+
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_members_need_a_required_role() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    assert!(
+        is_permitted(&pool, &monitor_interaction(&[], "0"))
+            .await
+            .unwrap(),
+        "Commands without requirements are open to everyone"
+    );
+
+    add_required_role(&pool, SERVER_ID as i64, "monitor", RoleId::new(10))
+        .await
+        .unwrap();
+    add_required_role(&pool, SERVER_ID as i64, "removemonitor", RoleId::new(20))
+        .await
+        .unwrap();
+
+    assert!(is_permitted(&pool, &monitor_interaction(&[7, 10], "0"))
+        .await
+        .unwrap());
+    assert!(
+        !is_permitted(&pool, &monitor_interaction(&[7, 20], "0"))
+            .await
+            .unwrap(),
+        "Roles required by other commands do not count"
+    );
+
+    // 8 is the Administrator permission bit
+    assert!(
+        is_permitted(&pool, &monitor_interaction(&[], "8"))
+            .await
+            .unwrap(),
+        "Administrators always pass"
+    );
+
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_role_gated_commands_are_refused_outside_servers() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    assert!(
+        matches!(
+            is_permitted(&pool, &dm_monitor_interaction()).await,
+            Err(BotError::GuildOnly)
+        ),
+        "Role requirements cannot be checked in a DM"
+    );
+
+    pool.close().await;
+}
@@ -0,0 +1,122 @@
+// ABOUTME: Scripted in-memory Discord shared by the integration tests
+// ABOUTME: Serves users, servers and images from per-step scripts and counts user fetches
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use pfp_checker::util::discord::{DiscordGuild, DiscordSource, DiscordUser};
+use serenity::async_trait;
+
+/// Responses of one user or server per step; `None` is a failed request.
+type Script<K, V> = Mutex<HashMap<K, Vec<Option<V>>>>;
+
+/// In-memory Discord for tests.
+///
+/// Each user and server has a script of responses, one per step. Every fetch returns the
+/// response of the current step, and the last one once the script runs out; `advance` moves to
+/// the next step, typically between two update passes. Unscripted entities and images fail like
+/// a deleted user or a 404 would.
+#[derive(Default)]
+pub struct ScriptedDiscord {
+    step: Mutex<usize>,
+    users: Script<u64, DiscordUser>,
+    guilds: Script<u64, DiscordGuild>,
+    images: Mutex<HashMap<String, Vec<u8>>>,
+    user_fetches: Mutex<HashMap<u64, usize>>,
+}
+
+impl ScriptedDiscord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves every script to its next response.
+    pub fn advance(&self) {
+        *self.step.lock().unwrap() += 1;
+    }
+
+    /// Appends a response for `user.id`.
+    pub fn push_user(&self, user: DiscordUser) {
+        push(&self.users, user.id, Some(user));
+    }
+
+    /// Appends a failed request for `user_id`.
+    pub fn push_user_error(&self, user_id: u64) {
+        push(&self.users, user_id, None);
+    }
+
+    /// Appends a response for `guild.id`.
+    pub fn push_guild(&self, guild: DiscordGuild) {
+        push(&self.guilds, guild.id, Some(guild));
+    }
+
+    /// Appends a failed request for `guild_id`.
+    pub fn push_guild_error(&self, guild_id: u64) {
+        push(&self.guilds, guild_id, None);
+    }
+
+    /// Serves `bytes` when `url` is downloaded.
+    pub fn set_image(&self, url: &str, bytes: Vec<u8>) {
+        self.images.lock().unwrap().insert(url.to_string(), bytes);
+    }
+
+    /// Number of times `user_id` was fetched.
+    pub fn user_fetches(&self, user_id: u64) -> usize {
+        self.user_fetches
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Response of the current step, `None` for failed or unscripted requests.
+    fn current<K: Eq + Hash, V: Clone>(&self, script: &Script<K, V>, key: &K) -> Option<V> {
+        let step = *self.step.lock().unwrap();
+        let script = script.lock().unwrap();
+        let responses = script.get(key)?;
+
+        responses
+            .get(step)
+            .or_else(|| responses.last())
+            .cloned()
+            .flatten()
+    }
+}
+
+fn push<K: Eq + Hash, V>(script: &Script<K, V>, key: K, response: Option<V>) {
+    script
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .push(response);
+}
+
+#[async_trait]
+impl DiscordSource for ScriptedDiscord {
+    async fn fetch_user(&self, user_id: u64) -> Result<DiscordUser, serenity::Error> {
+        *self
+            .user_fetches
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_default() += 1;
+        self.current(&self.users, &user_id)
+            .ok_or(serenity::Error::Other("Unknown User"))
+    }
+
+    async fn fetch_guild(&self, guild_id: u64) -> Result<DiscordGuild, serenity::Error> {
+        self.current(&self.guilds, &guild_id)
+            .ok_or(serenity::Error::Other("Unknown Guild"))
+    }
+
+    async fn fetch_image(&self, url: &str) -> Result<Vec<u8>, serenity::Error> {
+        self.images
+            .lock()
+            .unwrap()
+            .get(url)
+            .cloned()
+            .ok_or(serenity::Error::Other("404 Not Found"))
+    }
+}
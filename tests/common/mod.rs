@@ -0,0 +1,77 @@
+// ABOUTME: Helpers shared by the integration tests
+// ABOUTME: Creates migrated temporary databases and provides in-memory image store and Discord fakes
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use pfp_checker::util::storage::{ImageStore, StorageError, StoredImage};
+use serenity::async_trait;
+use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+use tempfile::TempDir;
+
+pub mod discord;
+
+/// Helper function to create a test database
+pub async fn create_test_db() -> (SqlitePool, TempDir) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db_url = format!("sqlite:{}", db_path.display());
+
+    // Create database
+    Sqlite::create_database(&db_url).await.unwrap();
+
+    // Connect to database
+    let pool = SqlitePool::connect(&db_url).await.unwrap();
+
+    // Run migrations
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+    (pool, temp_dir)
+}
+
+/// Image store that links to the file name and remembers uploads and deletions
+#[derive(Default)]
+pub struct FakeImageStore {
+    pub uploads: Mutex<Vec<String>>,
+    pub deleted: Mutex<Vec<StoredImage>>,
+    /// Makes every deletion fail, as if the image host could not be reached
+    pub deletions_fail: AtomicBool,
+}
+
+impl FakeImageStore {
+    /// A store whose deletions fail until `deletions_fail` is cleared
+    pub fn failing_deletions() -> Self {
+        let store = FakeImageStore::default();
+        store.deletions_fail.store(true, Ordering::SeqCst);
+        store
+    }
+}
+
+#[async_trait]
+impl ImageStore for FakeImageStore {
+    async fn upload(
+        &self,
+        _image_data: Vec<u8>,
+        filename: String,
+    ) -> Result<StoredImage, StorageError> {
+        let link = format!("https://images.test/{}", filename);
+        self.uploads.lock().unwrap().push(link.clone());
+        Ok(StoredImage {
+            link,
+            delete_url: None,
+        })
+    }
+
+    async fn fetch(&self, _link: &str) -> Result<Vec<u8>, StorageError> {
+        Err(StorageError::Unsupported("fetch"))
+    }
+
+    async fn delete(&self, image: &StoredImage) -> Result<(), StorageError> {
+        if self.deletions_fail.load(Ordering::SeqCst) {
+            return Err(StorageError::Unsupported("delete"));
+        }
+        self.deleted.lock().unwrap().push(image.clone());
+        Ok(())
+    }
+}
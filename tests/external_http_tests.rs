@@ -0,0 +1,368 @@
+// ABOUTME: Integration tests for ImgBB uploads, deletions and CDN downloads against a local HTTP stand-in
+// ABOUTME: Covers successful requests, error statuses, malformed responses, timeouts and a full update pass
+mod common;
+
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::RawQuery;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::Router;
+use common::create_test_db;
+use common::discord::ScriptedDiscord;
+use pfp_checker::util::chron_update::update_monitored_users;
+use pfp_checker::util::discord::{CdnClient, DiscordGuild, DiscordSource, DiscordUser};
+use pfp_checker::util::external::imgbb::ImgBBError;
+use pfp_checker::util::storage::{ImageStore, ImgBBStore, StorageError, StoredImage};
+use pfp_checker::util::update_runs::RunTrigger;
+use pfp_checker::util::{check_status, history};
+use serenity::async_trait;
+use serenity::http::HttpError;
+
+const API_KEY: &str = "test-key";
+const AVATAR: &[u8] = b"\x89PNG avatar";
+const TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Answers like ImgBB's `/1/upload`, linking to the uploaded file name
+async fn upload(RawQuery(query): RawQuery, body: Bytes) -> impl IntoResponse {
+    if query.as_deref() != Some("key=test-key") {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CONTENT_TYPE, "application/json")],
+            r#"{"status_code":400,"error":{"message":"Invalid API v1 key.","code":100},"status_txt":"Bad Request"}"#
+                .to_string(),
+        );
+    }
+
+    let body = String::from_utf8_lossy(&body);
+    let filename = body
+        .split("filename=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap_or("missing");
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        format!(
+            r#"{{"data":{{"id":"abc","url":"https://i.ibb.co/abc/{0}","display_url":"https://i.ibb.co/abc/{0}","delete_url":"https://ibb.co/abc/deletehash"}},"success":true,"status":200}}"#,
+            filename
+        ),
+    )
+}
+
+/// Answers like ImgBB's `/json` endpoint, deleting only `abc` with the right token and hash
+async fn delete(body: Bytes) -> impl IntoResponse {
+    let body = String::from_utf8_lossy(&body);
+    let authorized = body.contains("auth_token=token123")
+        && body.contains("action=delete")
+        && body.contains("deleting%5Bid%5D=abc")
+        && body.contains("deleting%5Bhash%5D=deletehash");
+
+    let status_code = if authorized { 200 } else { 403 };
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        format!(r#"{{"status_code":{}}}"#, status_code),
+    )
+}
+
+/// Never answers, to run into the client's timeout
+async fn hang() -> StatusCode {
+    futures::future::pending::<()>().await;
+    StatusCode::OK
+}
+
+/// Starts a local stand-in for ImgBB and the Discord CDN and returns its base URL
+async fn start_stand_in() -> String {
+    let app = Router::new()
+        .route("/1/upload", post(upload))
+        .route(
+            "/server-error/1/upload",
+            post(|| async { StatusCode::SERVICE_UNAVAILABLE }),
+        )
+        .route(
+            "/malformed/1/upload",
+            post(|| async { "<html>Not quite JSON</html>" }),
+        )
+        .route("/slow/1/upload", post(hang))
+        .route(
+            "/abc/{hash}",
+            get(|| async {
+                r#"<html><script>PF.obj.config.auth_token="token123";</script></html>"#
+            }),
+        )
+        .route("/json", post(delete))
+        .route("/avatars/1/abc.png", get(|| async { AVATAR }))
+        .route(
+            "/avatars/2/broken.png",
+            get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        )
+        .route("/avatars/3/slow.png", get(hang));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+/// The HTTP error a failed download wraps
+fn request_error(error: &serenity::Error) -> &reqwest::Error {
+    match error {
+        serenity::Error::Http(HttpError::Request(e)) => e,
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+fn store(base_url: &str, path: &str) -> ImgBBStore {
+    ImgBBStore::new(API_KEY)
+        .with_upload_url(format!("{}{}", base_url, path))
+        .with_timeout(TIMEOUT)
+}
+
+#[tokio::test]
+async fn test_upload_returns_links() {
+    let base_url = start_stand_in().await;
+
+    let image = store(&base_url, "/1/upload")
+        .upload(AVATAR.to_vec(), "pfp_1_100.png".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(image.link, "https://i.ibb.co/abc/pfp_1_100.png");
+    assert_eq!(
+        image.delete_url.as_deref(),
+        Some("https://ibb.co/abc/deletehash")
+    );
+}
+
+#[tokio::test]
+async fn test_delete_through_delete_link() {
+    let base_url = start_stand_in().await;
+    let image = StoredImage {
+        link: "https://i.ibb.co/abc/pfp_1_100.png".to_string(),
+        delete_url: Some(format!("{}/abc/deletehash", base_url)),
+    };
+
+    store(&base_url, "/1/upload").delete(&image).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_delete_refused() {
+    let base_url = start_stand_in().await;
+    let image = StoredImage {
+        link: "https://i.ibb.co/abc/pfp_1_100.png".to_string(),
+        delete_url: Some(format!("{}/abc/wronghash", base_url)),
+    };
+
+    let result = store(&base_url, "/1/upload").delete(&image).await;
+
+    assert!(
+        matches!(result, Err(StorageError::ImgBB(ImgBBError::Rejected(_)))),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_delete_without_delete_link() {
+    let base_url = start_stand_in().await;
+    let image = StoredImage {
+        link: "https://i.ibb.co/abc/pfp_1_100.png".to_string(),
+        delete_url: None,
+    };
+
+    let result = store(&base_url, "/1/upload").delete(&image).await;
+
+    assert!(
+        matches!(result, Err(StorageError::Unsupported(_))),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_upload_client_error() {
+    let base_url = start_stand_in().await;
+
+    let result = ImgBBStore::new("wrong-key")
+        .with_upload_url(format!("{}/1/upload", base_url))
+        .upload(AVATAR.to_vec(), "pfp_1_100.png".to_string())
+        .await;
+
+    assert!(
+        matches!(result, Err(StorageError::ImgBB(ImgBBError::RequestError(ref e))) if e.status() == Some(reqwest::StatusCode::BAD_REQUEST)),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_upload_server_error() {
+    let base_url = start_stand_in().await;
+
+    let result = store(&base_url, "/server-error/1/upload")
+        .upload(AVATAR.to_vec(), "pfp_1_100.png".to_string())
+        .await;
+
+    assert!(
+        matches!(result, Err(StorageError::ImgBB(ImgBBError::RequestError(ref e))) if e.status() == Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_upload_malformed_response() {
+    let base_url = start_stand_in().await;
+
+    let result = store(&base_url, "/malformed/1/upload")
+        .upload(AVATAR.to_vec(), "pfp_1_100.png".to_string())
+        .await;
+
+    assert!(
+        matches!(result, Err(StorageError::ImgBB(ImgBBError::ParseError(_)))),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_upload_timeout() {
+    let base_url = start_stand_in().await;
+
+    let result = store(&base_url, "/slow/1/upload")
+        .upload(AVATAR.to_vec(), "pfp_1_100.png".to_string())
+        .await;
+
+    assert!(
+        matches!(result, Err(StorageError::ImgBB(ImgBBError::RequestError(ref e))) if e.is_timeout()),
+        "{:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_cdn_download() {
+    let base_url = start_stand_in().await;
+    let cdn = CdnClient::new(&base_url, TIMEOUT);
+
+    let bytes = cdn
+        .download("https://cdn.discordapp.com/avatars/1/abc.png")
+        .await
+        .unwrap();
+    assert_eq!(bytes, AVATAR);
+
+    let error = cdn
+        .download("https://cdn.discordapp.com/avatars/2/broken.png")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        request_error(&error).status(),
+        Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+    );
+
+    let error = cdn
+        .download("https://cdn.discordapp.com/avatars/9/missing.png")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        request_error(&error).status(),
+        Some(reqwest::StatusCode::NOT_FOUND)
+    );
+
+    let error = cdn
+        .download("https://cdn.discordapp.com/avatars/3/slow.png")
+        .await
+        .unwrap_err();
+    assert!(request_error(&error).is_timeout());
+}
+
+/// Scripted users and servers whose images are downloaded from the stand-in CDN
+struct StandInDiscord {
+    scripted: ScriptedDiscord,
+    cdn: CdnClient,
+}
+
+#[async_trait]
+impl DiscordSource for StandInDiscord {
+    async fn fetch_user(&self, user_id: u64) -> Result<DiscordUser, serenity::Error> {
+        self.scripted.fetch_user(user_id).await
+    }
+
+    async fn fetch_guild(&self, guild_id: u64) -> Result<DiscordGuild, serenity::Error> {
+        self.scripted.fetch_guild(guild_id).await
+    }
+
+    async fn fetch_image(&self, url: &str) -> Result<Vec<u8>, serenity::Error> {
+        self.cdn.download(url).await
+    }
+}
+
+#[tokio::test]
+async fn test_update_pass_over_http() {
+    let base_url = start_stand_in().await;
+    let (pool, _temp_dir) = create_test_db().await;
+
+    let discord = StandInDiscord {
+        scripted: ScriptedDiscord::new(),
+        cdn: CdnClient::new(&base_url, TIMEOUT),
+    };
+    for (user_id, avatar) in [(1, "1/abc.png"), (2, "2/broken.png"), (3, "3/slow.png")] {
+        sqlx::query!(
+            "INSERT INTO User (discordId, trackedSince) VALUES (?, 0)",
+            user_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        discord.scripted.push_user(DiscordUser {
+            id: user_id as u64,
+            avatar_url: format!("https://cdn.discordapp.com/avatars/{}", avatar),
+            global_name: None,
+        });
+    }
+
+    update_monitored_users(
+        &discord,
+        &pool,
+        &store(&base_url, "/1/upload"),
+        RunTrigger::Schedule,
+    )
+    .await;
+
+    let pictures = history::profile_pictures(&pool, 1).await.unwrap();
+    assert_eq!(pictures.len(), 1);
+    assert!(pictures[0]
+        .link
+        .as_deref()
+        .unwrap()
+        .starts_with("https://i.ibb.co/abc/pfp_1_"));
+    let delete_url = sqlx::query_scalar!(
+        "SELECT deleteUrl FROM ArchivedImage WHERE link = ?",
+        pictures[0].link
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(delete_url, "https://ibb.co/abc/deletehash");
+
+    for user_id in [2, 3] {
+        assert!(history::profile_pictures(&pool, user_id)
+            .await
+            .unwrap()
+            .is_empty());
+        let status = check_status::user_status(&pool, user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            status.consecutive_failures, 1,
+            "Failed download should be recorded"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// ABOUTME: Integration tests for the queue of archived images awaiting deletion from the image store
+// ABOUTME: Tests that delete links are kept, failed deletions are retried and images without a delete link are dropped
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use common::{create_test_db, FakeImageStore};
+use pfp_checker::util::image_deletion::{self, DeletionReport};
+use pfp_checker::util::storage::StoredImage;
+use sqlx::SqlitePool;
+
+async fn queue(pool: &SqlitePool, links: &[String]) {
+    let mut transaction = pool.begin().await.unwrap();
+    image_deletion::queue(&mut transaction, links, 1_000)
+        .await
+        .unwrap();
+    transaction.commit().await.unwrap();
+}
+
+async fn archive(pool: &SqlitePool, links: &[String]) {
+    for link in links {
+        let image = StoredImage {
+            link: link.clone(),
+            delete_url: Some(format!("{}/delete", link)),
+        };
+        image_deletion::record_upload(pool, &image).await.unwrap();
+    }
+}
+
+async fn pending(pool: &SqlitePool) -> Vec<(String, i64)> {
+    sqlx::query!(
+        r#"SELECT link AS "link!: String", attempts FROM PendingImageDeletion ORDER BY link"#
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|entry| (entry.link, entry.attempts))
+    .collect()
+}
+
+#[tokio::test]
+async fn test_queued_image_is_deleted_with_its_delete_link() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let store = FakeImageStore::default();
+    let image = StoredImage {
+        link: "https://i.ibb.co/abc/pfp.png".to_string(),
+        delete_url: Some("https://ibb.co/abc/hash".to_string()),
+    };
+    image_deletion::record_upload(&pool, &image).await.unwrap();
+
+    let links = vec![image.link.clone()];
+    queue(&pool, &links).await;
+    let report = image_deletion::delete_queued(&pool, &store, &links)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        DeletionReport {
+            deleted: 1,
+            failed: 0
+        }
+    );
+    assert_eq!(*store.deleted.lock().unwrap(), vec![image]);
+    assert!(pending(&pool).await.is_empty());
+    let archived = sqlx::query_scalar!("SELECT COUNT(*) FROM ArchivedImage")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(archived, 0);
+}
+
+#[tokio::test]
+async fn test_failed_deletion_stays_queued_until_retried() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let store = FakeImageStore::failing_deletions();
+    let links = vec![
+        "https://i.ibb.co/a/pfp.png".to_string(),
+        "https://i.ibb.co/b/pfp.png".to_string(),
+    ];
+    archive(&pool, &links).await;
+    queue(&pool, &links).await;
+
+    let report = image_deletion::delete_queued(&pool, &store, &links)
+        .await
+        .unwrap();
+    assert_eq!(report.failed, 2);
+    assert_eq!(
+        pending(&pool).await,
+        vec![(links[0].clone(), 1), (links[1].clone(), 1)]
+    );
+
+    store.deletions_fail.store(false, Ordering::SeqCst);
+    let report = image_deletion::retry_pending(&pool, &store).await.unwrap();
+
+    assert_eq!(
+        report,
+        DeletionReport {
+            deleted: 2,
+            failed: 0
+        }
+    );
+    assert!(pending(&pool).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_entries_without_delete_link_are_dropped_on_retry() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let store = FakeImageStore::default();
+
+    // Queued before images without a delete link were left to the operator
+    sqlx::query!(
+        "INSERT INTO PendingImageDeletion (link, deleteUrl, queuedAt) VALUES (?, NULL, ?)",
+        "https://i.ibb.co/old/pfp.png",
+        1_000_i64
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let report = image_deletion::retry_pending(&pool, &store).await.unwrap();
+
+    assert_eq!(report, DeletionReport::default());
+    assert!(store.deleted.lock().unwrap().is_empty());
+    assert!(pending(&pool).await.is_empty());
+}
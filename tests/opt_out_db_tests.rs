@@ -0,0 +1,145 @@
+// ABOUTME: Integration tests for the privacy opt-out tables
+// ABOUTME: Tests that opting out removes a user's history, queues their images and leaves an OptOut block behind
+mod common;
+
+use common::create_test_db;
+use pfp_checker::commands::optout;
+use pfp_checker::util::image_deletion::{self, QueuedImages};
+use pfp_checker::util::storage::StoredImage;
+use sqlx::SqlitePool;
+
+/// Helper function to add a tracked user with one archived profile picture
+async fn add_user_with_picture(pool: &SqlitePool, user_id: i64, link: &str) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, ?)",
+        user_id,
+        1000_i64
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        "INSERT INTO ProfilePicture (checksum, userId, changedAt, link) VALUES (?, ?, ?, ?)",
+        "abc",
+        user_id,
+        1000_i64,
+        link
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_opt_out_table_creation() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    // Verify OptOut table exists
+    let result =
+        sqlx::query!("SELECT name FROM sqlite_master WHERE type='table' AND name='OptOut'")
+            .fetch_one(&pool)
+            .await;
+
+    assert!(result.is_ok(), "OptOut table should exist");
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_opt_out_removes_only_that_users_history() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    let user_id: i64 = 111;
+    let other_user_id: i64 = 222;
+
+    for id in [user_id, other_user_id] {
+        let link = format!("https://i.ibb.co/abc/pfp_{}.png", id);
+        add_user_with_picture(&pool, id, &link).await;
+        image_deletion::record_upload(
+            &pool,
+            &StoredImage {
+                link,
+                delete_url: Some(format!("https://ibb.co/abc/{}", id)),
+            },
+        )
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            "INSERT INTO UsernameChange (userId, changedAt, username) VALUES (?, ?, ?)",
+            id,
+            1000_i64,
+            "name"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let images = optout::delete_user_data(&pool, user_id).await.unwrap();
+    assert_eq!(
+        images,
+        QueuedImages {
+            queued: vec!["https://i.ibb.co/abc/pfp_111.png".to_string()],
+            without_delete_link: vec![],
+        }
+    );
+
+    for (id, expected) in [(user_id, 0), (other_user_id, 1)] {
+        let pictures =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM ProfilePicture WHERE userId = ?", id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let usernames =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM UsernameChange WHERE userId = ?", id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        assert_eq!(pictures, expected, "Profile pictures of user {}", id);
+        assert_eq!(usernames, expected, "Usernames of user {}", id);
+    }
+
+    let block = sqlx::query!("SELECT userId FROM OptOut WHERE userId = ?", user_id)
+        .fetch_optional(&pool)
+        .await
+        .unwrap();
+    assert!(block.is_some(), "Opted out user should be blocked");
+
+    let pending = sqlx::query_scalar!("SELECT link FROM PendingImageDeletion")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        pending,
+        vec![Some("https://i.ibb.co/abc/pfp_111.png".to_string())],
+        "The opted out user's image should wait for deletion"
+    );
+
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_images_without_delete_link_are_left_to_the_operator() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let link = "https://i.ibb.co/abc/pfp_111.png";
+    add_user_with_picture(&pool, 111, link).await;
+
+    let images = optout::delete_user_data(&pool, 111).await.unwrap();
+    assert_eq!(
+        images,
+        QueuedImages {
+            queued: vec![],
+            without_delete_link: vec![link.to_string()],
+        }
+    );
+
+    let pending = sqlx::query_scalar!("SELECT COUNT(*) FROM PendingImageDeletion")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(pending, 0, "No retry could ever delete the image");
+
+    pool.close().await;
+}
@@ -0,0 +1,145 @@
+// ABOUTME: Integration tests for finding profile pictures shared across users
+// ABOUTME: Tests exact and similar matches, usage periods and perceptual hashes recorded by update passes
+mod common;
+
+use std::io::Cursor;
+
+use common::discord::ScriptedDiscord;
+use common::{create_test_db, FakeImageStore};
+use image::{ImageFormat, Rgb, RgbImage};
+use pfp_checker::util::chron_update::update_monitored_users;
+use pfp_checker::util::discord::DiscordUser;
+use pfp_checker::util::fingerprint;
+use pfp_checker::util::history::{self, PictureMatch};
+use pfp_checker::util::update_runs::RunTrigger;
+use sqlx::SqlitePool;
+
+/// Helper function to add a user with recorded profile pictures
+async fn add_user(pool: &SqlitePool, user_id: i64, pictures: &[(&str, i64, Option<&str>)]) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    for (checksum, changed_at, perceptual_hash) in pictures {
+        let link = format!("https://images.test/{}.png", checksum);
+        sqlx::query!(
+            "INSERT INTO ProfilePicture (checksum, userId, changedAt, link, perceptualHash) VALUES (?, ?, ?, ?, ?)",
+            checksum,
+            user_id,
+            changed_at,
+            link,
+            perceptual_hash
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = RgbImage::from_fn(width, height, |x, y| {
+        Rgb([(x * 4) as u8, (y * 4) as u8, 128])
+    });
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn test_exact_matches_with_periods() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_user(&pool, 1, &[("shared", 100, None), ("other", 200, None)]).await;
+    add_user(&pool, 2, &[("other", 150, None), ("shared", 300, None)]).await;
+    add_user(&pool, 3, &[("unrelated", 100, None)]).await;
+
+    let uses = history::picture_uses(&pool, "shared", None).await.unwrap();
+
+    assert_eq!(uses.len(), 2);
+    assert_eq!(
+        (uses[0].user_id, uses[0].from, uses[0].until),
+        (1, 100, Some(200))
+    );
+    assert_eq!(
+        (uses[1].user_id, uses[1].from, uses[1].until),
+        (2, 300, None),
+        "The current picture has no end"
+    );
+    assert!(uses
+        .iter()
+        .all(|found| found.matched == PictureMatch::Exact));
+}
+
+#[tokio::test]
+async fn test_similar_matches_by_perceptual_hash() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_user(&pool, 1, &[("original", 100, Some("00000000000000ff"))]).await;
+    add_user(&pool, 2, &[("reencoded", 200, Some("00000000000000fc"))]).await;
+    add_user(&pool, 3, &[("different", 300, Some("ffffffffffffff00"))]).await;
+    add_user(&pool, 4, &[("unhashed", 400, None)]).await;
+    // Six bits off, spread over six of the eight bytes
+    add_user(&pool, 5, &[("edited", 500, Some("01010101010100ff"))]).await;
+
+    let uses = history::picture_uses(&pool, "original", Some("00000000000000ff"))
+        .await
+        .unwrap();
+
+    let found: Vec<(i64, PictureMatch)> = uses
+        .iter()
+        .map(|found| (found.user_id, found.matched))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (1, PictureMatch::Exact),
+            (2, PictureMatch::Similar(2)),
+            (5, PictureMatch::Similar(6))
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_update_pass_stores_perceptual_hash() {
+    let (pool, _temp_dir) = create_test_db().await;
+    sqlx::query!("INSERT INTO User (discordId, trackedSince) VALUES (1, 0)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let avatar = png(64, 64);
+    let discord = ScriptedDiscord::new();
+    discord.push_user(DiscordUser {
+        id: 1,
+        avatar_url: "https://cdn.test/a.png".to_string(),
+        global_name: None,
+    });
+    discord.set_image("https://cdn.test/a.png", avatar.clone());
+
+    update_monitored_users(
+        &discord,
+        &pool,
+        &FakeImageStore::default(),
+        RunTrigger::Schedule,
+    )
+    .await;
+
+    // A rescaled copy is a different file but the same picture
+    let copy = png(32, 32);
+    let uses = history::picture_uses(
+        &pool,
+        &fingerprint::checksum(&copy),
+        fingerprint::perceptual_hash(&copy).as_deref(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(uses.len(), 1);
+    assert_eq!(uses[0].user_id, 1);
+    assert_eq!(uses[0].checksum, fingerprint::checksum(&avatar));
+    assert!(matches!(uses[0].matched, PictureMatch::Similar(_)));
+}
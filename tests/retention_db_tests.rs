@@ -0,0 +1,103 @@
+// ABOUTME: Integration tests for retention pruning against a migrated database
+// ABOUTME: Tests that previews change nothing and that images the store fails to delete stay queued
+mod common;
+
+use chrono::Utc;
+use common::{create_test_db, FakeImageStore};
+use pfp_checker::util::image_deletion;
+use pfp_checker::util::retention::{self, PruneReport, PruneScope, RetentionPolicy};
+use pfp_checker::util::storage::StoredImage;
+use sqlx::SqlitePool;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Helper function to add a user with profile pictures changed the given days ago
+async fn add_user(pool: &SqlitePool, user_id: i64, pictures: &[(&str, i64)]) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let now = Utc::now().timestamp();
+    for (checksum, days_ago) in pictures {
+        let changed_at = now - days_ago * DAY;
+        let link = format!("https://i.ibb.co/{}/pfp_{}.png", checksum, user_id);
+        sqlx::query!(
+            "INSERT INTO ProfilePicture (checksum, userId, changedAt, link) VALUES (?, ?, ?, ?)",
+            checksum,
+            user_id,
+            changed_at,
+            link
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let image = StoredImage {
+            delete_url: Some(format!("https://ibb.co/{}/hash", checksum)),
+            link,
+        };
+        image_deletion::record_upload(pool, &image).await.unwrap();
+    }
+}
+
+async fn picture_count(pool: &SqlitePool) -> i64 {
+    sqlx::query_scalar!("SELECT COUNT(*) FROM ProfilePicture")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_preview_matches_cleanup_and_keeps_failed_deletions() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_user(&pool, 1, &[("old", 300), ("shared", 200), ("current", 1)]).await;
+    add_user(&pool, 2, &[("shared", 1)]).await;
+    let store = FakeImageStore::failing_deletions();
+    let policy = RetentionPolicy {
+        max_age_days: None,
+        max_entries: Some(1),
+    };
+
+    let preview = retention::prune(&pool, &store, policy, PruneScope::All, true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        preview,
+        PruneReport {
+            profile_pictures: 2,
+            usernames: 0,
+            server_pictures: 0,
+            images: 1,
+            failed_image_deletions: 0,
+        },
+        "The shared picture is still used by user 2"
+    );
+    assert_eq!(picture_count(&pool).await, 4, "A preview removes nothing");
+
+    let report = retention::prune(&pool, &store, policy, PruneScope::All, false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        PruneReport {
+            failed_image_deletions: 1,
+            ..preview
+        }
+    );
+    assert_eq!(picture_count(&pool).await, 2);
+
+    let pending = sqlx::query_scalar!("SELECT link FROM PendingImageDeletion")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        pending,
+        vec![Some("https://i.ibb.co/old/pfp_1.png".to_string())]
+    );
+}
@@ -1,27 +1,11 @@
 // ABOUTME: Integration tests for server tracking database functionality
 // ABOUTME: Tests migrations, CRUD operations, and data integrity for Server and ServerPicture tables
+mod common;
+
 use chrono::{DateTime, Utc};
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+use common::create_test_db;
+use pfp_checker::util::history;
 use std::time::SystemTime;
-use tempfile::TempDir;
-
-/// Helper function to create a test database
-async fn create_test_db() -> (SqlitePool, TempDir) {
-    let temp_dir = tempfile::tempdir().unwrap();
-    let db_path = temp_dir.path().join("test.db");
-    let db_url = format!("sqlite:{}", db_path.display());
-
-    // Create database
-    Sqlite::create_database(&db_url).await.unwrap();
-
-    // Connect to database
-    let pool = SqlitePool::connect(&db_url).await.unwrap();
-
-    // Run migrations
-    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
-
-    (pool, temp_dir)
-}
 
 #[tokio::test]
 async fn test_server_table_creation() {
@@ -251,12 +235,7 @@ async fn test_retrieve_server_pictures() {
     }
 
     // Retrieve all pictures for the server
-    let results = sqlx::query!(
-        "SELECT checksum, changedAt, link FROM ServerPicture WHERE serverId = ? ORDER BY changedAt DESC",
-        server_id
-    )
-    .fetch_all(&pool)
-    .await;
+    let results = history::server_icons(&pool, server_id).await;
 
     assert!(
         results.is_ok(),
@@ -266,9 +245,13 @@ async fn test_retrieve_server_pictures() {
     assert_eq!(records.len(), 3, "Should retrieve all three pictures");
 
     // Verify ordering (DESC by changedAt)
-    assert_eq!(records[0].checksum.as_ref().unwrap(), "checksum3");
-    assert_eq!(records[1].checksum.as_ref().unwrap(), "checksum2");
-    assert_eq!(records[2].checksum.as_ref().unwrap(), "checksum1");
+    assert_eq!(records[0].checksum, "checksum3");
+    assert_eq!(records[1].checksum, "checksum2");
+    assert_eq!(records[2].checksum, "checksum1");
+    assert_eq!(
+        records[0].link.as_deref(),
+        Some("https://example.com/icon3.png")
+    );
 
     pool.close().await;
 }
@@ -0,0 +1,269 @@
+// ABOUTME: Integration tests for the similarity alert job
+// ABOUTME: Tests avatar and display name matching across users, the scan position and alert channel lookup
+mod common;
+
+use common::create_test_db;
+use pfp_checker::util::alerts::{self, MatchedValue, COMMON_MATCH_USERS};
+use pfp_checker::util::history::PictureMatch;
+use serenity::http::Http;
+use sqlx::SqlitePool;
+
+/// Helper function to record a profile picture, adding the user if needed
+async fn add_picture(
+    pool: &SqlitePool,
+    user_id: i64,
+    checksum: &str,
+    changed_at: i64,
+    perceptual_hash: Option<&str>,
+) {
+    sqlx::query!(
+        "INSERT OR IGNORE INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO ProfilePicture (checksum, userId, changedAt, link, perceptualHash) VALUES (?, ?, ?, NULL, ?)",
+        checksum,
+        user_id,
+        changed_at,
+        perceptual_hash
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Helper function to record a display name, adding the user if needed
+async fn add_name(pool: &SqlitePool, user_id: i64, username: &str, changed_at: i64) {
+    sqlx::query!(
+        "INSERT OR IGNORE INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO UsernameChange (userId, changedAt, username) VALUES (?, ?, ?)",
+        user_id,
+        changed_at,
+        username
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_new_avatar_matching_other_user_is_found() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_picture(&pool, 1, "banned", 100, None).await;
+    add_picture(&pool, 1, "later", 200, None).await;
+    add_picture(&pool, 2, "banned", 1_000, None).await;
+
+    let matches = alerts::find_matches(&pool, 500, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert_eq!(matches.len(), 1);
+    let found = &matches[0];
+    assert_eq!((found.user_id, found.other_user_id), (2, 1));
+    assert_eq!((found.other_from, found.other_until), (100, Some(200)));
+    assert!(matches!(
+        found.value,
+        MatchedValue::Avatar {
+            matched: PictureMatch::Exact,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_similar_avatar_is_found() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_picture(&pool, 1, "original", 100, Some("00000000000000ff")).await;
+    add_picture(&pool, 2, "resized", 1_000, Some("00000000000000fe")).await;
+
+    let matches = alerts::find_matches(&pool, 500, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert_eq!(matches.len(), 1);
+    assert!(matches!(
+        matches[0].value,
+        MatchedValue::Avatar {
+            matched: PictureMatch::Similar(1),
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_records_outside_the_scan_are_ignored() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_picture(&pool, 1, "shared", 100, None).await;
+    add_picture(&pool, 2, "shared", 200, None).await;
+
+    let matches = alerts::find_matches(&pool, 200, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert!(
+        matches.is_empty(),
+        "Records at the scan start were alerted before"
+    );
+}
+
+#[tokio::test]
+async fn test_reverted_avatar_is_not_alerted_again() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_picture(&pool, 1, "shared", 100, None).await;
+    add_picture(&pool, 2, "shared", 200, None).await;
+    add_picture(&pool, 2, "other", 300, None).await;
+    add_picture(&pool, 2, "shared", 1_000, None).await;
+
+    let matches = alerts::find_matches(&pool, 500, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert!(matches.is_empty());
+}
+
+#[tokio::test]
+async fn test_common_avatar_is_skipped() {
+    let (pool, _temp_dir) = create_test_db().await;
+    for user_id in 1..=COMMON_MATCH_USERS as i64 + 1 {
+        add_picture(&pool, user_id, "default", 100, None).await;
+    }
+    add_picture(&pool, 99, "default", 1_000, None).await;
+
+    let matches = alerts::find_matches(&pool, 500, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert!(
+        matches.is_empty(),
+        "Default avatars are shared by many users"
+    );
+}
+
+#[tokio::test]
+async fn test_look_alike_name_is_found() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_name(&pool, 1, "John Doe", 100).await;
+    add_name(&pool, 1, "Someone Else", 200).await;
+    add_name(&pool, 2, "j0hn_doe", 1_000).await;
+    add_name(&pool, 3, "Jane Doe", 1_100).await;
+
+    let matches = alerts::find_matches(&pool, 500, 2_000)
+        .await
+        .unwrap()
+        .matches;
+
+    assert_eq!(matches.len(), 1);
+    let found = &matches[0];
+    assert_eq!((found.user_id, found.other_user_id), (2, 1));
+    assert_eq!((found.other_from, found.other_until), (100, Some(200)));
+    assert_eq!(
+        found.value,
+        MatchedValue::Name {
+            name: "j0hn_doe".to_string(),
+            other_name: "John Doe".to_string(),
+        }
+    );
+
+    let unnormalized =
+        sqlx::query_scalar!("SELECT COUNT(*) FROM UsernameChange WHERE normalizedName IS NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        unnormalized, 0,
+        "Names recorded without one are normalized by the scan"
+    );
+}
+
+#[tokio::test]
+async fn test_scan_reports_latest_scanned_record() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_picture(&pool, 1, "avatar", 800, None).await;
+    add_name(&pool, 1, "alice", 900).await;
+
+    let scan = alerts::find_matches(&pool, 500, 2_000).await.unwrap();
+    assert_eq!(scan.scanned_until, Some(900));
+
+    let scan = alerts::find_matches(&pool, 900, 2_000).await.unwrap();
+    assert_eq!(scan.scanned_until, None, "Nothing was recorded after 900");
+}
+
+#[tokio::test]
+async fn test_scan_position_only_moves_after_successful_scans() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let http = Http::new("token");
+
+    assert_eq!(alerts::scan_position(&pool).await.unwrap(), None);
+    alerts::run(&http, &pool, 100).await;
+    assert_eq!(
+        alerts::scan_position(&pool).await.unwrap(),
+        Some(100),
+        "The first run starts at the present"
+    );
+
+    add_name(&pool, 1, "alice", 150).await;
+    alerts::run(&http, &pool, 200).await;
+    assert_eq!(
+        alerts::scan_position(&pool).await.unwrap(),
+        Some(150),
+        "Only up to the latest scanned record"
+    );
+
+    alerts::run(&http, &pool, 300).await;
+    assert_eq!(alerts::scan_position(&pool).await.unwrap(), Some(150));
+
+    add_name(&pool, 1, "bob", 350).await;
+    sqlx::query!("ALTER TABLE ProfilePicture RENAME TO ProfilePictureMissing")
+        .execute(&pool)
+        .await
+        .unwrap();
+    alerts::run(&http, &pool, 400).await;
+    assert_eq!(
+        alerts::scan_position(&pool).await.unwrap(),
+        Some(150),
+        "A failed scan is repeated"
+    );
+}
+
+#[tokio::test]
+async fn test_alert_channels_of_servers_monitoring_the_user() {
+    let (pool, _temp_dir) = create_test_db().await;
+    add_name(&pool, 1, "alice", 100).await;
+    for server_id in [10, 20, 30] {
+        sqlx::query!(
+            "INSERT INTO TrackedUserServer (userId, serverId, addedAt) VALUES (1, ?, 0)",
+            server_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    alerts::set_alert_channel(&pool, 10, 1_000).await.unwrap();
+    alerts::set_alert_channel(&pool, 20, 2_000).await.unwrap();
+    alerts::set_alert_channel(&pool, 40, 4_000).await.unwrap();
+    assert!(alerts::clear_alert_channel(&pool, 20).await.unwrap());
+
+    assert_eq!(
+        alerts::alert_channels_for_user(&pool, 1).await.unwrap(),
+        vec![1_000]
+    );
+    assert_eq!(
+        alerts::fetch_alert_channel(&pool, 40).await.unwrap(),
+        Some(4_000)
+    );
+    assert_eq!(alerts::fetch_alert_channel(&pool, 20).await.unwrap(), None);
+}
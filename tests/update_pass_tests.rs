@@ -0,0 +1,307 @@
+// ABOUTME: Integration tests that drive full update passes against fake Discord and image store backends
+// ABOUTME: Tests archiving of new images, reverts, username recording and backoff of failing entities
+mod common;
+
+use common::discord::ScriptedDiscord;
+use common::{create_test_db, FakeImageStore};
+use pfp_checker::util::chron_update::{
+    check_user_now, update_monitored_servers, update_monitored_users, CheckOutcome,
+};
+use pfp_checker::util::discord::{DiscordGuild, DiscordSource, DiscordUser};
+use pfp_checker::util::update_runs::{self, RunTrigger};
+use pfp_checker::util::{check_status, history};
+use sqlx::SqlitePool;
+
+/// Helper function to add a user to the monitoring list
+async fn monitor_user(pool: &SqlitePool, user_id: i64) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince) VALUES (?, 0)",
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Moves all recorded history one minute into the past, so the next pass records at a later time
+async fn age_history(pool: &SqlitePool) {
+    sqlx::query!("UPDATE ProfilePicture SET changedAt = changedAt - 60")
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+/// Helper function to add a server to the monitoring list
+async fn monitor_server(pool: &SqlitePool, server_id: i64) {
+    sqlx::query!(
+        "INSERT INTO Server (serverId, trackedSince) VALUES (?, 0)",
+        server_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+fn user(id: u64, avatar_url: &str, global_name: Option<&str>) -> DiscordUser {
+    DiscordUser {
+        id,
+        avatar_url: avatar_url.to_string(),
+        global_name: global_name.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_script_follows_steps() {
+    let discord = ScriptedDiscord::new();
+    discord.push_user(user(1, "a", None));
+    discord.push_user_error(1);
+    discord.push_user(user(1, "b", None));
+
+    assert_eq!(discord.fetch_user(1).await.unwrap().avatar_url, "a");
+    assert_eq!(
+        discord.fetch_user(1).await.unwrap().avatar_url,
+        "a",
+        "Fetches within a step should agree"
+    );
+    discord.advance();
+    assert!(discord.fetch_user(1).await.is_err());
+    discord.advance();
+    assert_eq!(discord.fetch_user(1).await.unwrap().avatar_url, "b");
+    discord.advance();
+    assert_eq!(
+        discord.fetch_user(1).await.unwrap().avatar_url,
+        "b",
+        "The last response should be repeated"
+    );
+    assert_eq!(discord.user_fetches(1), 5);
+}
+
+#[tokio::test]
+async fn test_unscripted_requests_fail() {
+    let discord = ScriptedDiscord::new();
+
+    assert!(discord.fetch_user(1).await.is_err());
+    assert!(discord.fetch_guild(1).await.is_err());
+    assert!(discord.fetch_image("https://cdn.test/a.png").await.is_err());
+}
+
+#[tokio::test]
+async fn test_new_avatar_and_username_are_recorded() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+
+    let discord = ScriptedDiscord::new();
+    discord.push_user(user(1, "https://cdn.test/a.png", Some("alice")));
+    discord.set_image("https://cdn.test/a.png", vec![1, 2, 3]);
+    let store = FakeImageStore::default();
+
+    assert!(update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await);
+
+    let pictures = history::profile_pictures(&pool, 1).await.unwrap();
+    assert_eq!(pictures.len(), 1);
+    assert!(pictures[0]
+        .link
+        .as_deref()
+        .unwrap()
+        .starts_with("https://images.test/pfp_1_"));
+
+    let usernames = history::usernames(&pool, 1).await.unwrap();
+    assert_eq!(usernames.len(), 1);
+    assert_eq!(usernames[0].username, "alice");
+    let normalized_name = sqlx::query_scalar!("SELECT normalizedName FROM UsernameChange")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        normalized_name.as_deref(),
+        Some("alice"),
+        "Stored for the look-alike name lookup"
+    );
+
+    let status = check_status::user_status(&pool, 1).await.unwrap().unwrap();
+    assert_eq!(status.consecutive_failures, 0);
+    assert!(status.last_success_at.is_some());
+
+    let runs = update_runs::latest_runs(&pool).await.unwrap();
+    assert_eq!(runs.len(), 2, "Profile picture and username passes");
+    assert!(runs.iter().all(|run| run.finished_at.is_some()));
+}
+
+#[tokio::test]
+async fn test_unchanged_avatar_is_archived_once() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+
+    let discord = ScriptedDiscord::new();
+    discord.push_user(user(1, "https://cdn.test/a.png", None));
+    discord.set_image("https://cdn.test/a.png", vec![1, 2, 3]);
+    let store = FakeImageStore::default();
+
+    update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+    update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+
+    assert_eq!(history::profile_pictures(&pool, 1).await.unwrap().len(), 1);
+    assert_eq!(store.uploads.lock().unwrap().len(), 1);
+    assert!(
+        history::usernames(&pool, 1).await.unwrap().is_empty(),
+        "Users without a display name have no username history"
+    );
+}
+
+#[tokio::test]
+async fn test_avatar_changes_twice_then_reverts() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+
+    let discord = ScriptedDiscord::new();
+    discord.push_user(user(1, "https://cdn.test/a.png", None));
+    discord.push_user(user(1, "https://cdn.test/b.png", None));
+    discord.push_user(user(1, "https://cdn.test/a.png", None));
+    discord.set_image("https://cdn.test/a.png", vec![1, 1]);
+    discord.set_image("https://cdn.test/b.png", vec![2, 2]);
+    let store = FakeImageStore::default();
+
+    for _ in 0..3 {
+        update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+        age_history(&pool).await;
+        discord.advance();
+    }
+
+    let pictures = history::profile_pictures(&pool, 1).await.unwrap();
+    assert_eq!(pictures.len(), 3);
+    assert_ne!(pictures[0].checksum, pictures[1].checksum);
+    assert_eq!(pictures[0].checksum, pictures[2].checksum);
+    assert_eq!(
+        pictures[2].link, pictures[0].link,
+        "Reverted picture should reuse the link of its first archive"
+    );
+    assert_eq!(
+        store.uploads.lock().unwrap().len(),
+        2,
+        "Reverted picture should not be uploaded again"
+    );
+
+    let changes = sqlx::query_scalar!(
+        "SELECT SUM(changes) FROM UpdateRun WHERE entityType = 'profile picture'"
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(changes, Some(3), "Archives and reverts count as changes");
+}
+
+#[tokio::test]
+async fn test_deleted_user_is_backed_off() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+
+    let discord = ScriptedDiscord::new();
+    discord.push_user_error(1);
+    let store = FakeImageStore::default();
+
+    // The first failure is retried on the next pass, the second one is backed off
+    update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+    update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+    let fetches = discord.user_fetches(1);
+    update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await;
+
+    assert_eq!(
+        discord.user_fetches(1),
+        fetches,
+        "Backed off user should not be fetched"
+    );
+
+    let status = check_status::user_status(&pool, 1).await.unwrap().unwrap();
+    assert_eq!(status.consecutive_failures, 2);
+    assert!(status.last_error.unwrap().contains("Unknown User"));
+    assert!(status.next_check_at.is_some());
+}
+
+#[tokio::test]
+async fn test_check_now_reports_the_outcome() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+
+    let discord = ScriptedDiscord::new();
+    let store = FakeImageStore::default();
+
+    assert!(matches!(
+        check_user_now(&discord, &pool, &store, 1).await,
+        CheckOutcome::Failed("discord", _)
+    ));
+
+    discord.push_user(user(1, "https://cdn.test/a.png", Some("Alice")));
+    discord.set_image("https://cdn.test/a.png", vec![1, 2, 3]);
+
+    assert_eq!(
+        check_user_now(&discord, &pool, &store, 1).await,
+        CheckOutcome::Archived
+    );
+}
+
+#[tokio::test]
+async fn test_server_icons() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_server(&pool, 100).await;
+    monitor_server(&pool, 200).await;
+
+    let discord = ScriptedDiscord::new();
+    discord.push_guild(DiscordGuild {
+        id: 100,
+        name: "With icon".to_string(),
+        icon_url: Some("https://cdn.test/icon.png".to_string()),
+    });
+    discord.push_guild(DiscordGuild {
+        id: 200,
+        name: "Without icon".to_string(),
+        icon_url: None,
+    });
+    discord.set_image("https://cdn.test/icon.png", vec![9, 9]);
+    let store = FakeImageStore::default();
+
+    update_monitored_servers(&discord, &pool, &store, RunTrigger::Schedule).await;
+
+    let icons = history::server_icons(&pool, 100).await.unwrap();
+    assert_eq!(icons.len(), 1);
+    assert!(history::server_icons(&pool, 200).await.unwrap().is_empty());
+
+    let status = check_status::server_status(&pool, 200)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status.consecutive_failures, 0, "An icon is optional");
+    assert!(status.last_success_at.is_some());
+}
+
+#[tokio::test]
+async fn test_pass_without_database_is_not_successful() {
+    let (pool, _temp_dir) = create_test_db().await;
+    monitor_user(&pool, 1).await;
+    monitor_server(&pool, 100).await;
+    pool.close().await;
+
+    let discord = ScriptedDiscord::new();
+    let store = FakeImageStore::default();
+
+    assert!(!update_monitored_users(&discord, &pool, &store, RunTrigger::Schedule).await);
+    assert!(!update_monitored_servers(&discord, &pool, &store, RunTrigger::Schedule).await);
+}
+
+#[tokio::test]
+async fn test_pass_that_cannot_load_entities_records_no_run() {
+    let (pool, _temp_dir) = create_test_db().await;
+    sqlx::query("DROP TABLE ServerPicture")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DROP TABLE Server")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let discord = ScriptedDiscord::new();
+    let store = FakeImageStore::default();
+
+    assert!(!update_monitored_servers(&discord, &pool, &store, RunTrigger::Schedule).await);
+    assert!(update_runs::latest_runs(&pool).await.unwrap().is_empty());
+}
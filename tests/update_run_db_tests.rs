@@ -0,0 +1,119 @@
+// ABOUTME: Integration tests for the UpdateRun table behind /botstatus
+// ABOUTME: Tests that passes are recorded, summarized per entity type, and pruned after 30 days
+mod common;
+
+use common::create_test_db;
+use pfp_checker::util::update_runs::{self, RunCounts, RunTrigger};
+use sqlx::SqlitePool;
+
+/// Helper function to record a pass, finished unless `finished_at` is `None`
+async fn insert_run(
+    pool: &SqlitePool,
+    entity_type: &str,
+    started_at: i64,
+    finished_at: Option<i64>,
+    checked: i64,
+    failures: i64,
+) {
+    let run_id = update_runs::start_run(pool, entity_type, RunTrigger::Schedule, started_at)
+        .await
+        .unwrap();
+
+    if let Some(finished_at) = finished_at {
+        let counts = RunCounts {
+            checked,
+            changes: 0,
+            failures,
+        };
+        update_runs::finish_run(pool, run_id, finished_at, counts)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_latest_run_per_entity_type() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_run(&pool, "profile picture", 1000, Some(1010), 5, 0).await;
+    insert_run(&pool, "server icon", 1020, Some(1025), 2, 0).await;
+    insert_run(&pool, "profile picture", 2800, None, 0, 0).await;
+
+    let latest = update_runs::latest_runs(&pool).await.unwrap();
+
+    assert_eq!(latest.len(), 2);
+    assert_eq!(latest[0].entity_type, "profile picture");
+    assert_eq!(latest[0].started_at, 2800);
+    assert_eq!(
+        latest[0].finished_at, None,
+        "Running pass should be reported as unfinished"
+    );
+    assert_eq!(latest[1].finished_at, Some(1025));
+}
+
+#[tokio::test]
+async fn test_stats_skip_unfinished_and_old_runs() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_run(&pool, "profile picture", 100, Some(200), 10, 10).await;
+    insert_run(&pool, "profile picture", 1000, Some(1010), 10, 1).await;
+    insert_run(&pool, "profile picture", 2000, Some(2030), 10, 0).await;
+    insert_run(&pool, "profile picture", 3000, None, 0, 0).await;
+
+    let stats = update_runs::run_stats_since(&pool, 500).await.unwrap();
+
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].runs, 2);
+    assert_eq!(stats[0].average_duration_secs, 20.0);
+    assert_eq!(stats[0].checked, 20);
+    assert_eq!(stats[0].failures, 1);
+    assert_eq!(stats[0].failure_rate(), 0.05);
+}
+
+#[tokio::test]
+async fn test_last_scheduled_start() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_run(&pool, "server icon", 1000, Some(1010), 1, 0).await;
+    update_runs::start_run(&pool, "server icon", RunTrigger::Command, 2000)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        update_runs::last_scheduled_start(&pool).await.unwrap(),
+        Some(1000),
+        "Passes started by commands should not move the schedule"
+    );
+}
+
+#[tokio::test]
+async fn test_prune_old_runs() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    let now = 100 * 24 * 60 * 60_i64;
+    insert_run(&pool, "username", now - 31 * 24 * 60 * 60, Some(now), 1, 0).await;
+    insert_run(&pool, "username", now - 60, Some(now), 1, 0).await;
+
+    let pruned = update_runs::prune_runs(&pool, now).await.unwrap();
+    assert_eq!(pruned, 1);
+
+    let remaining = sqlx::query_scalar!("SELECT COUNT(*) FROM UpdateRun")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 1, "Only runs of the last 30 days should remain");
+}
+
+#[tokio::test]
+async fn test_database_size_query() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    let bytes = sqlx::query_scalar::<_, i64>(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    assert!(bytes > 0);
+}
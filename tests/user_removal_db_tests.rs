@@ -0,0 +1,115 @@
+// ABOUTME: Integration tests for soft-deleting monitored users
+// ABOUTME: Tests that removed users stop being polled, keep their history, and are purged after the grace period
+mod common;
+
+use chrono::Utc;
+use common::discord::ScriptedDiscord;
+use common::{create_test_db, FakeImageStore};
+use pfp_checker::util::chron_update::update_monitored_users;
+use pfp_checker::util::discord::DiscordUser;
+use pfp_checker::util::retention;
+use pfp_checker::util::update_runs::RunTrigger;
+use sqlx::SqlitePool;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Helper function to insert a user with one profile picture
+async fn insert_user(pool: &SqlitePool, user_id: i64, removed_at: Option<i64>) {
+    sqlx::query!(
+        "INSERT INTO User (discordId, trackedSince, removedAt) VALUES (?, ?, ?)",
+        user_id,
+        1000_i64,
+        removed_at
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let link = format!("https://i.ibb.co/abc/pfp_{}.png", user_id);
+    sqlx::query!(
+        "INSERT INTO ProfilePicture (checksum, userId, changedAt, link) VALUES (?, ?, ?, ?)",
+        "abc",
+        user_id,
+        1000_i64,
+        link
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_removed_users_are_not_polled() {
+    let (pool, _temp_dir) = create_test_db().await;
+
+    insert_user(&pool, 1, None).await;
+    insert_user(&pool, 2, Some(5000)).await;
+
+    let discord = ScriptedDiscord::new();
+    for id in [1, 2] {
+        discord.push_user(DiscordUser {
+            id,
+            avatar_url: "https://cdn.test/a.png".to_string(),
+            global_name: None,
+        });
+    }
+    discord.set_image("https://cdn.test/a.png", vec![1, 2, 3]);
+
+    update_monitored_users(
+        &discord,
+        &pool,
+        &FakeImageStore::default(),
+        RunTrigger::Schedule,
+    )
+    .await;
+
+    assert!(discord.user_fetches(1) > 0, "Active users should be polled");
+    assert_eq!(
+        discord.user_fetches(2),
+        0,
+        "Removed users should not be polled"
+    );
+
+    let history = sqlx::query_scalar!("SELECT COUNT(*) FROM ProfilePicture WHERE userId = 2")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(history, 1, "History of removed users should be kept");
+
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_purge_only_removes_users_past_grace_period() {
+    let (pool, _temp_dir) = create_test_db().await;
+    let store = FakeImageStore::default();
+
+    let now = Utc::now().timestamp();
+    insert_user(&pool, 1, None).await;
+    insert_user(&pool, 2, Some(now - 40 * DAY)).await;
+    insert_user(&pool, 3, Some(now - DAY)).await;
+
+    let report = retention::purge_removed_users(&pool, &store, 30)
+        .await
+        .unwrap();
+
+    assert_eq!(report.profile_pictures, 1);
+    assert_eq!(
+        report.images, 0,
+        "The checksum is still used by users 1 and 3"
+    );
+
+    let remaining = sqlx::query_scalar!("SELECT discordId FROM User ORDER BY discordId")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, vec![1, 3]);
+
+    let orphaned = sqlx::query_scalar!("SELECT COUNT(*) FROM ProfilePicture WHERE userId = 2")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(orphaned, 0, "Purged user's history should cascade");
+
+    pool.close().await;
+}